@@ -0,0 +1,124 @@
+//! Measures tokenizer and tape throughput on a large generated save file.
+//!
+//! Run with `cargo bench --bench tokenizer`. The size of the save in megabytes can be set with the
+//! `CLAUSER_BENCH_MB` environment variable, and defaults to 100.
+
+use std::{hint::black_box, io::Cursor, time::Instant};
+
+use clauser::{dialect::Dialect, encoding::Encoding, tape::Tape, tokenizer::Tokenizer};
+
+const RUNS: usize = 5;
+
+/// Generates a save shaped like an EU4 game state, with nested objects, dates, numbers, quoted
+/// strings, and the occasional comment.
+fn generate_save(target_len: usize) -> String {
+    let mut text = String::with_capacity(target_len + 4096);
+    text.push_str("date=1444.11.11\nplayer=\"FRA\"\n# generated for benchmarking\n");
+
+    let mut i: u64 = 0;
+    while text.len() < target_len {
+        i += 1;
+        text.push_str(&format!(
+            "country_{i}={{\n\
+             \thuman=no\n\
+             \thas_set_government_name=yes\n\
+             \tname=\"Kingdom of Förstadt {i}\"\n\
+             \tcapital={}\n\
+             \ttreasury={}.{:03}\n\
+             \testimated_monthly_income=-{}.250\n\
+             \tlast_election=1{:03}.{}.{}\n\
+             \tflags={{\n\
+             \t\tflavor_fra.{}=1444.11.12\n\
+             \t\tscope:target.owner=yes # the owner of the target\n\
+             \t}}\n\
+             \tmodifier=\"gfx/interface/ideas_EU4/idea_{}.dds\"\n\
+             \tunit={{ id={} type=54 }}\n\
+             \tcolor={{ {} {} {} }}\n\
+             \thistory={{\n\
+             \t\t1500.1.1={{ monarch={{ name=\"Louis \\\"the Great\\\"\" ADM=3 DIP=4 MIL=5 }} }}\n\
+             \t}}\n\
+             }}\n",
+            i % 2000,
+            i * 37 % 10000,
+            i % 1000,
+            i % 50,
+            i % 1000,
+            1 + i % 12,
+            1 + i % 28,
+            100 + i % 900,
+            i % 40,
+            i,
+            i * 7 % 256,
+            i * 13 % 256,
+            i * 31 % 256,
+        ));
+    }
+
+    text
+}
+
+/// Reads every token from `tokenizer`, returning how many there were.
+fn count_tokens(mut tokenizer: Tokenizer) -> usize {
+    let mut count = 0;
+    while let Some(token) = tokenizer.next().expect("generated save should tokenize") {
+        black_box(token);
+        count += 1;
+    }
+
+    count
+}
+
+fn main() {
+    let megabytes: usize = std::env::var("CLAUSER_BENCH_MB")
+        .ok()
+        .and_then(|mb| mb.parse().ok())
+        .unwrap_or(100);
+
+    let text = generate_save(megabytes * 1024 * 1024);
+    let size = text.len() as f64 / (1024.0 * 1024.0);
+    println!("tokenizing {:.1} MB save, best of {} runs", size, RUNS);
+
+    for (comments, streamed) in [(false, false), (true, false), (false, true)] {
+        let mut best = f64::MAX;
+        let mut count = 0;
+
+        for _ in 0..RUNS {
+            let bytes = Cursor::new(text.clone().into_bytes());
+            let start = Instant::now();
+            let tokenizer = match streamed {
+                true => Tokenizer::from_read(bytes, Encoding::Utf8),
+                false => Tokenizer::new(&text),
+            };
+            count = count_tokens(tokenizer.with_dialect(Dialect::EU4).with_comments(comments));
+
+            best = f64::min(best, start.elapsed().as_secs_f64());
+        }
+
+        println!(
+            "comments {:<5} streamed {:<5}: {} tokens in {:.3}s ({:.1} MB/s)",
+            comments,
+            streamed,
+            count,
+            best,
+            size / best
+        );
+    }
+
+    let mut best = f64::MAX;
+    let mut count = 0;
+    for _ in 0..RUNS {
+        let start = Instant::now();
+        let tape = Tape::from_tokenizer(Tokenizer::new(&text).with_dialect(Dialect::EU4))
+            .expect("generated save should build a tape");
+        count = tape.tokens().len();
+        best = f64::min(best, start.elapsed().as_secs_f64());
+        black_box(tape);
+    }
+
+    println!(
+        "tape                        : {} tokens in {:.3}s ({:.1} MB/s)",
+        count,
+        best,
+        size / best
+    );
+}
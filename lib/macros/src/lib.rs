@@ -293,11 +293,11 @@ fn visitor_for_struct(item: &DeriveInput, s: &DataStruct) -> Result<TokenStream,
 
                 #declarations
 
-                while let Some(key) = map.next_key::<&str>()? {
-                    match key {
+                while let Some(clauser::de::FieldKey(key)) = map.next_key()? {
+                    match key.as_ref() {
                         #(#normal_match_arms),*
                         #(#duplicate_match_arms),*
-                        _ => Err(serde::de::Error::unknown_field(key, &#name::#names_const_label)),
+                        _ => Err(serde::de::Error::unknown_field(&key, &#name::#names_const_label)),
                     }?;
                 }
 
@@ -0,0 +1,107 @@
+use std::borrow::Cow;
+
+use crate::source::Span;
+
+/// A comment read from a source file, such as `# text`.
+///
+/// Comments are only kept if enabled with [Tokenizer::with_comments](`crate::tokenizer::Tokenizer::with_comments`)
+/// or [Reader::with_comments](`crate::reader::Reader::with_comments`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Comment<'a> {
+    /// The full text of the comment, including the leading `#`.
+    pub text: Cow<'a, str>,
+    /// The span of source text the comment covers.
+    pub span: Span,
+}
+
+impl<'a> Comment<'a> {
+    /// Creates a new [Comment] from its full text and span.
+    pub fn new(text: impl Into<Cow<'a, str>>, span: Span) -> Comment<'a> {
+        Comment {
+            text: text.into(),
+            span,
+        }
+    }
+
+    /// Returns the number of `#` characters at the start of the comment.
+    ///
+    /// Tools like CWTools use this to tell kinds of comments apart, with `##` marking
+    /// annotations such as `## cardinality = 0..1` and `###` marking documentation.
+    pub fn level(&self) -> usize {
+        self.text.chars().take_while(|c| *c == '#').count()
+    }
+
+    /// Returns the contents of the comment, without the leading `#` characters or surrounding whitespace.
+    pub fn content(&self) -> &str {
+        self.text.trim_start_matches('#').trim()
+    }
+
+    /// Converts this comment into one that owns its text.
+    pub fn into_owned(self) -> Comment<'static> {
+        Comment {
+            text: Cow::Owned(self.text.into_owned()),
+            span: self.span,
+        }
+    }
+}
+
+/// The comments attached to a value and its entries, created by
+/// [Value::from_reader_with_comments](`crate::value::Value::from_reader_with_comments`).
+///
+/// A [Comments] tree has the same shape as the [Value](`crate::value::Value`) it was read with.
+/// If the value is an object or an array, [Comments::entries] has one element for each of its
+/// entries or items, in the same order.
+///
+/// Comments are attached to entries based on where they appear:
+/// - A comment on the same line as the end of an entry is that entry's trailing comment.
+/// - Other comments are leading comments of the next entry in the same object or array.
+/// - Comments after the last entry of an object or array are dangling.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Comments<'a> {
+    /// The comments on the lines before this entry.
+    pub leading: Vec<Comment<'a>>,
+    /// The comment after this entry on the same line, if any.
+    pub trailing: Option<Comment<'a>>,
+    /// The comments attached to each entry of this value, if it's an object or array.
+    pub entries: Vec<Comments<'a>>,
+    /// The comments after the last entry of this value, if it's an object or array.
+    pub dangling: Vec<Comment<'a>>,
+}
+
+impl<'a> Comments<'a> {
+    /// Returns the comments attached to the entry at `index`, if any.
+    pub fn entry(&self, index: usize) -> Option<&Comments<'a>> {
+        self.entries.get(index)
+    }
+
+    /// Returns true if neither this entry nor any of its entries have comments.
+    pub fn is_empty(&self) -> bool {
+        self.leading.is_empty()
+            && self.trailing.is_none()
+            && self.dangling.is_empty()
+            && self.entries.iter().all(Comments::is_empty)
+    }
+
+    /// Attaches comments found inside a collection to its entries, given the end position of each entry.
+    pub(crate) fn attach(
+        &mut self,
+        comments: Vec<Comment<'a>>,
+        entry_ends: &[usize],
+        same_line: impl Fn(usize, usize) -> bool,
+    ) {
+        for comment in comments {
+            let index = entry_ends.partition_point(|end| *end <= comment.span.start);
+
+            if index > 0
+                && self.entries[index - 1].trailing.is_none()
+                && same_line(entry_ends[index - 1], comment.span.start)
+            {
+                self.entries[index - 1].trailing = Some(comment);
+            } else if index < self.entries.len() {
+                self.entries[index].leading.push(comment);
+            } else {
+                self.dangling.push(comment);
+            }
+        }
+    }
+}
@@ -1,19 +1,83 @@
+use serde::de::value::{SeqAccessDeserializer, SeqDeserializer};
+use serde::de::DeserializeOwned;
 use serde::de::{
     self, DeserializeSeed, EnumAccess, IntoDeserializer, MapAccess, SeqAccess, VariantAccess,
     Visitor,
 };
 use serde::Deserialize;
-
-use crate::reader::Reader;
-use crate::token::TokenType;
-use crate::types::{CollectionType, RealType};
+use std::borrow::Cow;
+use std::io::Read;
+use std::path::Path;
+
+use crate::dialect::Dialect;
+use crate::encoding::Encoding;
+use crate::limits::Limits;
+use crate::reader::{Entry, Reader};
+use crate::template::TEMPLATE_STRUCT_NAME;
+use crate::types::{
+    CollectionType, Color, ObjectKey, Operator, RealType, COLOR_FIELDS, COLOR_STRUCT_NAME,
+    COMPARISON_STRUCT_NAME,
+};
 use crate::util::error::{Error, ErrorType, ParseCompleteResult};
+use crate::variables::Variables;
 
 type Result<T> = ParseCompleteResult<T>;
 
 #[cfg(feature = "macros")]
 extern crate clauser_macros;
 
+/// The name of a struct field that collects the bare values of a collection that also has
+/// properties, such as the `1 2 3` in `{ 1 2 3 a = b }` or the `flag` in `{ key = 1 flag }`.
+///
+/// Use `#[serde(rename = "$items")]` on a sequence field to collect them. The items keep their
+/// order, and are read after the rest of the collection, so they can be anywhere in it.
+///
+/// ```
+/// use serde::Deserialize;
+///
+/// #[derive(Deserialize)]
+/// struct Modifier {
+///     #[serde(rename = "$items")]
+///     flags: Vec<String>,
+///     value: i32,
+/// }
+///
+/// let modifier: Modifier = clauser::de::from_str("hidden value = 5 global")?;
+/// assert_eq!(modifier.flags, vec!["hidden", "global"]);
+/// assert_eq!(modifier.value, 5);
+/// # Ok::<(), clauser::error::Error>(())
+/// ```
+pub const ITEMS_FIELD: &str = "$items";
+
+/// The deepest that collections can be nested when deserializing or reading a
+/// [Value](`crate::value::Value`), whatever [Limits] are set.
+///
+/// Deserializing recurses once for every nested collection, and so do dropping and formatting a
+/// [Value](`crate::value::Value`), so deeper input is rejected with a
+/// [LimitError](`crate::error::ErrorType::LimitError`) rather than risking overflowing the stack.
+pub const MAX_DEPTH: usize = 128;
+
+/// How a map reads the bare values of a collection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Items {
+    /// Bare values are an error, for structs without an [ITEMS_FIELD].
+    Forbidden,
+    /// Bare values are returned under an [ITEMS_FIELD] key if there are any, for maps.
+    IfAny,
+    /// Bare values are always returned under an [ITEMS_FIELD] key, for structs that have one.
+    Always,
+}
+
+impl Items {
+    /// Returns how a struct with the given fields reads bare values.
+    pub(crate) fn for_struct(fields: &[&str]) -> Items {
+        match fields.contains(&ITEMS_FIELD) {
+            true => Items::Always,
+            false => Items::Forbidden,
+        }
+    }
+}
+
 /// A [serde] deserializer for Clausewitz source files.
 ///
 /// Deserializing numbers, booleans, strings, identifiers, dates, objects, and arrays is supported.
@@ -23,22 +87,142 @@ extern crate clauser_macros;
 /// though because they aren't valid Rust identifiers, they can only be deserialized as maps with a key
 /// of [Date](`crate::types::Date`) (such as a `HashMap<Date, T>`).
 ///
+/// Strings are borrowed from the input where possible. Strings containing escape sequences
+/// (`\"`, `\\`, `\n` and `\t`) have to be copied, so they can't be deserialized as `&str`.
+///
+/// Object keys can be identifiers, dates, integers, quoted strings, or booleans. Integer keys
+/// can be deserialized as numbers or strings (such as a `HashMap<i32, T>` or a `HashMap<String, T>`),
+/// and every other kind of key can be deserialized as a string.
+///
+/// Properties can use operators other than `=`, such as `age > 30`. The operator is ignored
+/// unless the value is deserialized as a [Comparison](`crate::types::Comparison`).
+///
+/// Scripted variable definitions (`@cost = 100`) are skipped, and references to them (`@cost`)
+/// are deserialized as the variable's value. Inline math expressions (`@[ cost * 2 ]`) are
+/// deserialized as the number they evaluate to.
+///
+/// Blocks containing parameters (`$PARAM$`) or conditional blocks (`[[PARAM] ... ]`) can't be
+/// deserialized as objects or arrays until they're expanded. They can be deserialized as a
+/// [Template](`crate::template::Template`) instead, which keeps the text of the block as-is.
+///
+/// Color blocks such as `rgb { 255 128 0 }` can be deserialized as a [Color](`crate::types::Color`),
+/// or converted to RGB and deserialized as a `[u8; 3]`.
+///
+/// Collections that mix properties and bare values, like `{ 1 2 3 a = b }`, can be deserialized
+/// as structs with an [ITEMS_FIELD] to collect the bare values into. Maps and self-describing
+/// types, such as a `HashMap`, get the bare values as a sequence under an [ITEMS_FIELD] key.
+///
+/// Deserializing recurses once for every nested collection, so collections can't be nested more
+/// than [MAX_DEPTH] deep. Input that can't be trusted should also be read with [Limits], such as
+/// [Limits::UNTRUSTED], using [Deserializer::with_limits].
+///
 /// Clausewitz files can contain duplicate keys.
 /// By default, these will be deserialized with the last key's value replacing the rest.
 /// If you need all the values of these duplicate keys, use the [duplicate_keys](`clauser_macros::duplicate_keys`) macro.
 pub struct Deserializer<'de> {
     reader: Reader<'de>,
     started_base_struct: bool,
+    /// The operator of the property currently being deserialized, if any.
+    operator: Option<Operator>,
 }
 
 impl<'de> Deserializer<'de> {
     /// Creates a new [Deserializer] to deserialize the input string.
     pub fn from_str(input: &'de str) -> Self {
+        Deserializer::from_reader(Reader::new(input))
+    }
+
+    /// Creates a new [Deserializer] to deserialize raw bytes in the given [Encoding].
+    ///
+    /// If the input has to be transcoded, strings can't be borrowed from it, so
+    /// deserializing into types like `&str` will fail.
+    pub fn from_bytes(input: &'de [u8], encoding: Encoding) -> Result<Self> {
+        Ok(Deserializer::from_reader(Reader::from_bytes(
+            input, encoding,
+        )?))
+    }
+
+    /// Creates a new [Deserializer] that reads its input from `read` in the given [Encoding],
+    /// a chunk at a time.
+    ///
+    /// Memory use doesn't depend on the size of the input, but strings can't be borrowed from it,
+    /// so deserializing into types like `&str` will fail. See [Reader::from_read] for more information.
+    pub fn from_read(read: impl Read + Send + 'static, encoding: Encoding) -> Self {
+        Deserializer::from_reader(Reader::from_read(read, encoding))
+    }
+
+    /// Creates a new [Deserializer] that reads the file at `path` in the given [Encoding].
+    ///
+    /// Strings can't be borrowed from the file, so deserializing into types like `&str` will fail.
+    /// See [Reader::from_path] for more information.
+    pub fn from_path(path: impl AsRef<Path>, encoding: Encoding) -> Result<Self> {
+        Ok(Deserializer::from_reader(Reader::from_path(
+            path, encoding,
+        )?))
+    }
+
+    /// Creates a new [Deserializer] that reads its input from the given [Reader].
+    ///
+    /// This can be used to deserialize from a [Reader] with custom settings, such as one created by
+    /// [SaveFile::reader](`crate::save::SaveFile::reader`) or [SourceMap::reader](`crate::source::SourceMap::reader`).
+    /// The reader's [Limits::max_depth] is lowered to [MAX_DEPTH] if it's higher.
+    pub fn from_reader(reader: Reader<'de>) -> Self {
+        let limits = capped(*reader.limits());
         Deserializer {
-            reader: Reader::new(&input),
+            reader: reader.with_limits(limits),
             started_base_struct: false,
+            operator: None,
+        }
+    }
+
+    /// Reads an object as a map, collecting its bare values into an [ITEMS_FIELD] as `items` says.
+    fn read_map<'a, V>(&'a mut self, visitor: V, items: Items) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        let had_started = self.started_base_struct;
+        if self.started_base_struct {
+            self.reader.begin_collection()?;
+        } else {
+            self.started_base_struct = true;
+        }
+
+        let value = visitor.visit_map(MapAccessor::new(self, items))?;
+
+        if had_started {
+            self.reader.end_collection()?;
         }
+
+        Ok(value)
     }
+
+    /// Sets the [Dialect] used to read the input.
+    pub fn with_dialect(mut self, dialect: Dialect) -> Self {
+        self.reader = self.reader.with_dialect(dialect);
+        self
+    }
+
+    /// Sets the table of scripted variables that references in the input can be resolved from.
+    ///
+    /// See [Reader::with_variables] for more information.
+    pub fn with_variables(mut self, variables: Variables) -> Self {
+        self.reader = self.reader.with_variables(variables);
+        self
+    }
+
+    /// Sets the [Limits] on the size of the input.
+    ///
+    /// Deserializing recurses once for every nested collection, so [Limits::max_depth] is lowered
+    /// to [MAX_DEPTH] if it's higher.
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.reader = self.reader.with_limits(capped(limits));
+        self
+    }
+}
+
+/// Lowers the [Limits::max_depth] of `limits` to [MAX_DEPTH] if it's higher.
+pub(crate) fn capped(limits: Limits) -> Limits {
+    limits.with_max_depth(limits.max_depth.min(MAX_DEPTH))
 }
 
 /// Attempts to deserialize the input string into `T`.
@@ -46,7 +230,55 @@ pub fn from_str<'a, T>(s: &'a str) -> Result<T>
 where
     T: Deserialize<'a>,
 {
-    let mut deserializer = Deserializer::from_str(s);
+    from_deserializer(Deserializer::from_str(s))
+}
+
+/// Attempts to deserialize the input string into `T`, using the syntax of the given [Dialect].
+pub fn from_str_with_dialect<'a, T>(s: &'a str, dialect: Dialect) -> Result<T>
+where
+    T: Deserialize<'a>,
+{
+    from_deserializer(Deserializer::from_str(s).with_dialect(dialect))
+}
+
+/// Attempts to deserialize raw bytes in the given [Encoding] into `T`.
+///
+/// See [Deserializer::from_bytes] for more information.
+pub fn from_bytes<'a, T>(bytes: &'a [u8], encoding: Encoding) -> Result<T>
+where
+    T: Deserialize<'a>,
+{
+    from_deserializer(Deserializer::from_bytes(bytes, encoding)?)
+}
+
+/// Attempts to deserialize `T` from a stream in the given [Encoding], reading it a chunk at a time.
+///
+/// See [Deserializer::from_read] for more information.
+pub fn from_read<T>(read: impl Read + Send + 'static, encoding: Encoding) -> Result<T>
+where
+    T: DeserializeOwned,
+{
+    from_deserializer(Deserializer::from_read(read, encoding))
+}
+
+/// Attempts to deserialize `T` from the file at `path` in the given [Encoding].
+///
+/// Any [Error] records the path of the file. See [Deserializer::from_path] for more information.
+pub fn from_path<T>(path: impl AsRef<Path>, encoding: Encoding) -> Result<T>
+where
+    T: DeserializeOwned,
+{
+    let path = path.as_ref();
+    from_deserializer(Deserializer::from_path(path, encoding)?).map_err(|e| e.with_path(path))
+}
+
+/// Attempts to deserialize `T` using the given [Deserializer].
+///
+/// Unlike calling `T::deserialize` directly, this adds line context to any errors.
+pub fn from_deserializer<'a, T>(mut deserializer: Deserializer<'a>) -> Result<T>
+where
+    T: Deserialize<'a>,
+{
     let result = T::deserialize(&mut deserializer);
     match result {
         Ok(t) => Ok(t),
@@ -80,18 +312,23 @@ impl<'de> de::Deserializer<'de> for &mut Deserializer<'de> {
             RealType::Number => {
                 let number = self.reader.read_number_as_str()?;
                 match number.contains(".") {
-                    true => visitor.visit_f64(self.reader.parse_number(number)?),
-                    false => visitor.visit_i64(self.reader.parse_number(number)?),
+                    true => visitor.visit_f64(self.reader.parse_number(&number)?),
+                    false => visitor.visit_i64(self.reader.parse_number(&number)?),
                 }
             }
             RealType::Date => self.deserialize_u128(visitor),
             RealType::String => self.deserialize_string(visitor),
             RealType::Identifier => self.deserialize_identifier(visitor),
+            RealType::Color => visitor.visit_map(ColorAccessor::new(self.reader.read_color()?)),
             RealType::ObjectOrArray => match self.reader.try_discern_array_or_map()? {
-                Some(collection_type) => match collection_type {
-                    CollectionType::Array => self.deserialize_seq(visitor),
-                    CollectionType::Object => self.deserialize_map(visitor),
+                // a collection starting with bare values could still have properties later on
+                Some(CollectionType::Array) => match self.reader.discern_collection()? {
+                    Some(CollectionType::Mixed) => self.deserialize_map(visitor),
+                    _ => self.deserialize_seq(visitor),
                 },
+                Some(CollectionType::Object | CollectionType::Mixed) => {
+                    self.deserialize_map(visitor)
+                }
                 None => visitor.visit_none(),
             },
         }
@@ -192,7 +429,7 @@ impl<'de> de::Deserializer<'de> for &mut Deserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        visitor.visit_borrowed_str(self.reader.read_stringlike()?)
+        visit_cow_str(visitor, self.reader.read_stringlike()?)
     }
 
     fn deserialize_string<V>(self, visitor: V) -> Result<V::Value>
@@ -257,6 +494,8 @@ impl<'de> de::Deserializer<'de> for &mut Deserializer<'de> {
     where
         V: Visitor<'de>,
     {
+        // the operator of the property belongs to the array, not its items
+        self.operator = None;
         self.reader.begin_collection()?;
         let value = visitor.visit_seq(ArrayAccessor::new(self))?;
         self.reader.end_collection()?;
@@ -268,18 +507,29 @@ impl<'de> de::Deserializer<'de> for &mut Deserializer<'de> {
     where
         V: Visitor<'de>,
     {
+        // colors can be read as RGB arrays, like [u8; 3]
+        if self.reader.peek_next_type()? == Some(RealType::Color) {
+            let rgb = self.reader.read_color()?.to_rgb();
+            return visitor.visit_seq(SeqDeserializer::new(rgb.into_iter()));
+        }
+
         self.deserialize_seq(visitor)
     }
 
     fn deserialize_tuple_struct<V>(
         self,
-        _name: &'static str,
+        name: &'static str,
         _len: usize,
         visitor: V,
     ) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
+        if name == COMPARISON_STRUCT_NAME {
+            let operator = self.operator.take().unwrap_or(Operator::Equals);
+            return visitor.visit_seq(ComparisonAccessor::new(self, operator));
+        }
+
         self.deserialize_seq(visitor)
     }
 
@@ -287,32 +537,31 @@ impl<'de> de::Deserializer<'de> for &mut Deserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        let had_started = self.started_base_struct;
-        if self.started_base_struct {
-            self.reader.begin_collection()?;
-        } else {
-            self.started_base_struct = true;
-        }
-
-        let value = visitor.visit_map(MapAccessor::new(self))?;
-
-        if had_started {
-            self.reader.end_collection()?;
-        }
-
-        Ok(value)
+        self.read_map(visitor, Items::IfAny)
     }
 
     fn deserialize_struct<V>(
         self,
-        _name: &'static str,
-        _fields: &'static [&'static str],
+        name: &'static str,
+        fields: &'static [&'static str],
         visitor: V,
     ) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        self.deserialize_map(visitor)
+        if name == TEMPLATE_STRUCT_NAME {
+            return visit_cow_str(visitor, self.reader.read_template()?.into_text());
+        }
+
+        if name == COLOR_STRUCT_NAME {
+            // unprefixed colors are plain arrays of RGB components
+            return match self.reader.peek_next_type_expect()? {
+                RealType::Color => visitor.visit_map(ColorAccessor::new(self.reader.read_color()?)),
+                _ => self.deserialize_seq(visitor),
+            };
+        }
+
+        self.read_map(visitor, Items::for_struct(fields))
     }
 
     fn deserialize_enum<V>(
@@ -333,7 +582,7 @@ impl<'de> de::Deserializer<'de> for &mut Deserializer<'de> {
             RealType::String => {
                 let str = self.reader.peek_expected_string()?;
 
-                if variants.contains(&str) {
+                if variants.contains(&str.as_ref()) {
                     visitor.visit_enum(self.reader.read_string()?.into_deserializer())
                 } else {
                     visitor.visit_enum(EnumAccessor::new(self))
@@ -360,14 +609,72 @@ impl<'de> de::Deserializer<'de> for &mut Deserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        visitor.visit_borrowed_str(self.reader.read_identifier()?)
+        visit_cow_str(visitor, self.reader.read_identifier()?)
     }
 
     fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        self.deserialize_any(visitor)
+        if self.reader.peek_next_type()?.is_none() {
+            return visitor.visit_none();
+        }
+
+        self.reader.skip_value()?;
+        visitor.visit_unit()
+    }
+}
+
+/// Visits a string, borrowing it from the input if possible.
+pub(crate) fn visit_cow_str<'de, V>(visitor: V, str: Cow<'de, str>) -> Result<V::Value>
+where
+    V: Visitor<'de>,
+{
+    match str {
+        Cow::Borrowed(str) => visitor.visit_borrowed_str(str),
+        Cow::Owned(str) => visitor.visit_string(str),
+    }
+}
+
+/// An object key used by the [duplicate_keys](`clauser_macros::duplicate_keys`) macro, which is
+/// borrowed from the input unless the input was transcoded.
+#[doc(hidden)]
+pub struct FieldKey<'de>(pub Cow<'de, str>);
+
+impl<'de> Deserialize<'de> for FieldKey<'de> {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<FieldKey<'de>, D::Error>
+    where
+        D: de::Deserializer<'de>,
+    {
+        struct FieldKeyVisitor;
+
+        impl<'de> Visitor<'de> for FieldKeyVisitor {
+            type Value = FieldKey<'de>;
+
+            fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+                formatter.write_str("a field name")
+            }
+
+            fn visit_borrowed_str<E: de::Error>(
+                self,
+                v: &'de str,
+            ) -> std::result::Result<FieldKey<'de>, E> {
+                Ok(FieldKey(Cow::Borrowed(v)))
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> std::result::Result<FieldKey<'de>, E> {
+                Ok(FieldKey(Cow::Owned(v.to_owned())))
+            }
+
+            fn visit_string<E: de::Error>(
+                self,
+                v: String,
+            ) -> std::result::Result<FieldKey<'de>, E> {
+                Ok(FieldKey(Cow::Owned(v)))
+            }
+        }
+
+        deserializer.deserialize_identifier(FieldKeyVisitor)
     }
 }
 
@@ -382,11 +689,67 @@ impl<'a, 'de> ArrayAccessor<'a, 'de> {
 }
 struct MapAccessor<'a, 'de: 'a> {
     de: &'a mut Deserializer<'de>,
+    /// The position of each bare value of the object, if they're collected into an [ITEMS_FIELD].
+    items: Option<Vec<usize>>,
+    /// Whether to return an [ITEMS_FIELD] key even if the object has no bare values.
+    always_items: bool,
+    /// The bare values to read as the value of the [ITEMS_FIELD] key that was just returned.
+    pending_items: Option<Vec<usize>>,
 }
 
 impl<'a, 'de> MapAccessor<'a, 'de> {
-    fn new(de: &'a mut Deserializer<'de>) -> Self {
-        MapAccessor { de }
+    fn new(de: &'a mut Deserializer<'de>, items: Items) -> Self {
+        MapAccessor {
+            de,
+            items: (items != Items::Forbidden).then(Vec::new),
+            always_items: items == Items::Always,
+            pending_items: None,
+        }
+    }
+
+    /// Finds the next property of an object that can have bare values, saving where each
+    /// bare value starts and skipping over it.
+    fn next_key_with_items<K>(&mut self, seed: K) -> Result<Option<K::Value>>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        loop {
+            let position = self.de.reader.current_position();
+            match self.de.reader.next_entry()? {
+                Some(Entry::Property((key, _), operator)) => {
+                    let key = self.deserialize_key(seed, key, position)?;
+                    self.de.operator = Some(operator);
+                    return Ok(Some(key));
+                }
+                Some(Entry::Item) => {
+                    let position = self.de.reader.current_position();
+                    self.items.get_or_insert_with(Vec::new).push(position);
+                    self.de.reader.skip_value()?;
+                }
+                None => {
+                    let items = self.items.take().unwrap_or_default();
+                    if items.is_empty() && !self.always_items {
+                        return Ok(None);
+                    }
+
+                    // the items are read once every property has been
+                    self.pending_items = Some(items);
+                    return seed.deserialize(ITEMS_FIELD.into_deserializer()).map(Some);
+                }
+            }
+        }
+    }
+
+    /// Deserializes the key of a property, adding the position of the key to any error.
+    fn deserialize_key<K>(&self, seed: K, key: ObjectKey<'de>, position: usize) -> Result<K::Value>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        seed.deserialize(KeyDeserializer(key))
+            .map_err(|e| match e.has_context() {
+                true => e,
+                false => e.with_context(&self.de.reader, position),
+            })
     }
 }
 
@@ -413,13 +776,259 @@ impl<'de, 'a> MapAccess<'de> for MapAccessor<'a, 'de> {
     where
         K: DeserializeSeed<'de>,
     {
+        if self.items.is_some() {
+            return self.next_key_with_items(seed);
+        }
+
         match self.de.reader.is_collection_ended()? {
             true => Ok(None),
             false => {
-                let identifier = seed.deserialize(&mut *self.de)?;
-                self.de.reader.expect_token(TokenType::Equals)?;
-                Ok(Some(identifier))
+                let position = self.de.reader.current_position();
+                let key = self.de.reader.read_key()?;
+                let key = self.deserialize_key(seed, key, position)?;
+                self.de.operator = Some(self.de.reader.read_operator()?);
+                Ok(Some(key))
+            }
+        }
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        if let Some(items) = self.pending_items.take() {
+            // go back to read each item, then return to the end of the object, discarding the
+            // errors recovered from while skipping over the items again
+            let end = self.de.reader.current_position();
+            let diagnostics = self.de.reader.diagnostics().len();
+            let value = seed.deserialize(SeqAccessDeserializer::new(ItemsAccessor {
+                de: &mut *self.de,
+                items: items.into_iter(),
+            }));
+            self.de.reader.seek(end);
+            self.de.reader.truncate_diagnostics(diagnostics);
+            return value;
+        }
+
+        let value = seed.deserialize(&mut *self.de);
+        self.de.operator = None;
+        value
+    }
+}
+
+/// Reads the bare values of an object collected by [MapAccessor] as a sequence.
+struct ItemsAccessor<'a, 'de: 'a> {
+    de: &'a mut Deserializer<'de>,
+    items: std::vec::IntoIter<usize>,
+}
+
+impl<'de, 'a> SeqAccess<'de> for ItemsAccessor<'a, 'de> {
+    type Error = Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        match self.items.next() {
+            Some(position) => {
+                self.de.reader.seek(position);
+                seed.deserialize(&mut *self.de).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+/// Deserializes the [ObjectKey] of a property.
+pub(crate) struct KeyDeserializer<'de>(pub(crate) ObjectKey<'de>);
+
+impl<'de> KeyDeserializer<'de> {
+    /// Visits an integer key, parsing it from a string key if necessary.
+    fn visit_integer<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self.0 {
+            ObjectKey::Identifier(ref s) | ObjectKey::String(ref s) => match s.parse::<i64>() {
+                Ok(i) => visitor.visit_i64(i),
+                Err(_) => de::Deserializer::deserialize_any(self, visitor),
+            },
+            _ => de::Deserializer::deserialize_any(self, visitor),
+        }
+    }
+}
+
+impl<'de> de::Deserializer<'de> for KeyDeserializer<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self.0 {
+            ObjectKey::Identifier(s) | ObjectKey::String(s) => visit_cow_str(visitor, s),
+            ObjectKey::Date(d) => visitor.visit_u128(d.into()),
+            ObjectKey::Integer(i) => visitor.visit_i64(i),
+            ObjectKey::Boolean(b) => visitor.visit_bool(b),
+        }
+    }
+
+    fn deserialize_str<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self.0 {
+            ObjectKey::Identifier(s) | ObjectKey::String(s) => visit_cow_str(visitor, s),
+            ObjectKey::Boolean(true) => visitor.visit_borrowed_str("yes"),
+            ObjectKey::Boolean(false) => visitor.visit_borrowed_str("no"),
+            key => visitor.visit_string(key.to_string()),
+        }
+    }
+
+    fn deserialize_string<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_identifier<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_i8<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.visit_integer(visitor)
+    }
+
+    fn deserialize_i16<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.visit_integer(visitor)
+    }
+
+    fn deserialize_i32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.visit_integer(visitor)
+    }
+
+    fn deserialize_i64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.visit_integer(visitor)
+    }
+
+    fn deserialize_u8<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.visit_integer(visitor)
+    }
+
+    fn deserialize_u16<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.visit_integer(visitor)
+    }
+
+    fn deserialize_u32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.visit_integer(visitor)
+    }
+
+    fn deserialize_u64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.visit_integer(visitor)
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'de>,
+    {
+        match self.0 {
+            ObjectKey::Identifier(s) | ObjectKey::String(s) => {
+                visitor.visit_enum(s.into_deserializer())
             }
+            key => visitor.visit_enum(key.to_string().into_deserializer()),
+        }
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i128 u128 f32 f64 char bytes byte_buf unit unit_struct seq tuple
+        tuple_struct map struct ignored_any
+    }
+}
+
+/// Provides the operator and value of a [Comparison](`crate::types::Comparison`) as a sequence.
+struct ComparisonAccessor<'a, 'de: 'a> {
+    de: &'a mut Deserializer<'de>,
+    operator: Option<Operator>,
+    read_value: bool,
+}
+
+impl<'a, 'de> ComparisonAccessor<'a, 'de> {
+    fn new(de: &'a mut Deserializer<'de>, operator: Operator) -> Self {
+        ComparisonAccessor {
+            de,
+            operator: Some(operator),
+            read_value: false,
+        }
+    }
+}
+
+impl<'de, 'a> SeqAccess<'de> for ComparisonAccessor<'a, 'de> {
+    type Error = Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        if let Some(operator) = self.operator.take() {
+            return seed
+                .deserialize(operator.as_str().into_deserializer())
+                .map(Some);
+        }
+
+        if self.read_value {
+            return Ok(None);
+        }
+
+        self.read_value = true;
+        seed.deserialize(&mut *self.de).map(Some)
+    }
+}
+
+/// Presents a [Color] as a map with `space` and `values` fields.
+pub(crate) struct ColorAccessor {
+    color: Color,
+    field: usize,
+}
+
+impl ColorAccessor {
+    pub(crate) fn new(color: Color) -> Self {
+        ColorAccessor { color, field: 0 }
+    }
+}
+
+impl<'de> MapAccess<'de> for ColorAccessor {
+    type Error = Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        match COLOR_FIELDS.get(self.field) {
+            Some(field) => seed.deserialize(field.into_deserializer()).map(Some),
+            None => Ok(None),
         }
     }
 
@@ -427,7 +1036,14 @@ impl<'de, 'a> MapAccess<'de> for MapAccessor<'a, 'de> {
     where
         V: DeserializeSeed<'de>,
     {
-        seed.deserialize(&mut *self.de)
+        self.field += 1;
+        match self.field {
+            1 => seed.deserialize(self.color.space.prefix().into_deserializer()),
+            _ => {
+                let values = self.color.values.into_iter().chain(self.color.alpha);
+                seed.deserialize(SeqDeserializer::new(values))
+            }
+        }
     }
 }
 
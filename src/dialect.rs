@@ -0,0 +1,108 @@
+/// Describes the syntax understood by a particular Clausewitz game.
+///
+/// Clausewitz titles mostly share the same syntax, but newer games support constructs
+/// that older ones don't. Passing a [Dialect] to a [Tokenizer](`crate::tokenizer::Tokenizer`),
+/// [Reader](`crate::reader::Reader`), or [Deserializer](`crate::de::Deserializer`) makes it lex
+/// a file the same way its game would, and report a
+/// [DialectError](`crate::error::ErrorType::DialectError`) for syntax the game doesn't support.
+///
+/// [Dialect::default] accepts the syntax of every supported game.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Dialect {
+    /// The name of the game this dialect belongs to, used in error messages.
+    pub name: &'static str,
+    /// Whether the `?=` operator is supported.
+    pub existence_check: bool,
+    /// Whether scripted variables (`@name`) are supported.
+    pub scripted_variables: bool,
+    /// Whether inline math expressions (`@[ ... ]`) are supported.
+    pub inline_math: bool,
+    /// The identifiers that can prefix a color block, such as `rgb` in `rgb { 255 0 0 }`.
+    pub color_prefixes: &'static [&'static str],
+    /// Whether scripted effect parameters (`$PARAM$`) are supported.
+    pub parameters: bool,
+    /// Whether conditional parameter blocks (`[[PARAM] ... ]`) are supported.
+    pub parameter_blocks: bool,
+}
+
+impl Dialect {
+    /// Europa Universalis IV.
+    pub const EU4: Dialect = Dialect {
+        name: "Europa Universalis IV",
+        existence_check: false,
+        scripted_variables: false,
+        inline_math: false,
+        color_prefixes: &[],
+        parameters: true,
+        parameter_blocks: false,
+    };
+
+    /// Crusader Kings III.
+    pub const CK3: Dialect = Dialect {
+        name: "Crusader Kings III",
+        existence_check: true,
+        scripted_variables: true,
+        inline_math: true,
+        color_prefixes: &["rgb", "hsv", "hsv360"],
+        parameters: true,
+        parameter_blocks: true,
+    };
+
+    /// Hearts of Iron IV.
+    pub const HOI4: Dialect = Dialect {
+        name: "Hearts of Iron IV",
+        existence_check: false,
+        scripted_variables: true,
+        inline_math: false,
+        color_prefixes: &["rgb", "hsv"],
+        parameters: true,
+        parameter_blocks: false,
+    };
+
+    /// Stellaris.
+    pub const STELLARIS: Dialect = Dialect {
+        name: "Stellaris",
+        existence_check: false,
+        scripted_variables: true,
+        inline_math: true,
+        color_prefixes: &["rgb", "hsv"],
+        parameters: true,
+        parameter_blocks: true,
+    };
+
+    /// Imperator: Rome.
+    pub const IMPERATOR: Dialect = Dialect {
+        name: "Imperator: Rome",
+        existence_check: false,
+        scripted_variables: true,
+        inline_math: true,
+        color_prefixes: &["rgb", "hsv"],
+        parameters: true,
+        parameter_blocks: true,
+    };
+
+    /// Victoria 3.
+    pub const VICTORIA3: Dialect = Dialect {
+        name: "Victoria 3",
+        existence_check: true,
+        scripted_variables: true,
+        inline_math: true,
+        color_prefixes: &["rgb", "hsv", "hsv360", "hex"],
+        parameters: true,
+        parameter_blocks: true,
+    };
+}
+
+impl Default for Dialect {
+    fn default() -> Self {
+        Dialect {
+            name: "Clausewitz",
+            existence_check: true,
+            scripted_variables: true,
+            inline_math: true,
+            color_prefixes: &["rgb", "hsv", "hsv360", "hex"],
+            parameters: true,
+            parameter_blocks: true,
+        }
+    }
+}
@@ -0,0 +1,88 @@
+use std::borrow::Cow;
+
+use crate::util::error::{Error, ErrorType};
+
+pub(crate) const UTF8_BOM: &[u8] = &[0xEF, 0xBB, 0xBF];
+
+/// The characters represented by the bytes `0x80..=0x9F` in Windows-1252.
+///
+/// Bytes that are undefined in Windows-1252 (`0x81`, `0x8D`, `0x8F`, `0x90` and `0x9D`)
+/// are mapped to the C1 control character with the same value.
+const WINDOWS_1252_HIGH: [char; 32] = [
+    '\u{20AC}', '\u{0081}', '\u{201A}', '\u{0192}', '\u{201E}', '\u{2026}', '\u{2020}', '\u{2021}',
+    '\u{02C6}', '\u{2030}', '\u{0160}', '\u{2039}', '\u{0152}', '\u{008D}', '\u{017D}', '\u{008F}',
+    '\u{0090}', '\u{2018}', '\u{2019}', '\u{201C}', '\u{201D}', '\u{2022}', '\u{2013}', '\u{2014}',
+    '\u{02DC}', '\u{2122}', '\u{0161}', '\u{203A}', '\u{0153}', '\u{009D}', '\u{017E}', '\u{0178}',
+];
+
+/// The text encoding of a Clausewitz source file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    /// UTF-8, with or without a byte order mark.
+    Utf8,
+    /// Windows-1252, used by most game files from older Clausewitz titles.
+    Windows1252,
+    /// Detect the encoding from the input.
+    ///
+    /// Input starting with a UTF-8 byte order mark or that is valid UTF-8 is treated
+    /// as UTF-8. Anything else is treated as Windows-1252.
+    Detect,
+}
+
+impl Encoding {
+    /// Detects the encoding of `bytes`, returning either [Encoding::Utf8] or [Encoding::Windows1252].
+    pub fn detect(bytes: &[u8]) -> Encoding {
+        if bytes.starts_with(UTF8_BOM) || std::str::from_utf8(bytes).is_ok() {
+            Encoding::Utf8
+        } else {
+            Encoding::Windows1252
+        }
+    }
+}
+
+/// Decodes `bytes` into a string using the given [Encoding].
+///
+/// A leading UTF-8 byte order mark is removed from UTF-8 input. Input that's already
+/// valid UTF-8 is borrowed rather than copied.
+///
+/// Positions in any [Error] produced while parsing the decoded string refer to the
+/// decoded string, not to `bytes`.
+pub fn decode(bytes: &[u8], encoding: Encoding) -> Result<Cow<'_, str>, Error> {
+    let encoding = match encoding {
+        Encoding::Detect => Encoding::detect(bytes),
+        e => e,
+    };
+
+    match encoding {
+        Encoding::Utf8 => {
+            let bytes = bytes.strip_prefix(UTF8_BOM).unwrap_or(bytes);
+            std::str::from_utf8(bytes).map(Cow::Borrowed).map_err(|e| {
+                Error::new_positioned(
+                    ErrorType::EncodingError,
+                    e.valid_up_to(),
+                    format!("invalid UTF-8 sequence at byte {}", e.valid_up_to()),
+                )
+            })
+        }
+        Encoding::Windows1252 => Ok(decode_windows_1252(bytes)),
+        Encoding::Detect => unreachable!("encoding was detected above"),
+    }
+}
+
+/// Decodes Windows-1252 bytes, borrowing them if they're plain ASCII.
+pub(crate) fn decode_windows_1252(bytes: &[u8]) -> Cow<'_, str> {
+    if bytes.is_ascii() {
+        // ASCII is identical in both encodings
+        return Cow::Borrowed(std::str::from_utf8(bytes).unwrap());
+    }
+
+    Cow::Owned(
+        bytes
+            .iter()
+            .map(|b| match *b {
+                0x80..=0x9F => WINDOWS_1252_HIGH[(*b - 0x80) as usize],
+                b => b as char,
+            })
+            .collect(),
+    )
+}
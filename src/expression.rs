@@ -0,0 +1,302 @@
+use std::fmt;
+
+use crate::{
+    token::TokenType,
+    util::error::{Error, ErrorType},
+    variables::Variables,
+};
+
+/// The most operators and parentheses an [Expression] can contain.
+///
+/// This bounds how deeply expressions can be nested, so parsing and evaluating untrusted
+/// input can't overflow the stack.
+const MAX_EXPRESSION_NODES: usize = 256;
+
+/// An arithmetic operator in an inline math [Expression].
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum MathOperator {
+    /// The `+` operator.
+    Add,
+    /// The `-` operator.
+    Subtract,
+    /// The `*` operator.
+    Multiply,
+    /// The `/` operator.
+    Divide,
+    /// The `%` operator.
+    Remainder,
+}
+
+impl MathOperator {
+    /// Returns the symbol for this operator.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            MathOperator::Add => "+",
+            MathOperator::Subtract => "-",
+            MathOperator::Multiply => "*",
+            MathOperator::Divide => "/",
+            MathOperator::Remainder => "%",
+        }
+    }
+
+    /// Returns how tightly this operator binds. Higher values bind more tightly.
+    fn precedence(&self) -> u8 {
+        match self {
+            MathOperator::Add | MathOperator::Subtract => 1,
+            MathOperator::Multiply | MathOperator::Divide | MathOperator::Remainder => 2,
+        }
+    }
+
+    fn from_char(c: char) -> Option<MathOperator> {
+        match c {
+            '+' => Some(MathOperator::Add),
+            '-' => Some(MathOperator::Subtract),
+            '*' => Some(MathOperator::Multiply),
+            '/' => Some(MathOperator::Divide),
+            '%' => Some(MathOperator::Remainder),
+            _ => None,
+        }
+    }
+}
+
+/// A parsed inline math expression, such as `base_cost * 2 + 5` in `@[ base_cost * 2 + 5 ]`.
+///
+/// Expressions are made up of numbers, scripted variables (with or without their leading `@`),
+/// the operators `+`, `-`, `*`, `/` and `%`, and parentheses. Multiplication, division, and
+/// remainders are evaluated before addition and subtraction, and operators of the same
+/// precedence are evaluated from left to right.
+///
+/// Expressions can contain at most 256 operators and parentheses, and evaluating them fails
+/// if any part of the result isn't a finite number.
+///
+/// Positions in errors produced by [Expression::parse] and [Expression::evaluate] are byte
+/// offsets into the expression's text.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Expression {
+    /// A number literal.
+    Number(f64),
+    /// A reference to a scripted variable, along with its position in the expression.
+    Variable(String, usize),
+    /// A negated expression, such as `-x`.
+    Negate(Box<Expression>),
+    /// Two expressions combined with an operator, along with the operator's position in the expression.
+    Binary(MathOperator, Box<Expression>, Box<Expression>, usize),
+}
+
+impl Expression {
+    /// Parses the text of an inline math expression, without the surrounding `@[` and `]`.
+    pub fn parse(text: &str) -> Result<Expression, Error> {
+        let mut parser = ExpressionParser {
+            text,
+            position: 0,
+            nodes: 0,
+        };
+        let expression = parser.parse_binary(0)?;
+
+        parser.skip_whitespace();
+        match parser.peek() {
+            None => Ok(expression),
+            Some(c) => Err(parser.error(format!("unexpected character {} in expression", c))),
+        }
+    }
+
+    /// Evaluates this expression, looking up variables in the given table.
+    pub fn evaluate(&self, variables: &Variables) -> Result<f64, Error> {
+        match self {
+            Expression::Number(n) => Ok(*n),
+            Expression::Variable(name, position) => {
+                let variable = variables.get(name).ok_or_else(|| {
+                    Error::new_positioned(
+                        ErrorType::UndefinedVariableError,
+                        *position,
+                        format!("undefined variable '{}'", name),
+                    )
+                })?;
+
+                match variable.token_type {
+                    TokenType::Number => {
+                        variable.value.parse().ok().filter(|n: &f64| n.is_finite())
+                    }
+                    _ => None,
+                }
+                .ok_or_else(|| {
+                    Error::new_positioned(
+                        ErrorType::ExpressionError,
+                        *position,
+                        format!("variable '{}' isn't a number", name),
+                    )
+                })
+            }
+            Expression::Negate(e) => Ok(-e.evaluate(variables)?),
+            Expression::Binary(operator, left, right, position) => {
+                let left = left.evaluate(variables)?;
+                let right = right.evaluate(variables)?;
+
+                let result = match operator {
+                    MathOperator::Add => left + right,
+                    MathOperator::Subtract => left - right,
+                    MathOperator::Multiply => left * right,
+                    MathOperator::Divide | MathOperator::Remainder if right == 0.0 => {
+                        return Err(Error::new_positioned(
+                            ErrorType::ExpressionError,
+                            *position,
+                            "division by zero in expression",
+                        ))
+                    }
+                    MathOperator::Divide => left / right,
+                    MathOperator::Remainder => left % right,
+                };
+
+                // infinity and NaN can't be written back out as numbers
+                match result.is_finite() {
+                    true => Ok(result),
+                    false => Err(Error::new_positioned(
+                        ErrorType::ExpressionError,
+                        *position,
+                        "result of expression is too large",
+                    )),
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expression::Number(n) => write!(f, "{}", n),
+            Expression::Variable(name, _) => f.write_str(name),
+            Expression::Negate(e) => write!(f, "-{}", e),
+            Expression::Binary(operator, left, right, _) => {
+                write!(f, "({} {} {})", left, operator.as_str(), right)
+            }
+        }
+    }
+}
+
+/// Formats the result of an expression the way it would be written in a source file.
+///
+/// Whole numbers are written without a fractional part, so they can be read as integers.
+pub(crate) fn format_number(n: f64) -> String {
+    if n.fract() == 0.0 && n.abs() < 1e15 {
+        format!("{}", n as i64)
+    } else {
+        format!("{}", n)
+    }
+}
+
+/// A recursive descent parser for inline math expressions.
+struct ExpressionParser<'a> {
+    text: &'a str,
+    position: usize,
+    /// The number of operators and parentheses parsed so far.
+    nodes: usize,
+}
+
+impl<'a> ExpressionParser<'a> {
+    /// Parses a chain of binary operators binding at least as tightly as `min_precedence`.
+    fn parse_binary(&mut self, min_precedence: u8) -> Result<Expression, Error> {
+        let mut left = self.parse_unary()?;
+
+        loop {
+            self.skip_whitespace();
+            let operator = match self.peek().and_then(MathOperator::from_char) {
+                Some(operator) if operator.precedence() >= min_precedence => operator,
+                _ => return Ok(left),
+            };
+
+            self.add_node()?;
+            let position = self.position;
+            self.position += 1;
+            let right = self.parse_binary(operator.precedence() + 1)?;
+            left = Expression::Binary(operator, Box::new(left), Box::new(right), position);
+        }
+    }
+
+    /// Parses a number, variable, parenthesized expression, or negation.
+    fn parse_unary(&mut self) -> Result<Expression, Error> {
+        self.skip_whitespace();
+
+        match self.peek() {
+            Some('-') => {
+                self.add_node()?;
+                self.position += 1;
+                Ok(Expression::Negate(Box::new(self.parse_unary()?)))
+            }
+            Some('(') => {
+                self.add_node()?;
+                self.position += 1;
+                let expression = self.parse_binary(0)?;
+                self.skip_whitespace();
+                match self.peek() {
+                    Some(')') => {
+                        self.position += 1;
+                        Ok(expression)
+                    }
+                    _ => Err(self.error("expected ) in expression")),
+                }
+            }
+            Some(c) if c.is_ascii_digit() || c == '.' => {
+                let start = self.position;
+                self.take_while(|c| c.is_ascii_digit() || c == '.');
+                let str = &self.text[start..self.position];
+                let number = str.parse().ok().filter(|n: &f64| n.is_finite());
+                number.map(Expression::Number).ok_or_else(|| {
+                    Error::new_positioned(
+                        ErrorType::InvalidNumberError,
+                        start,
+                        format!("failed to parse number from '{}'", str),
+                    )
+                })
+            }
+            Some(c) if c == '@' || c == '_' || c.is_alphabetic() => {
+                let start = self.position;
+                if c == '@' {
+                    self.position += 1;
+                }
+
+                let name_start = self.position;
+                self.take_while(|c| c == '_' || c.is_alphanumeric());
+                if self.position == name_start {
+                    return Err(self.error("expected variable name after '@'"));
+                }
+
+                let name = self.text[name_start..self.position].to_owned();
+                Ok(Expression::Variable(name, start))
+            }
+            Some(c) => Err(self.error(format!("unexpected character {} in expression", c))),
+            None => Err(self.error("unexpected end of expression")),
+        }
+    }
+
+    /// Counts an operator or parenthesis towards [MAX_EXPRESSION_NODES].
+    fn add_node(&mut self) -> Result<(), Error> {
+        self.nodes += 1;
+        match self.nodes > MAX_EXPRESSION_NODES {
+            true => Err(self.error("expression is too complex")),
+            false => Ok(()),
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.text[self.position..].chars().next()
+    }
+
+    fn take_while(&mut self, predicate: impl Fn(char) -> bool) {
+        while let Some(c) = self.peek() {
+            if !predicate(c) {
+                break;
+            }
+
+            self.position += c.len_utf8();
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        self.take_while(char::is_whitespace);
+    }
+
+    fn error(&self, message: impl ToString) -> Error {
+        Error::new_positioned(ErrorType::ExpressionError, self.position, message)
+    }
+}
@@ -11,6 +11,8 @@
 //! - [Tokenizer](`tokenizer::Tokenizer`) turns a Clausewitz file into a series of tokens.
 //! - [Reader](`reader::Reader`) is a wrapper around [Tokenizer](`tokenizer::Tokenizer`) that enables
 //!   low-level parsing operations on a Clausewitz source file.
+//! - [Tape](`tape::Tape`) tokenizes a whole Clausewitz file in one pass into a flat list of tokens,
+//!   which can be navigated or deserialized without re-reading skipped values.
 
 #![feature(let_chains)]
 #![feature(doc_cfg)]
@@ -20,8 +22,26 @@
 #[doc(cfg(feature = "serde"))]
 pub mod de;
 
+/// Comments read from source files.
+pub mod comments;
+/// Syntax differences between Clausewitz games.
+pub mod dialect;
+/// Text encodings used by Clausewitz files.
+pub mod encoding;
+/// Inline math expressions.
+pub mod expression;
+/// Limits on the size of untrusted input.
+pub mod limits;
 /// Low-level parser for Clausewitz files.
 pub mod reader;
+/// Headers, metadata, and checksums of text save files.
+pub mod save;
+/// Source maps for locating positions across many files.
+pub mod source;
+/// A flat, fully-parsed representation of a file, for fast random access.
+pub mod tape;
+/// Parameterized definitions, such as scripted effects.
+pub mod template;
 /// Tokens obtained from a source file.
 pub mod token;
 /// A Tokenizer for parsing Clausewitz files.
@@ -30,6 +50,8 @@ pub mod tokenizer;
 pub mod types;
 /// Deserialization for Clausewitz files without a known schema.
 pub mod value;
+/// Scripted variables shared between Clausewitz files.
+pub mod variables;
 
 mod schema;
 mod util;
@@ -0,0 +1,83 @@
+/// Limits on the size of the input a [Reader](`crate::reader::Reader`) will read, for reading
+/// files that can't be trusted, such as mods or saves from other players.
+///
+/// Input that goes over a limit is rejected with a [LimitError](`crate::error::ErrorType::LimitError`),
+/// which isn't recovered from in [ParseMode::Lenient](`crate::reader::ParseMode::Lenient`).
+/// A limit of [usize::MAX] means there's no limit.
+///
+/// [Limits::default] doesn't limit anything, though deserializing and reading a
+/// [Value](`crate::value::Value`) never allow collections to be nested more than
+/// [MAX_DEPTH](`crate::de::MAX_DEPTH`) deep. [Limits::UNTRUSTED] has limits
+/// well above what any game file needs, which keep memory use bounded.
+///
+/// ```
+/// use clauser::{error::ErrorType, limits::Limits, reader::Reader, value::Value};
+///
+/// let text = "a = ".to_owned() + &"{ ".repeat(100_000);
+/// let mut reader = Reader::new(&text).with_limits(Limits::UNTRUSTED);
+/// let error = Value::from_reader(&mut reader).unwrap_err();
+/// assert_eq!(error.error_type, ErrorType::LimitError);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    /// The maximum number of collections that can be nested inside each other.
+    ///
+    /// The [Deserializer](`crate::de::Deserializer`) and [Value](`crate::value::Value`) recurse
+    /// once for every nested collection, so they lower this to [MAX_DEPTH](`crate::de::MAX_DEPTH`)
+    /// to avoid overflowing the stack.
+    pub max_depth: usize,
+    /// The maximum number of tokens that can be read, including comments.
+    pub max_tokens: usize,
+    /// The maximum length in bytes of a single string, identifier, or inline math expression.
+    pub max_string_length: usize,
+    /// The maximum number of entries in a single collection, including the root of the input.
+    pub max_collection_size: usize,
+}
+
+impl Limits {
+    /// No limits.
+    pub const NONE: Limits = Limits {
+        max_depth: usize::MAX,
+        max_tokens: usize::MAX,
+        max_string_length: usize::MAX,
+        max_collection_size: usize::MAX,
+    };
+
+    /// Limits suitable for reading untrusted input.
+    pub const UNTRUSTED: Limits = Limits {
+        max_depth: 128,
+        max_tokens: 100_000_000,
+        max_string_length: 1024 * 1024,
+        max_collection_size: 10_000_000,
+    };
+
+    /// Sets the maximum number of collections that can be nested inside each other.
+    pub fn with_max_depth(mut self, max_depth: usize) -> Limits {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Sets the maximum number of tokens that can be read.
+    pub fn with_max_tokens(mut self, max_tokens: usize) -> Limits {
+        self.max_tokens = max_tokens;
+        self
+    }
+
+    /// Sets the maximum length in bytes of a single string, identifier, or inline math expression.
+    pub fn with_max_string_length(mut self, max_string_length: usize) -> Limits {
+        self.max_string_length = max_string_length;
+        self
+    }
+
+    /// Sets the maximum number of entries in a single collection.
+    pub fn with_max_collection_size(mut self, max_collection_size: usize) -> Limits {
+        self.max_collection_size = max_collection_size;
+        self
+    }
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits::NONE
+    }
+}
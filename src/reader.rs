@@ -1,42 +1,640 @@
 use crate::{
+    comments::Comment,
+    dialect::Dialect,
+    encoding::Encoding,
+    expression::{format_number, Expression},
+    limits::Limits,
+    source::{SourceId, Span},
+    template::Template,
     token::{Token, TokenType},
     tokenizer::Tokenizer,
-    types::{CollectionType, Date, ObjectKey, RealType},
+    types::{CollectionType, Color, ColorSpace, Date, ObjectKey, Operator, RealType},
     util::error::{Error, ErrorContext, ErrorContextProvider, ErrorType, ParseResult},
+    util::text_helpers::unescape,
+    value::Value,
+    variables::{Variable, Variables},
 };
-use std::str::FromStr;
+use std::{borrow::Cow, io::Read, path::Path, str::FromStr, sync::Arc};
+
+/// The types of tokens that can be used as the key of a property.
+const KEY_TOKEN_TYPES: [TokenType; 5] = [
+    TokenType::Identifier,
+    TokenType::Date,
+    TokenType::Number,
+    TokenType::String,
+    TokenType::Boolean,
+];
+
+/// The types of tokens that can be the value of a scripted variable.
+const VARIABLE_TOKEN_TYPES: [TokenType; 5] = [
+    TokenType::Number,
+    TokenType::String,
+    TokenType::Identifier,
+    TokenType::Boolean,
+    TokenType::Date,
+];
+
+/// The types of tokens that can start a value.
+const VALUE_TOKEN_TYPES: [TokenType; 8] = [
+    TokenType::OpenBracket,
+    TokenType::Identifier,
+    TokenType::Date,
+    TokenType::Number,
+    TokenType::String,
+    TokenType::Boolean,
+    TokenType::Variable,
+    TokenType::InlineMath,
+];
+
+/// How a [Reader] handles malformed input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ParseMode {
+    /// Stop reading at the first error.
+    #[default]
+    Strict,
+    /// Record errors as diagnostics and keep reading.
+    ///
+    /// Invalid tokens and stray close brackets are skipped, a missing `=` is assumed,
+    /// unclosed collections are closed at the end of the input, and properties that
+    /// can't be read are skipped up to the next property.
+    Lenient,
+}
+
+/// An event read by [Reader::next_event].
+///
+/// Every property of an object produces a [Event::Key], an [Event::Operator], and then the
+/// events of its value. Objects and arrays start with [Event::BeginObject] or [Event::BeginArray],
+/// followed by the events of their entries, and finish with [Event::End].
+///
+/// Collections can mix properties and bare values, like `{ 1 2 3 a = b }`. These start with the
+/// event for their first entry, and their other entries produce events the same way.
+///
+/// ```
+/// use clauser::{reader::{Event, Reader}, types::{ObjectKey, Operator}, value::Value};
+///
+/// let mut reader = Reader::new("tags = { FRA ENG }");
+/// assert_eq!(reader.next_event()?, Some(Event::Key(ObjectKey::Identifier("tags".into()))));
+/// assert_eq!(reader.next_event()?, Some(Event::Operator(Operator::Equals)));
+/// assert_eq!(reader.next_event()?, Some(Event::BeginArray));
+/// assert_eq!(reader.next_event()?, Some(Event::Scalar(Value::Identifier("FRA".into()))));
+/// assert_eq!(reader.next_event()?, Some(Event::Scalar(Value::Identifier("ENG".into()))));
+/// assert_eq!(reader.next_event()?, Some(Event::End));
+/// assert_eq!(reader.next_event()?, None);
+/// # Ok::<(), clauser::error::Error>(())
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event<'a> {
+    /// The key of a property.
+    Key(ObjectKey<'a>),
+    /// The operator between the key and value of a property.
+    Operator(Operator),
+    /// A value that isn't an object or an array.
+    ///
+    /// This is never [Value::Object] or [Value::Array], but can be a [Value::Color],
+    /// or [Value::None] for a value that couldn't be read in [ParseMode::Lenient].
+    Scalar(Value<'a>),
+    /// The start of an object, or a collection whose first entry is a property.
+    BeginObject,
+    /// The start of an array, or a collection whose first entry is a bare value.
+    ///
+    /// Empty collections (`{}`) are read as arrays.
+    BeginArray,
+    /// The end of the most recent object or array.
+    End,
+}
+
+/// What [Reader::next_event] expects to read next.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum EventState {
+    /// The next property of an object or item of an array.
+    Entry,
+    /// The operator of the property whose key was just read.
+    Operator(Operator),
+    /// The value of the property whose operator was just read.
+    Value,
+}
+
+/// A collection being read through by [Reader::discern_collection], along with the kinds of
+/// entries found in it so far.
+struct Discerning {
+    /// The position of the collection's opening bracket.
+    start: usize,
+    collection_type: CollectionType,
+    properties: bool,
+    items: bool,
+}
+
+impl Discerning {
+    /// Returns whether the collection is an object, an array, or a mix of both.
+    fn collection_type(&self) -> CollectionType {
+        match (self.properties, self.items) {
+            (true, true) => CollectionType::Mixed,
+            (true, false) => CollectionType::Object,
+            (false, _) => CollectionType::Array,
+        }
+    }
+}
+
+/// The state of a [Reader] saved by [Reader::checkpoint], which it can be rewound to
+/// with [Reader::restore].
+#[derive(Debug, Clone)]
+pub struct Checkpoint {
+    position: usize,
+    depth: usize,
+    last_token_end: usize,
+    operator_end: Option<usize>,
+    last_recovery: Option<usize>,
+    parameter_found: bool,
+    diagnostics: usize,
+    variables: Arc<Variables>,
+    events: Vec<CollectionType>,
+    event_state: EventState,
+    collection_sizes: Vec<(usize, Option<usize>)>,
+}
+
+impl Checkpoint {
+    /// Returns the position in the source text the checkpoint was created at.
+    pub fn position(&self) -> usize {
+        self.position
+    }
+}
 
 /// A pair of ([ObjectKey], [RealType]) values representing the next property read
 /// from an object.
 pub type PropertyInfo<'a> = (ObjectKey<'a>, RealType);
 
+/// The start of an entry of a collection, read by [Reader::next_entry].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Entry<'a> {
+    /// A property, whose key and operator have been read. Its value is next.
+    Property(PropertyInfo<'a>, Operator),
+    /// A value without a key, such as an array item or the `flag` in `{ key = 1 flag }`.
+    /// Nothing has been read yet, so the value is next.
+    Item,
+}
+
 /// [Reader] offers a wrapper around a [Tokenizer] that allows low-level parsing
 /// operations on a Clausewitz source file.
 ///
 /// [Reader] is useful for situations where a file can't be properly parsed by either
 /// [Deserializer](`crate::de::Deserializer`) or [Value](`crate::value::Value`).
+///
+/// Scripted variable definitions (`@cost = 100`) are collected into the reader's [Variables]
+/// as they're read, rather than being returned as properties. References to a variable (`@cost`)
+/// are read as if they were the variable's value, and inline math expressions (`@[ cost * 2 ]`)
+/// are evaluated using those variables and read as numbers.
+///
+/// In [ParseMode::Lenient], the reader recovers from errors instead of returning them,
+/// and every error it recovered from is available from [Reader::diagnostics].
+///
+/// Files can also be read without knowing their structure using [Reader::next_event],
+/// which returns a stream of [Event] values.
 pub struct Reader<'a> {
     tokenizer: Tokenizer<'a>,
     current_depth: usize,
+    /// The scripted variables, shared with any [Checkpoint] until they change.
+    variables: Arc<Variables>,
+    /// Whether blocks containing parameters or conditional blocks are read as templates.
+    templates: bool,
+    /// Whether a parameter or conditional block has been read since [Reader::take_parameter].
+    parameter_found: bool,
+    mode: ParseMode,
+    diagnostics: Vec<Error>,
+    /// The position of the last call to [Reader::recover].
+    last_recovery: Option<usize>,
+    /// Comments read so far that haven't been taken, in the order they appear.
+    comments: Vec<Comment<'a>>,
+    /// The end of the last token read, not counting comments.
+    last_token_end: usize,
+    /// The end of the last operator read.
+    operator_end: Option<usize>,
+    /// The collections opened by [Reader::next_event] that haven't ended yet.
+    events: Vec<CollectionType>,
+    event_state: EventState,
+    limits: Limits,
+    /// The number of tokens read so far, counting each token once even if it's read again.
+    tokens: usize,
+    /// The end of the furthest token counted in [Reader::tokens].
+    counted_end: usize,
+    /// The number of entries read from the root and each collection the reader is inside,
+    /// along with the position of the last one counted.
+    collection_sizes: Vec<(usize, Option<usize>)>,
+    /// The types of the collections read through by the last call to [Reader::discern_collection]
+    /// that wasn't answered from here, by the position of their opening bracket.
+    discerned: Vec<(usize, CollectionType)>,
 }
 
 impl<'a> Reader<'a> {
     /// Creates a new Reader from the given text.
-    pub fn new(text: &'a str) -> Reader {
+    pub fn new(text: &'a str) -> Reader<'a> {
+        Reader::from_tokenizer(Tokenizer::new(text))
+    }
+
+    /// Creates a new Reader from raw bytes in the given [Encoding].
+    ///
+    /// See [Tokenizer::from_bytes] for more information.
+    pub fn from_bytes(bytes: &'a [u8], encoding: Encoding) -> Result<Reader<'a>, Error> {
+        Ok(Reader::from_tokenizer(Tokenizer::from_bytes(
+            bytes, encoding,
+        )?))
+    }
+
+    /// Creates a new [Reader] that reads its input from `read` in the given [Encoding], a chunk at a time.
+    ///
+    /// Memory use doesn't depend on the size of the input, and strings are always owned.
+    /// Templates aren't detected when streaming, and [Checkpoint]s can only be restored while
+    /// their position is still buffered. See [Tokenizer::from_read] for more information.
+    pub fn from_read(read: impl Read + Send + 'static, encoding: Encoding) -> Reader<'a> {
+        Reader::from_tokenizer(Tokenizer::from_read(read, encoding))
+    }
+
+    /// Creates a new [Reader] that reads the file at `path` in the given [Encoding].
+    ///
+    /// The path is recorded in every [Error] the reader produces. See [Tokenizer::from_path]
+    /// for more information, and [Tokenizer::from_path_mapped] to memory-map the file instead.
+    pub fn from_path(path: impl AsRef<Path>, encoding: Encoding) -> Result<Reader<'a>, Error> {
+        Ok(Reader::from_tokenizer(Tokenizer::from_path(
+            path, encoding,
+        )?))
+    }
+
+    /// Creates a new [Reader] that reads tokens from the given [Tokenizer].
+    pub fn from_tokenizer(tokenizer: Tokenizer<'a>) -> Reader<'a> {
         Reader {
-            tokenizer: Tokenizer::new(text),
+            tokenizer,
             current_depth: 0,
+            variables: Arc::new(Variables::new()),
+            templates: false,
+            parameter_found: false,
+            mode: ParseMode::Strict,
+            diagnostics: Vec::new(),
+            last_recovery: None,
+            comments: Vec::new(),
+            last_token_end: 0,
+            operator_end: None,
+            events: Vec::new(),
+            event_state: EventState::Entry,
+            limits: Limits::default(),
+            tokens: 0,
+            counted_end: 0,
+            collection_sizes: vec![(0, None)],
+            discerned: Vec::new(),
+        }
+    }
+
+    /// Sets the [SourceId] used for the spans of errors produced by this reader.
+    pub fn with_source(mut self, source: SourceId) -> Reader<'a> {
+        self.tokenizer = self.tokenizer.with_source(source);
+        self
+    }
+
+    /// Returns the [SourceId] of the text being read.
+    pub fn source(&self) -> SourceId {
+        self.tokenizer.source()
+    }
+
+    /// Sets the [Dialect] used to read the source text.
+    pub fn with_dialect(mut self, dialect: Dialect) -> Reader<'a> {
+        self.tokenizer = self.tokenizer.with_dialect(dialect);
+        self
+    }
+
+    /// Returns the [Dialect] used to read the source text.
+    pub fn dialect(&self) -> &Dialect {
+        self.tokenizer.dialect()
+    }
+
+    /// Sets the [ParseMode] used to handle malformed input.
+    pub fn with_mode(mut self, mode: ParseMode) -> Reader<'a> {
+        self.mode = mode;
+        self
+    }
+
+    /// Returns the [ParseMode] used to handle malformed input.
+    pub fn mode(&self) -> ParseMode {
+        self.mode
+    }
+
+    /// Sets the [Limits] on the size of the input, for reading input that can't be trusted.
+    pub fn with_limits(mut self, limits: Limits) -> Reader<'a> {
+        self.limits = limits;
+        self
+    }
+
+    /// Returns the [Limits] on the size of the input.
+    pub fn limits(&self) -> &Limits {
+        &self.limits
+    }
+
+    /// Replaces the [Limits] of a reader that's already in use.
+    pub(crate) fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    /// Sets whether blocks containing parameters (`$PARAM$`) or conditional blocks (`[[PARAM] ... ]`),
+    /// such as the bodies of scripted effects, are read as templates by [Value](`crate::value::Value`).
+    ///
+    /// A block is read as a [Value::Template] if a parameter is found in an identifier or key
+    /// inside it, or it contains a conditional block. Parameters inside quoted strings are ignored.
+    /// Templates aren't detected when streaming, by [Reader::next_event], or by the
+    /// [Deserializer](`crate::de::Deserializer`), which reads a block as a template when it's
+    /// deserialized as one. Disabled by default.
+    pub fn with_templates(mut self, templates: bool) -> Reader<'a> {
+        self.templates = templates;
+        self
+    }
+
+    /// Returns true if blocks containing parameters are read as templates.
+    pub(crate) fn detects_templates(&self) -> bool {
+        self.templates && !self.tokenizer.is_streaming()
+    }
+
+    /// Returns whether a parameter or conditional block has been read since this was last called.
+    pub(crate) fn take_parameter(&mut self) -> bool {
+        std::mem::take(&mut self.parameter_found)
+    }
+
+    /// Records whether a token is a parameter or starts a conditional block, if templates are detected.
+    fn note_parameter(&mut self, token: &Token) {
+        if self.templates {
+            self.parameter_found |= match token.token_type {
+                TokenType::ParameterBlockStart => true,
+                TokenType::Identifier => self.tokenizer.str_for_token(token).contains('$'),
+                _ => false,
+            };
+        }
+    }
+
+    /// Returns every error that was recovered from in [ParseMode::Lenient], in the order
+    /// they were encountered.
+    pub fn diagnostics(&self) -> &[Error] {
+        &self.diagnostics
+    }
+
+    /// Removes and returns every error that was recovered from in [ParseMode::Lenient].
+    pub fn take_diagnostics(&mut self) -> Vec<Error> {
+        std::mem::take(&mut self.diagnostics)
+    }
+
+    /// Handles an error encountered while reading.
+    ///
+    /// In [ParseMode::Strict], the error is returned. In [ParseMode::Lenient], the error is
+    /// recorded as a diagnostic, and the reader skips ahead to the end of the current collection
+    /// or the start of the next property.
+    pub fn recover(&mut self, error: Error) -> Result<(), Error> {
+        if self.mode == ParseMode::Strict || !is_recoverable(&error) {
+            return Err(error);
+        }
+
+        self.report(error);
+
+        // always skip at least one token if we've already recovered here, so we can't get stuck
+        let mut skip_first = self.last_recovery == Some(self.tokenizer.position);
+        self.last_recovery = Some(self.tokenizer.position);
+        let mut depth = 0;
+
+        loop {
+            let initial_pos = self.tokenizer.position;
+            let token = match self.next_token()? {
+                Some(token) => token,
+                None => return Ok(()),
+            };
+
+            let at_sync_point = depth == 0
+                && !skip_first
+                && match token.token_type {
+                    TokenType::CloseBracket => self.current_depth > 0,
+                    ref t if *t == TokenType::Variable || KEY_TOKEN_TYPES.contains(t) => matches!(
+                        self.peek_token()?,
+                        Some(Token { ref token_type, .. }) if Operator::TOKEN_TYPES.contains(token_type)
+                    ),
+                    _ => false,
+                };
+
+            if at_sync_point {
+                self.tokenizer.position = initial_pos;
+                return Ok(());
+            }
+
+            match token.token_type {
+                TokenType::OpenBracket => depth += 1,
+                TokenType::CloseBracket if depth > 0 => depth -= 1,
+                _ => (),
+            }
+
+            skip_first = false;
+        }
+    }
+
+    /// Records an error as a diagnostic, unless the same error was already recorded.
+    fn report(&mut self, error: Error) {
+        let is_duplicate = self
+            .diagnostics
+            .iter()
+            .any(|e| e.position == error.position && e.error_type == error.error_type);
+
+        if !is_duplicate {
+            self.diagnostics.push(error);
+        }
+    }
+
+    /// Obtains the next token from the tokenizer, skipping invalid tokens in [ParseMode::Lenient]
+    /// and collecting any comments.
+    fn next_token(&mut self) -> ParseResult<Token> {
+        loop {
+            match self.tokenizer.next() {
+                Ok(Some(token)) if token.token_type == TokenType::Comment => {
+                    self.count_token(&token)?;
+                    self.collect_comment(&token)
+                }
+                Ok(Some(token)) => {
+                    self.count_token(&token)?;
+                    self.note_parameter(&token);
+                    self.last_token_end = token.index + token.length;
+                    return Ok(Some(token));
+                }
+                Err(e) if self.mode == ParseMode::Lenient && is_recoverable(&e) => {
+                    self.tokenizer.skip_error(&e);
+                    self.report(e);
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Peeks the next token from the tokenizer, skipping invalid tokens in [ParseMode::Lenient]
+    /// and collecting any comments.
+    fn peek_token(&mut self) -> ParseResult<Token> {
+        loop {
+            match self.tokenizer.peek() {
+                Ok(Some(token)) if token.token_type == TokenType::Comment => {
+                    self.count_token(&token)?;
+                    // comments can be consumed even when peeking, since they don't affect parsing
+                    self.tokenizer.position = token.index + token.length;
+                    self.collect_comment(&token);
+                }
+                Ok(Some(token)) => {
+                    self.count_token(&token)?;
+                    self.note_parameter(&token);
+                    return Ok(Some(token));
+                }
+                Err(e) if self.mode == ParseMode::Lenient && is_recoverable(&e) => {
+                    self.tokenizer.skip_error(&e);
+                    self.report(e);
+                }
+                result => return result,
+            }
         }
     }
 
+    /// Checks a token against the [Limits] of the reader, counting it if it hasn't been before.
+    fn count_token(&mut self, token: &Token) -> Result<(), Error> {
+        // tokens are read again after peeking or restoring a checkpoint, but only counted once
+        if token.index >= self.counted_end {
+            self.tokens += 1;
+            self.counted_end = token.index + token.length;
+        }
+
+        if self.tokens > self.limits.max_tokens {
+            return Err(self.parse_error_token(
+                token,
+                ErrorType::LimitError,
+                format!("input has more than {} tokens", self.limits.max_tokens),
+            ));
+        }
+
+        let is_string = matches!(
+            token.token_type,
+            TokenType::String | TokenType::Identifier | TokenType::InlineMath
+        );
+        if is_string && token.length > self.limits.max_string_length {
+            return Err(self.parse_error_token(
+                token,
+                ErrorType::LimitError,
+                format!(
+                    "{:?} is longer than {} bytes",
+                    token.token_type, self.limits.max_string_length
+                ),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Counts the entry of the current collection starting at `position` against
+    /// [Limits::max_collection_size], unless it's already been counted.
+    fn count_entry(&mut self, position: usize) -> Result<(), Error> {
+        let max = self.limits.max_collection_size;
+        let (entries, last) = self
+            .collection_sizes
+            .last_mut()
+            .expect("the root is never ended");
+
+        if last.is_some_and(|last| position <= last) {
+            return Ok(());
+        }
+
+        *entries += 1;
+        *last = Some(position);
+
+        match *entries > max {
+            true => Err(self.tokenizer.parse_error_pos(
+                ErrorType::LimitError,
+                position,
+                format!("collection has more than {} entries", max),
+            )),
+            false => Ok(()),
+        }
+    }
+
+    /// Checks that a collection nested `depth` levels deep is allowed by [Limits::max_depth].
+    fn check_depth(&self, depth: usize, token: &Token) -> Result<(), Error> {
+        match depth > self.limits.max_depth {
+            true => Err(self.parse_error_token(
+                token,
+                ErrorType::LimitError,
+                format!(
+                    "collections are nested more than {} deep",
+                    self.limits.max_depth
+                ),
+            )),
+            false => Ok(()),
+        }
+    }
+
+    /// Adds a comment token to the list of comments, unless it's already been seen.
+    fn collect_comment(&mut self, token: &Token) {
+        // comments are always read in order, but might be seen again after looking ahead
+        let is_new = self
+            .comments
+            .last()
+            .is_none_or(|last| token.index >= last.span.end);
+
+        if is_new {
+            self.comments.push(Comment::new(
+                self.tokenizer.cow_for_token(token),
+                token.span(),
+            ));
+        }
+    }
+
+    /// Sets whether comments are kept instead of skipped.
+    ///
+    /// Kept comments can be obtained with [Reader::take_comments], or attached to the values
+    /// they belong to with [Value::from_reader_with_comments](`crate::value::Value::from_reader_with_comments`).
+    pub fn with_comments(mut self, comments: bool) -> Reader<'a> {
+        self.tokenizer = self.tokenizer.with_comments(comments);
+        self
+    }
+
+    /// Removes and returns every comment read so far.
+    pub fn take_comments(&mut self) -> Vec<Comment<'a>> {
+        std::mem::take(&mut self.comments)
+    }
+
+    /// Removes and returns every comment read so far that starts within `start..end`.
+    pub(crate) fn take_comments_in(&mut self, start: usize, end: usize) -> Vec<Comment<'a>> {
+        let first = self.comments.partition_point(|c| c.span.start < start);
+        let last = self.comments.partition_point(|c| c.span.start < end);
+        self.comments.drain(first..last).collect()
+    }
+
+    /// Returns the end of the last token read, not counting comments.
+    pub(crate) fn last_token_end(&self) -> usize {
+        self.last_token_end
+    }
+
+    /// Sets the table of scripted variables that references can be resolved from,
+    /// such as variables defined in another file.
+    ///
+    /// Variables defined in the source text replace those in the table.
+    pub fn with_variables(mut self, variables: Variables) -> Reader<'a> {
+        self.variables = Arc::new(variables);
+        self
+    }
+
+    /// Returns the scripted variables known to this reader, including those defined so far
+    /// in the source text.
+    pub fn variables(&self) -> &Variables {
+        &self.variables
+    }
+
+    /// Consumes this reader, returning its scripted variables.
+    pub fn into_variables(self) -> Variables {
+        Arc::unwrap_or_clone(self.variables)
+    }
+
     /// Returns the current position of the underlying tokenizer in the token stream
     pub fn current_position(&self) -> usize {
         self.tokenizer.position
     }
 
-    // Increments the current depth of the reader.
-    fn increment_depth(&mut self) {
-        self.current_depth = self.current_depth + 1
+    // Increments the current depth of the reader for the collection opened by `token`.
+    fn increment_depth(&mut self, token: &Token) -> Result<(), Error> {
+        self.check_depth(self.current_depth + 1, token)?;
+        self.current_depth = self.current_depth + 1;
+        self.collection_sizes.push((0, None));
+        Ok(())
     }
 
     /// Decrements the current depth of the reader.
@@ -49,6 +647,7 @@ impl<'a> Reader<'a> {
         }
 
         self.current_depth = self.current_depth - 1;
+        self.collection_sizes.pop();
         Ok(None)
     }
 
@@ -57,9 +656,156 @@ impl<'a> Reader<'a> {
         self.current_depth == 0
     }
 
+    /// Saves the current state of the reader, so it can be rewound with [Reader::restore].
+    ///
+    /// This allows parsers built on a [Reader] to try reading an ambiguous construct one way,
+    /// and start over if that fails. A checkpoint captures the position, the depth of nested
+    /// collections, the scripted variables defined so far, the diagnostics reported so far,
+    /// the number of entries read from each collection, whether the last thing read was an
+    /// operator or a parameter, and the state of [Reader::next_event].
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            position: self.tokenizer.position,
+            depth: self.current_depth,
+            last_token_end: self.last_token_end,
+            operator_end: self.operator_end,
+            last_recovery: self.last_recovery,
+            parameter_found: self.parameter_found,
+            diagnostics: self.diagnostics.len(),
+            variables: self.variables.clone(),
+            events: self.events.clone(),
+            event_state: self.event_state,
+            collection_sizes: self.collection_sizes.clone(),
+        }
+    }
+
+    /// Rewinds the reader to a [Checkpoint] created by [Reader::checkpoint] on this reader.
+    ///
+    /// Diagnostics reported since the checkpoint are discarded, and so are comments found after
+    /// it, which will be found again as the reader continues. Comments taken with
+    /// [Reader::take_comments] since the checkpoint aren't restored.
+    pub fn restore(&mut self, checkpoint: Checkpoint) {
+        self.tokenizer.position = checkpoint.position;
+        self.current_depth = checkpoint.depth;
+        self.last_token_end = checkpoint.last_token_end;
+        self.operator_end = checkpoint.operator_end;
+        self.last_recovery = checkpoint.last_recovery;
+        self.parameter_found = checkpoint.parameter_found;
+        self.diagnostics.truncate(checkpoint.diagnostics);
+        self.variables = checkpoint.variables;
+        self.events = checkpoint.events;
+        self.event_state = checkpoint.event_state;
+        self.collection_sizes = checkpoint.collection_sizes;
+        self.comments
+            .retain(|comment| comment.span.start < checkpoint.position);
+    }
+
+    /// Moves the reader to `position` in the collection it's reading, to read a value there that
+    /// was skipped over, without saving a whole [Checkpoint].
+    ///
+    /// Nothing but the position is moved, so the reader has to be inside the same collection
+    /// as it was at `position`, and errors recovered from while reading the value again are
+    /// reported again. Use [Reader::truncate_diagnostics] to discard them.
+    pub(crate) fn seek(&mut self, position: usize) {
+        self.tokenizer.position = position;
+    }
+
+    /// Discards every error recovered from after the first `len`.
+    pub(crate) fn truncate_diagnostics(&mut self, len: usize) {
+        self.diagnostics.truncate(len);
+    }
+
+    /// Returns the number of collections the reader is currently inside.
+    pub fn depth(&self) -> usize {
+        self.current_depth
+    }
+
+    /// Reads the next [Event] from the token stream, or returns `None` at the end of the input.
+    ///
+    /// Events are read relative to where the first call to [Reader::next_event] started,
+    /// which is expected to be the start of an object, such as the root of a file. Mixing
+    /// calls to this with other reading methods before an [Event::End] isn't supported.
+    pub fn next_event(&mut self) -> ParseResult<Event<'a>> {
+        loop {
+            match std::mem::replace(&mut self.event_state, EventState::Entry) {
+                EventState::Operator(operator) => {
+                    self.event_state = EventState::Value;
+                    return Ok(Some(Event::Operator(operator)));
+                }
+                EventState::Value => {
+                    return match self.next_value_event() {
+                        Ok(event) => Ok(Some(event)),
+                        Err(e) => {
+                            self.recover(e)?;
+                            Ok(Some(Event::Scalar(Value::None)))
+                        }
+                    };
+                }
+                EventState::Entry => (),
+            }
+
+            let entry = match self.events.last() {
+                // the root of the file only contains properties
+                None => self
+                    .next_property_with_operator()?
+                    .map(|p| Entry::Property(p.0, p.1)),
+                Some(collection_type) => self.next_collection_entry(*collection_type)?,
+            };
+
+            return match entry {
+                Some(Entry::Property((key, _), operator)) => {
+                    self.event_state = EventState::Operator(operator);
+                    Ok(Some(Event::Key(key)))
+                }
+                Some(Entry::Item) => match self.next_value_event() {
+                    Ok(event) => Ok(Some(event)),
+                    // skip items that can't be read
+                    Err(e) => {
+                        self.recover(e)?;
+                        continue;
+                    }
+                },
+                None => self.end_event(),
+            };
+        }
+    }
+
+    /// Reads the next value for [Reader::next_event], starting a collection if it's an object or array.
+    fn next_value_event(&mut self) -> Result<Event<'a>, Error> {
+        match self.peek_next_type()? {
+            Some(RealType::ObjectOrArray) => {
+                // a collection that ends at EOF will error when its first property is read
+                let collection_type = self
+                    .try_discern_array_or_map()?
+                    .unwrap_or(CollectionType::Object);
+
+                self.begin_collection()?;
+                let event = match collection_type {
+                    CollectionType::Array => Event::BeginArray,
+                    CollectionType::Object | CollectionType::Mixed => Event::BeginObject,
+                };
+
+                self.events.push(collection_type);
+                Ok(event)
+            }
+            Some(_) => Ok(Event::Scalar(Value::next_from_reader(self)?)),
+            None => Ok(Event::Scalar(Value::None)),
+        }
+    }
+
+    /// Ends the collection most recently started by [Reader::next_event], if any.
+    fn end_event(&mut self) -> ParseResult<Event<'a>> {
+        if self.events.pop().is_none() {
+            return Ok(None);
+        }
+
+        self.end_collection()?;
+        Ok(Some(Event::End))
+    }
+
     /// Obtains the next token from the tokenizer, erroring if the token type doesn't match the expected types.
     pub fn expect_tokens(&mut self, expected_types: &[TokenType]) -> Result<Token, Error> {
-        match self.tokenizer.next() {
+        match self.next_token() {
             Ok(opt) => match opt {
                 Some(token) => match expected_types.contains(&token.token_type) {
                     true => Ok(token),
@@ -81,73 +827,395 @@ impl<'a> Reader<'a> {
 
     /// Tells the reader to begin reading an object or array.
     pub fn begin_collection(&mut self) -> ParseResult<()> {
-        self.expect_token(TokenType::OpenBracket)?;
-        self.increment_depth();
+        let token = self.expect_token(TokenType::OpenBracket)?;
+        self.increment_depth(&token)?;
         Ok(None)
     }
 
     /// Tells the reader to stop reading an object or array.
     pub fn end_collection(&mut self) -> ParseResult<()> {
-        self.expect_token(TokenType::CloseBracket)?;
+        match self.expect_token(TokenType::CloseBracket) {
+            Ok(_) => (),
+            // close unclosed collections at the end of the input
+            Err(e) if self.mode == ParseMode::Lenient && self.tokenizer.is_done() => self.report(e),
+            Err(e) => return Err(e),
+        }
+
         self.decrement_depth()?;
         Ok(None)
     }
 
     /// Reads the next property name and type, if available.
+    ///
+    /// Properties can use any [Operator] to separate their key and value. Use
+    /// [Reader::next_property_with_operator] to find out which one was used.
     pub fn next_property(&mut self) -> ParseResult<PropertyInfo<'a>> {
-        let result = self.tokenizer.peek()?;
-        if result.is_none() {
-            if self.current_depth == 0 {
-                // EOF is a valid end for the root object
+        Ok(self.next_property_with_operator()?.map(|(info, _)| info))
+    }
+
+    /// Reads the next property name and type, if available, along with the [Operator]
+    /// separating the property's key from its value.
+    pub fn next_property_with_operator(&mut self) -> ParseResult<(PropertyInfo<'a>, Operator)> {
+        loop {
+            if let Err(e) = self.read_variable_definitions() {
+                self.recover(e)?;
+                continue;
+            }
+
+            let result = self.peek_token()?;
+            if result.is_none() {
+                if self.current_depth == 0 {
+                    // EOF is a valid end for the root object
+                    return Ok(None);
+                }
+
+                let error = self.parse_error(
+                    ErrorType::UnexpectedTokenError,
+                    String::from("unexpected EOF while reading next property"),
+                );
+                self.recover(error)?;
                 return Ok(None);
             }
 
-            return Err(self.parse_error(
+            let token = result.unwrap();
+            if token.token_type == TokenType::CloseBracket {
+                if self.current_depth == 0 {
+                    let error = self.unexpected_token_error(&token, &[TokenType::CloseBracket]);
+                    // skip the stray bracket
+                    self.recover(error)?;
+                    continue;
+                }
+
+                // we've reached the end of the object, we're done
+                return Ok(None);
+            }
+
+            self.count_entry(token.index)?;
+            match self.read_property() {
+                Ok(property) => return Ok(Some(property)),
+                Err(e) => self.recover(e)?,
+            }
+        }
+    }
+
+    /// Reads the start of the next entry of a collection, or returns `None` at the end of it.
+    ///
+    /// Unlike [Reader::next_property] and [Reader::next_array_value], this reads collections that
+    /// mix properties and bare values, like `{ 1 2 3 a = b }` or `{ key = 1 flag }`. An entry is
+    /// a property if it starts with a key followed by an operator, and a bare value otherwise.
+    pub fn next_entry(&mut self) -> ParseResult<Entry<'a>> {
+        loop {
+            if self.is_collection_ended()? {
+                return Ok(None);
+            }
+
+            let initial_pos = self.tokenizer.position;
+            let token = match self.next_token()? {
+                Some(token) => token,
+                None => return Ok(None),
+            };
+
+            let is_property = KEY_TOKEN_TYPES.contains(&token.token_type)
+                && matches!(
+                    self.peek_token()?,
+                    Some(Token { ref token_type, .. }) if Operator::TOKEN_TYPES.contains(token_type)
+                );
+
+            if !is_property {
+                self.tokenizer.position = initial_pos;
+                return Ok(Some(Entry::Item));
+            }
+
+            let property = match self.key_for_token(&token) {
+                Ok(key) => self.read_property_after(key),
+                Err(e) => Err(e),
+            };
+
+            match property {
+                Ok((info, operator)) => return Ok(Some(Entry::Property(info, operator))),
+                Err(e) => self.recover(e)?,
+            }
+        }
+    }
+
+    /// Reads the start of the next entry of a collection discerned as `collection_type` by
+    /// [Reader::try_discern_array_or_map], or returns `None` at the end of it.
+    ///
+    /// This is like [Reader::next_entry], except that a key and operator in an array are only
+    /// read as a property if a value follows them. Otherwise they're read as bare values, so
+    /// `{ 1 2 = }` is an array with a stray operator rather than a property missing its value.
+    pub(crate) fn next_collection_entry(
+        &mut self,
+        collection_type: CollectionType,
+    ) -> ParseResult<Entry<'a>> {
+        match collection_type {
+            CollectionType::Array => match self.is_collection_ended()? {
+                true => Ok(None),
+                false if self.is_next_property()? => self.next_entry(),
+                false => Ok(Some(Entry::Item)),
+            },
+            CollectionType::Object | CollectionType::Mixed => self.next_entry(),
+        }
+    }
+
+    /// Peeks ahead to see if the next entry of a collection is a property with a key,
+    /// an operator, and a value.
+    pub fn is_next_property(&mut self) -> Result<bool, Error> {
+        let initial_pos = self.tokenizer.position;
+        let result = self.skip_property_start();
+        self.tokenizer.position = initial_pos;
+
+        result
+    }
+
+    /// Skips over a key and an operator, returning whether they were there and followed by a value.
+    fn skip_property_start(&mut self) -> Result<bool, Error> {
+        if !matches!(self.next_token()?, Some(token) if KEY_TOKEN_TYPES.contains(&token.token_type))
+        {
+            return Ok(false);
+        }
+
+        if !matches!(self.next_token()?, Some(token) if Operator::TOKEN_TYPES.contains(&token.token_type))
+        {
+            return Ok(false);
+        }
+
+        Ok(!matches!(
+            self.peek_token()?,
+            None | Some(Token {
+                token_type: TokenType::CloseBracket,
+                ..
+            })
+        ))
+    }
+
+    /// Reads the key, operator, and value type of a property.
+    fn read_property(&mut self) -> Result<(PropertyInfo<'a>, Operator), Error> {
+        let key = self.read_key()?;
+        self.read_property_after(key)
+    }
+
+    /// Reads the operator and value type of a property whose key has been read.
+    fn read_property_after(
+        &mut self,
+        key: ObjectKey<'a>,
+    ) -> Result<(PropertyInfo<'a>, Operator), Error> {
+        // property_name = ...
+        let operator_pos = self.tokenizer.position;
+        let operator = match self.read_operator() {
+            Ok(operator) => operator,
+            // assume a missing = if there's a value after the key
+            Err(e)
+                if self.mode == ParseMode::Lenient
+                    && !matches!(
+                        self.peek_token(),
+                        Ok(None
+                            | Some(Token {
+                                token_type: TokenType::CloseBracket,
+                                ..
+                            }))
+                    ) =>
+            {
+                self.tokenizer.position = operator_pos;
+                self.report(e);
+                Operator::Equals
+            }
+            Err(e) => return Err(e),
+        };
+
+        let real_type = self.peek_next_type()?.ok_or_else(|| {
+            self.parse_error(
                 ErrorType::UnexpectedTokenError,
-                String::from("unexpected EOF while reading next property"),
-            ));
+                String::from("expected value, got EOF"),
+            )
+        })?;
+
+        Ok(((key, real_type), operator))
+    }
+
+    /// Reads the key of a property from the token stream, if available.
+    ///
+    /// Identifiers, dates, integers, strings, and booleans can all be used as keys.
+    pub fn read_key(&mut self) -> Result<ObjectKey<'a>, Error> {
+        let token = self.expect_tokens(&KEY_TOKEN_TYPES)?;
+        self.key_for_token(&token)
+    }
+
+    /// Creates the [ObjectKey] for a key token that's been read.
+    fn key_for_token(&self, token: &Token) -> Result<ObjectKey<'a>, Error> {
+        match token.token_type {
+            TokenType::Identifier => Ok(ObjectKey::Identifier(self.tokenizer.cow_for_token(token))),
+            TokenType::Date => Ok(ObjectKey::Date(self.tokenizer.date_for_token(token)?)),
+            TokenType::Number => {
+                let str = self.tokenizer.str_for_token(token);
+                str.parse::<i64>().map(ObjectKey::Integer).map_err(|_| {
+                    self.parse_error_token(
+                        token,
+                        ErrorType::InvalidNumberError,
+                        format!("expected an integer key, found '{}'", str),
+                    )
+                })
+            }
+            TokenType::String => Ok(ObjectKey::String(unescape(
+                self.tokenizer.cow_for_token(token),
+            ))),
+            TokenType::Boolean => Ok(ObjectKey::Boolean(
+                self.tokenizer.str_for_token(token).starts_with('y'),
+            )),
+            _ => Err(self.unexpected_token_error(token, &KEY_TOKEN_TYPES)),
         }
+    }
 
-        let token = result.unwrap();
-        if token.token_type == TokenType::CloseBracket {
-            if self.current_depth == 0 {
-                return Err(self.unexpected_token_error(&token, &[TokenType::CloseBracket]));
+    /// Reads any scripted variable definitions (`@name = value`) at the current position
+    /// into the reader's [Variables].
+    ///
+    /// [Reader::next_property] and [Reader::is_collection_ended] call this automatically.
+    pub fn read_variable_definitions(&mut self) -> Result<(), Error> {
+        loop {
+            let initial_pos = self.tokenizer.position;
+
+            let name = match self.next_token()? {
+                Some(token) if token.token_type == TokenType::Variable => token,
+                _ => {
+                    self.tokenizer.position = initial_pos;
+                    return Ok(());
+                }
+            };
+
+            if !matches!(
+                self.next_token()?,
+                Some(Token {
+                    token_type: TokenType::Equals,
+                    ..
+                })
+            ) {
+                // a reference to a variable, not a definition
+                self.tokenizer.position = initial_pos;
+                return Ok(());
             }
 
-            // we've reached the end of the object, we're done
-            return Ok(None);
+            let (token, value) = self.expect_value(&VARIABLE_TOKEN_TYPES)?;
+            let name = self.tokenizer.str_for_token(&name).to_owned();
+            Arc::make_mut(&mut self.variables)
+                .insert(&name, Variable::new(token.token_type, value));
         }
+    }
+
+    /// Obtains the next token like [Reader::expect_tokens], along with its contents.
+    ///
+    /// If the token is a reference to a scripted variable, the variable's value is returned
+    /// instead, with the type of the value but the position of the reference. Inline math
+    /// expressions are evaluated and returned as numbers.
+    fn expect_value(
+        &mut self,
+        expected_types: &[TokenType],
+    ) -> Result<(Token, Cow<'a, str>), Error> {
+        let token = match self.next_token()? {
+            Some(token) => token,
+            None => {
+                return Err(self.parse_error(
+                    ErrorType::UnexpectedTokenError,
+                    format!("unexpected EOF, expected {:?}", expected_types),
+                ))
+            }
+        };
 
-        self.tokenizer.next()?;
-        let key: ObjectKey<'a> = match token.token_type {
-            TokenType::Identifier => Ok(self.tokenizer.str_for_token(&token).into()),
-            TokenType::Date => Ok(self.tokenizer.date_for_token(&token)?.into()),
+        let (token, value) = match token.token_type {
+            TokenType::Variable => {
+                let variable = self.resolve_variable(&token)?.clone();
+                let token = Token {
+                    token_type: variable.token_type,
+                    ..token
+                };
+                (token, Cow::Owned(variable.value))
+            }
+            TokenType::InlineMath => {
+                let value = self.evaluate_inline_math(&token)?;
+                let token = Token {
+                    token_type: TokenType::Number,
+                    ..token
+                };
+                (token, Cow::Owned(value))
+            }
             _ => {
-                return Err(
-                    self.unexpected_token_error(&token, &[TokenType::Identifier, TokenType::Date])
-                )
+                let value = self.tokenizer.cow_for_token(&token);
+                (token, value)
+            }
+        };
+
+        match expected_types.contains(&token.token_type) {
+            true => Ok((token, value)),
+            false => Err(self.unexpected_token_error(&token, expected_types)),
+        }
+    }
+
+    /// Peeks the next token, giving references to scripted variables the type of the variable's value
+    /// and inline math expressions the type of a number.
+    fn peek_value(&mut self) -> ParseResult<Token> {
+        match self.peek_token()? {
+            Some(token) if token.token_type == TokenType::Variable => {
+                let token_type = self.resolve_variable(&token)?.token_type.clone();
+                Ok(Some(Token {
+                    token_type,
+                    ..token
+                }))
             }
-        }?;
+            Some(token) if token.token_type == TokenType::InlineMath => Ok(Some(Token {
+                token_type: TokenType::Number,
+                ..token
+            })),
+            token => Ok(token),
+        }
+    }
 
-        // property_name = ...
-        self.expect_token(TokenType::Equals)?;
+    /// Evaluates the inline math expression in the given token, returning the result as the
+    /// text of a number.
+    fn evaluate_inline_math(&self, token: &Token) -> Result<String, Error> {
+        let text = self.tokenizer.str_for_token(token);
+        // skip the @[ and ]
+        let offset = token.index + 2;
 
-        let real_type = self.peek_next_type()?.ok_or(self.parse_error(
-            ErrorType::UnexpectedTokenError,
-            String::from("expected value, got EOF"),
-        ))?;
+        Expression::parse(&text[2..text.len() - 1])
+            .and_then(|expression| expression.evaluate(&self.variables))
+            .map(format_number)
+            .map_err(|e| match e.position {
+                Some(position) => {
+                    self.tokenizer
+                        .parse_error_pos(e.error_type, offset + position, e.message)
+                }
+                None => self.parse_error_token(token, e.error_type, e.message),
+            })
+    }
+
+    /// Finds the scripted variable referenced by the given token.
+    fn resolve_variable(&self, token: &Token) -> Result<&Variable, Error> {
+        let name = self.tokenizer.str_for_token(token);
+        self.variables.get(name).ok_or_else(|| {
+            self.parse_error_token(
+                token,
+                ErrorType::UndefinedVariableError,
+                format!("undefined variable '{}'", name),
+            )
+        })
+    }
 
-        Ok(Some((key, real_type)))
+    /// Reads an [Operator] from the token stream, if available.
+    pub fn read_operator(&mut self) -> Result<Operator, Error> {
+        let token = self.expect_tokens(&Operator::TOKEN_TYPES)?;
+        self.operator_end = Some(token.index + token.length);
+        Ok(Operator::from_token_type(&token.token_type).unwrap())
     }
 
     /// Reads a string from the token stream, if available.
-    pub fn read_string(&mut self) -> Result<&'a str, Error> {
-        let token = self.expect_token(TokenType::String)?;
-        Ok(self.tokenizer.str_for_token(&token))
+    ///
+    /// Escape sequences in the string are replaced, in which case the returned string is owned.
+    pub fn read_string(&mut self) -> Result<Cow<'a, str>, Error> {
+        let (_, str) = self.expect_value(&[TokenType::String])?;
+        Ok(unescape(str))
     }
 
     /// Peeks the next string that would be read from the token stream.
-    pub fn peek_expected_string(&mut self) -> Result<&'a str, Error> {
+    pub fn peek_expected_string(&mut self) -> Result<Cow<'a, str>, Error> {
         let pos = self.tokenizer.position;
         let str = self.read_string()?;
         self.tokenizer.position = pos;
@@ -155,20 +1223,21 @@ impl<'a> Reader<'a> {
     }
 
     /// Reads an identifier from the token stream, if available.
-    pub fn read_identifier(&mut self) -> Result<&'a str, Error> {
-        let token = self.expect_token(TokenType::Identifier)?;
-        Ok(self.tokenizer.str_for_token(&token))
+    pub fn read_identifier(&mut self) -> Result<Cow<'a, str>, Error> {
+        let (_, str) = self.expect_value(&[TokenType::Identifier])?;
+        Ok(str)
     }
 
     /// Reads a string, identifier, or empty from the input stream, if any.
-    pub fn read_stringlike(&mut self) -> Result<&'a str, Error> {
-        let in_property = self.tokenizer.last_char_was('=');
-        let next_token = self.tokenizer.peek()?;
+    pub fn read_stringlike(&mut self) -> Result<Cow<'a, str>, Error> {
+        // an operator was just read, so this is the value of a property
+        let in_property = self.operator_end == Some(self.tokenizer.position);
+        let next_token = self.peek_value()?;
 
         if next_token.is_none() {
             // empty string
             return match in_property {
-                true => Ok(""),
+                true => Ok(Cow::Borrowed("")),
                 false => Err(self.parse_error(
                     ErrorType::UnexpectedTokenError,
                     "expected string or identifier, got EOF",
@@ -180,7 +1249,7 @@ impl<'a> Reader<'a> {
 
         // new line before the next token, also an empty string
         if in_property && self.new_line_between(self.tokenizer.position, next_token.index) {
-            return Ok("");
+            return Ok(Cow::Borrowed(""));
         }
 
         match &next_token.token_type {
@@ -189,7 +1258,7 @@ impl<'a> Reader<'a> {
             t => {
                 // end of collection, it's an empty string
                 if *t == TokenType::CloseBracket && self.current_depth > 0 {
-                    return Ok("");
+                    return Ok(Cow::Borrowed(""));
                 }
 
                 Err(self.parse_error(
@@ -205,36 +1274,137 @@ impl<'a> Reader<'a> {
 
     /// Reads a [Date] from the token stream, if available.
     pub fn read_date(&mut self) -> Result<Date, Error> {
-        let token = self.expect_token(TokenType::Date)?;
-        self.tokenizer.date_for_token(&token)
+        let (token, str) = self.expect_value(&[TokenType::Date])?;
+        self.tokenizer.parse_date(&str, token.index)
+    }
+
+    /// Reads a [Color] block, such as `rgb { 255 128 0 }`, from the token stream, if available.
+    ///
+    /// Only the prefixes listed in the [Dialect]'s `color_prefixes` are accepted.
+    pub fn read_color(&mut self) -> Result<Color, Error> {
+        let token = self.expect_token(TokenType::Identifier)?;
+        let prefix = self.tokenizer.str_for_token(&token);
+        let space = match ColorSpace::from_prefix(prefix) {
+            Some(space) if self.tokenizer.dialect().color_prefixes.contains(&prefix) => space,
+            _ => {
+                return Err(self.parse_error_token(
+                    &token,
+                    ErrorType::UnexpectedTokenError,
+                    format!("expected a color prefix, found '{}'", prefix),
+                ))
+            }
+        };
+
+        self.expect_token(TokenType::OpenBracket)?;
+
+        let color = if space == ColorSpace::Hex {
+            let token = self.expect_tokens(&[TokenType::Identifier, TokenType::Number])?;
+            let str = self.tokenizer.str_for_token(&token);
+            Color::from_hex(str).ok_or_else(|| {
+                self.parse_error_token(
+                    &token,
+                    ErrorType::InvalidValue,
+                    format!("invalid hex color '{}'", str),
+                )
+            })?
+        } else {
+            let mut values = Vec::with_capacity(4);
+            while !self.is_collection_ended()? {
+                values.push(self.read_number::<f64>()?);
+            }
+
+            match values[..] {
+                [a, b, c] => Color::new(space, [a, b, c]),
+                [a, b, c, alpha] => Color::new(space, [a, b, c]).with_alpha(alpha),
+                _ => {
+                    return Err(self.parse_error_token(
+                        &token,
+                        ErrorType::InvalidLength,
+                        format!("expected 3 or 4 color components, found {}", values.len()),
+                    ))
+                }
+            }
+        };
+
+        self.expect_token(TokenType::CloseBracket)?;
+        Ok(color)
+    }
+
+    /// Reads a block containing parameters or conditional blocks, such as the body of a
+    /// scripted effect, as a [Template].
+    ///
+    /// The template's text is the contents of the block, without the surrounding brackets.
+    pub fn read_template(&mut self) -> Result<Template<'a>, Error> {
+        let (start, end) = self.skip_template_block()?;
+        // comments are kept as part of the template's text
+        self.take_comments_in(start, end);
+
+        Template::parse(self.tokenizer.cow_for_range((start, end))).map_err(|e| {
+            self.tokenizer
+                .parse_error_pos(e.error_type, start + e.position.unwrap_or(0), e.message)
+        })
+    }
+
+    /// Skips over a block, returning the range of its contents.
+    fn skip_template_block(&mut self) -> Result<(usize, usize), Error> {
+        let open = self.expect_token(TokenType::OpenBracket)?;
+        let start = open.index + open.length;
+        let mut depth = 1;
+        self.check_depth(self.current_depth + depth, &open)?;
+
+        loop {
+            let token = self.next_token()?.ok_or_else(|| {
+                self.parse_error(
+                    ErrorType::UnexpectedTokenError,
+                    "expected close bracket, found EOF",
+                )
+            })?;
+
+            match token.token_type {
+                TokenType::OpenBracket => {
+                    depth += 1;
+                    self.check_depth(self.current_depth + depth, &token)?;
+                }
+                TokenType::CloseBracket => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Ok((start, token.index));
+                    }
+                }
+                _ => (),
+            }
+        }
     }
 
     /// Reads a boolean from the token stream, if available.
     pub fn read_boolean(&mut self) -> Result<bool, Error> {
-        let token = self.expect_token(TokenType::Boolean)?;
-        let str = self.tokenizer.str_for_token(&token);
+        let (_, str) = self.expect_value(&[TokenType::Boolean])?;
         Ok(str.starts_with('y'))
     }
 
     /// Reads a number from the token stream, if available.
     pub fn read_number<T: FromStr>(&mut self) -> Result<T, Error> {
-        let token = self.expect_token(TokenType::Number)?;
-        let str = self.tokenizer.str_for_token(&token);
-        self.parse_number(str)
+        let (token, str) = self.expect_value(&[TokenType::Number])?;
+        self.parse_number_at(&str, token.index)
     }
 
     /// Read a number from the token stream, returning its string value.
-    pub fn read_number_as_str(&mut self) -> Result<&'a str, Error> {
-        let token = self.expect_token(TokenType::Number)?;
-        Ok(self.tokenizer.str_for_token(&token))
+    pub fn read_number_as_str(&mut self) -> Result<Cow<'a, str>, Error> {
+        let (_, str) = self.expect_value(&[TokenType::Number])?;
+        Ok(str)
     }
 
     /// Parses a number from a string.
     pub fn parse_number<T: FromStr>(&self, str: &str) -> Result<T, Error> {
+        self.parse_number_at(str, self.tokenizer.position.saturating_sub(str.len()))
+    }
+
+    /// Parses a number from the text of a number token that starts at `position`.
+    fn parse_number_at<T: FromStr>(&self, str: &str, position: usize) -> Result<T, Error> {
         str.parse::<T>().map_err(|_| {
             self.tokenizer.parse_error_pos(
                 ErrorType::InvalidNumberError,
-                self.tokenizer.position - str.len(),
+                position,
                 format!("failed to parse number from token '{}'", str),
             )
         })
@@ -242,7 +1412,7 @@ impl<'a> Reader<'a> {
 
     /// Reads the next array value's type, if available.
     pub fn next_array_value(&mut self) -> ParseResult<RealType> {
-        let result = self.tokenizer.peek()?;
+        let result = self.peek_value()?;
         if result.is_none() {
             // end of the array
             return Ok(None);
@@ -254,33 +1424,158 @@ impl<'a> Reader<'a> {
             return Ok(None);
         }
 
-        let real_type =
-            RealType::from_token_type(&token.token_type).ok_or(self.parse_error_token(
-                &token,
-                ErrorType::UnexpectedTokenError,
-                format!("unexpected token type {:?} in array", token.token_type),
-            ))?;
-
-        Ok(Some(real_type))
+        self.count_entry(token.index)?;
+        Ok(Some(self.real_type_for(&token, "array")?))
     }
 
     /// Peeks the type of the next value in the token stream, if any.
     pub fn peek_next_type(&mut self) -> ParseResult<RealType> {
         // peek at the next token to get its type
-        let token = self.tokenizer.peek()?;
+        let token = self.peek_value()?;
 
         match token {
-            Some(token) => Ok(Some(RealType::from_token_type(&token.token_type).ok_or(
-                self.parse_error_token(
-                    &token,
-                    ErrorType::UnexpectedTokenError,
-                    format!("unexpected token type {:?} in value", token.token_type),
-                ),
-            )?)),
+            Some(token) => Ok(Some(self.real_type_for(&token, "value")?)),
             None => Ok(None),
         }
     }
 
+    /// Finds the [RealType] of the value starting at `token`, which must be the next token.
+    fn real_type_for(&mut self, token: &Token, context: &str) -> Result<RealType, Error> {
+        if token.token_type == TokenType::Identifier && self.is_next_color()? {
+            return Ok(RealType::Color);
+        }
+
+        RealType::from_token_type(&token.token_type).ok_or_else(|| {
+            self.parse_error_token(
+                token,
+                ErrorType::UnexpectedTokenError,
+                format!(
+                    "unexpected token type {:?} in {}",
+                    token.token_type, context
+                ),
+            )
+        })
+    }
+
+    /// Peeks ahead to see if the next tokens are a color prefix followed by an open bracket.
+    fn is_next_color(&mut self) -> Result<bool, Error> {
+        let initial_pos = self.tokenizer.position;
+
+        let is_color = match self.next_token()? {
+            Some(token) if token.token_type == TokenType::Identifier => {
+                self.is_color_prefix(&token)
+                    && matches!(
+                        self.next_token()?,
+                        Some(Token {
+                            token_type: TokenType::OpenBracket,
+                            ..
+                        })
+                    )
+            }
+            _ => false,
+        };
+
+        self.tokenizer.position = initial_pos;
+        Ok(is_color)
+    }
+
+    /// Checks if the given identifier token is a color prefix supported by the [Dialect].
+    fn is_color_prefix(&self, token: &Token) -> bool {
+        let prefix = self.tokenizer.str_for_token(token);
+        ColorSpace::from_prefix(prefix).is_some()
+            && self.tokenizer.dialect().color_prefixes.contains(&prefix)
+    }
+
+    /// Skips over the next value without reading it, returning the [Span] of its source text.
+    ///
+    /// Objects and arrays are skipped by balancing their brackets, without reading their
+    /// contents into memory. Scripted variables defined inside a skipped value aren't collected,
+    /// and comments inside it are discarded.
+    pub fn skip_value(&mut self) -> Result<Span, Error> {
+        let token = self.next_token()?.ok_or_else(|| {
+            self.parse_error(ErrorType::UnexpectedTokenError, "expected value, got EOF")
+        })?;
+
+        // strings include their quotes
+        let quote = (token.token_type == TokenType::String) as usize;
+        let start = token.index - quote;
+        let end = match token.token_type {
+            TokenType::OpenBracket => self.skip_block(&token)?,
+            TokenType::Identifier
+                if self.is_color_prefix(&token)
+                    && matches!(
+                        self.peek_token()?,
+                        Some(Token {
+                            token_type: TokenType::OpenBracket,
+                            ..
+                        })
+                    ) =>
+            {
+                let open = self.next_token()?.unwrap();
+                self.skip_block(&open)?
+            }
+            t if VALUE_TOKEN_TYPES.contains(&t) => token.index + token.length + quote,
+            _ => return Err(self.unexpected_token_error(&token, &VALUE_TOKEN_TYPES)),
+        };
+
+        self.take_comments_in(start, end);
+        Ok(Span::new(self.source(), start, end))
+    }
+
+    /// Reads the next value as its exact source text, without parsing it, along with its [Span].
+    ///
+    /// This is useful for extracting part of a large file cheaply, such as a single country
+    /// from a save. See [Reader::skip_value] for more information.
+    pub fn read_raw_value(&mut self) -> Result<(Cow<'a, str>, Span), Error> {
+        // keep the value's text buffered while skipping it, in case the input is streamed
+        self.tokenizer.retain(Some(self.tokenizer.position));
+        let span = self.skip_value();
+        self.tokenizer.retain(None);
+
+        let span = span?;
+        Ok((self.tokenizer.cow_for_range((span.start, span.end)), span))
+    }
+
+    /// Skips to the end of a block whose open bracket, `open`, has been read, returning the position
+    /// after its close bracket.
+    fn skip_block(&mut self, open: &Token) -> Result<usize, Error> {
+        let mut depth = 1;
+        self.check_depth(self.current_depth + depth, open)?;
+
+        loop {
+            let token = match self.next_token()? {
+                Some(token) => token,
+                None => {
+                    let error = self.parse_error(
+                        ErrorType::UnexpectedTokenError,
+                        "expected close bracket, found EOF",
+                    );
+                    // close unclosed blocks at the end of the input
+                    match self.mode {
+                        ParseMode::Lenient => self.report(error),
+                        ParseMode::Strict => return Err(error),
+                    }
+
+                    return Ok(self.tokenizer.position);
+                }
+            };
+
+            match token.token_type {
+                TokenType::OpenBracket => {
+                    depth += 1;
+                    self.check_depth(self.current_depth + depth, &token)?;
+                }
+                TokenType::CloseBracket => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Ok(token.index + token.length);
+                    }
+                }
+                _ => (),
+            }
+        }
+    }
+
     /// Peeks the type of the next value in the token stream like [Reader::peek_next_type],
     /// but this method will error if EOF is encountered.
     pub fn peek_next_type_expect(&mut self) -> Result<RealType, Error> {
@@ -295,31 +1590,42 @@ impl<'a> Reader<'a> {
 
     /// Peeks ahead to see if this collection (array or object) has finished
     pub fn is_collection_ended(&mut self) -> Result<bool, Error> {
-        match self.tokenizer.peek()? {
+        if let Err(e) = self.read_variable_definitions() {
+            self.recover(e)?;
+        }
+
+        match self.peek_token()? {
             None => {
                 if self.current_depth == 0 {
                     return Ok(true);
                 }
 
-                return Err(self.parse_error(
+                let error = self.parse_error(
                     ErrorType::UnexpectedTokenError,
                     "expected value or close bracket, found EOF",
-                ));
+                );
+                self.recover(error)?;
+                Ok(true)
             }
             Some(token) => match token.token_type {
                 TokenType::CloseBracket => Ok(true),
-                _ => Ok(false),
+                _ => {
+                    self.count_entry(token.index)?;
+                    Ok(false)
+                }
             },
         }
     }
 
     /// Attempt to discern between an array or map by peeking ahead.
     ///
-    /// Empty collections (`{}`) will return Array.
+    /// Empty collections (`{}`) will return Array. Only the first entry is checked, so collections
+    /// that mix properties and bare values are discerned by their first entry. Use
+    /// [Reader::discern_collection] to find out if a collection is [CollectionType::Mixed].
     pub fn try_discern_array_or_map(&mut self) -> ParseResult<CollectionType> {
         let initial_pos = self.tokenizer.position;
 
-        let token = self.tokenizer.next()?;
+        let token = self.next_token()?;
         if token.is_none() {
             self.tokenizer.position = initial_pos;
             return Ok(None);
@@ -336,7 +1642,7 @@ impl<'a> Reader<'a> {
             ));
         }
 
-        let next = self.tokenizer.next()?;
+        let next = self.next_token()?;
         if next.is_none() {
             self.tokenizer.position = initial_pos;
             return Ok(None);
@@ -345,13 +1651,13 @@ impl<'a> Reader<'a> {
         let next = next.unwrap();
         let collection_type = match next.token_type {
             TokenType::CloseBracket => Some(CollectionType::Array),
-            TokenType::Identifier | TokenType::Date => {
-                // if it's an object, there will be an equals
-                let next = self.tokenizer.next()?;
+            t if KEY_TOKEN_TYPES.contains(&t) || t == TokenType::Variable => {
+                // if it's an object, there will be an operator
+                let next = self.next_token()?;
                 if let Some(next) = next {
-                    match next.token_type {
-                        TokenType::Equals => Some(CollectionType::Object),
-                        _ => Some(CollectionType::Array),
+                    match Operator::from_token_type(&next.token_type) {
+                        Some(_) => Some(CollectionType::Object),
+                        None => Some(CollectionType::Array),
                     }
                 } else {
                     None
@@ -365,12 +1671,117 @@ impl<'a> Reader<'a> {
         Ok(collection_type)
     }
 
+    /// Finds whether the next collection is an object, an array, or a mix of both, without
+    /// changing the position of the reader.
+    ///
+    /// Unlike [Reader::try_discern_array_or_map], which only looks at the first entry, this reads
+    /// every entry of the collection, so it's much slower. Empty collections (`{}`) are arrays.
+    /// In lenient mode, returns `None` if the collection isn't closed before the end of the input.
+    ///
+    /// The types of the collections nested inside it are found along the way, and remembered until
+    /// a collection outside of them is discerned, so discerning those isn't slow.
+    pub fn discern_collection(&mut self) -> ParseResult<CollectionType> {
+        let checkpoint = self.checkpoint();
+        let result = self.read_collection_types();
+        self.restore(checkpoint);
+
+        result
+    }
+
+    /// Reads through the next collection and every collection nested inside it, remembering
+    /// which kinds of entries each of them has, and returns the type of the outer one.
+    fn read_collection_types(&mut self) -> ParseResult<CollectionType> {
+        if let Some(token) = self.peek_token()? {
+            let found = self
+                .discerned
+                .binary_search_by_key(&token.index, |(start, _)| *start);
+            if let Ok(i) = found {
+                return Ok(Some(self.discerned[i].1));
+            }
+        }
+
+        let mut stack = Vec::new();
+        if !self.begin_discerning(&mut stack)? {
+            return Ok(None);
+        }
+
+        // collections are closed in the reverse of the order they're opened in
+        let mut discerned = Vec::new();
+        while let Some(collection) = stack.last_mut() {
+            match self.next_collection_entry(collection.collection_type)? {
+                Some(entry) => {
+                    match entry {
+                        Entry::Property(..) => collection.properties = true,
+                        Entry::Item => collection.items = true,
+                    }
+
+                    let nested = matches!(self.peek_next_type()?, Some(RealType::ObjectOrArray));
+                    if !(nested && self.begin_discerning(&mut stack)?) {
+                        self.skip_value()?;
+                    }
+                }
+                None => {
+                    // an unclosed collection is only read to the end in lenient mode
+                    if !matches!(
+                        self.peek_token()?,
+                        Some(Token {
+                            token_type: TokenType::CloseBracket,
+                            ..
+                        })
+                    ) {
+                        return Ok(None);
+                    }
+
+                    let collection = stack.pop().expect("the stack isn't empty");
+                    discerned.push((collection.start, collection.collection_type()));
+                    if !stack.is_empty() {
+                        self.end_collection()?;
+                    }
+                }
+            }
+        }
+
+        let collection_type = discerned
+            .last()
+            .map(|(_, collection_type)| *collection_type);
+        discerned.sort_unstable_by_key(|(start, _)| *start);
+        self.discerned = discerned;
+
+        Ok(collection_type)
+    }
+
+    /// Opens the next collection for [Reader::read_collection_types], returning false if the
+    /// input ends right after its opening bracket.
+    fn begin_discerning(&mut self, stack: &mut Vec<Discerning>) -> Result<bool, Error> {
+        let start = match self.peek_token()? {
+            Some(token) => token.index,
+            None => self.tokenizer.position,
+        };
+
+        let collection_type = match self.try_discern_array_or_map()? {
+            Some(collection_type) => collection_type,
+            None => return Ok(false),
+        };
+
+        self.begin_collection()?;
+        stack.push(Discerning {
+            start,
+            collection_type,
+            properties: false,
+            items: false,
+        });
+
+        Ok(true)
+    }
+
     /// Checks if this property might not have a value.
     pub fn is_next_value_empty(&mut self) -> Result<bool, Error> {
-        let next_token = self.tokenizer.peek()?;
+        let next_token = self.peek_token()?;
 
         Ok(match next_token {
             Some(next_token) => match next_token.token_type {
+                // an identifier starting a color block is the value
+                TokenType::Identifier if self.is_next_color()? => false,
                 // the next token is an identifier, meaning there's no value, just the next property
                 TokenType::Identifier => true,
                 // the next token is the end of the collection, so there's no value
@@ -398,7 +1809,7 @@ impl<'a> Reader<'a> {
     }
 
     /// Checks if there's a new line between the exclusive range `[start, end]``
-    fn new_line_between(&self, start: usize, end: usize) -> bool {
+    pub(crate) fn new_line_between(&self, start: usize, end: usize) -> bool {
         self.tokenizer.find_end_of_line(start) < end
     }
 
@@ -415,8 +1826,30 @@ impl<'a> Reader<'a> {
     }
 }
 
+/// Returns whether a [Reader] in [ParseMode::Lenient] can skip past an error and keep reading.
+///
+/// Errors reading a stream can't be skipped, since reading again would fail the same way,
+/// and neither can input that goes over the [Limits] of the reader.
+fn is_recoverable(error: &Error) -> bool {
+    !matches!(
+        error.error_type,
+        ErrorType::IoError
+            | ErrorType::EncodingError
+            | ErrorType::InvalidState
+            | ErrorType::LimitError
+    )
+}
+
 impl<'a> ErrorContextProvider for Reader<'a> {
     fn get_line_context(&self, position: usize, max_lines: usize) -> Option<ErrorContext> {
         self.tokenizer.get_line_context(position, max_lines)
     }
+
+    fn source_id(&self) -> SourceId {
+        self.tokenizer.source()
+    }
+
+    fn path(&self) -> Option<&Path> {
+        self.tokenizer.path()
+    }
 }
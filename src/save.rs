@@ -0,0 +1,339 @@
+use std::borrow::Cow;
+
+use md5::{Digest, Md5};
+
+use crate::{
+    encoding::{self, Encoding},
+    reader::Reader,
+    tokenizer::Tokenizer,
+    util::error::{Error, ErrorType},
+};
+
+/// The magic text at the start of the headers used by newer games, such as CK3 and Victoria 3.
+const SAV_MAGIC: &str = "SAV";
+/// The length of a `SAV` header line, not including the line break.
+const SAV_HEADER_LENGTH: usize = 23;
+/// The key of the property at the end of a save file holding its checksum.
+const CHECKSUM_KEY: &str = "checksum";
+
+/// The header at the start of a save file.
+///
+/// Older games, such as EU4 and HOI4, start their saves with the name of the game followed by
+/// `txt` or `bin` (`EU4txt`). Newer games start them with `SAV`, followed by hex digits
+/// giving the header version, the save's format, and the size of the metadata section
+/// (`SAV0100c1f3a2b400004a3b`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SaveHeader {
+    /// The magic text at the start of the header, such as `EU4txt` or `SAV`.
+    pub magic: String,
+    /// The version of the header, for `SAV` headers.
+    pub version: Option<u16>,
+    /// The raw format flags of the header, for `SAV` headers.
+    ///
+    /// Even values are text saves and odd values are binary. `0` and `1` are uncompressed,
+    /// while higher values are zip archives.
+    pub flags: Option<u16>,
+    /// Whether the save is in the binary format, rather than text.
+    pub binary: bool,
+    /// Whether the save is compressed into a zip archive.
+    pub compressed: bool,
+    /// The size in bytes of the metadata section following the header, for `SAV` headers.
+    pub metadata_size: Option<usize>,
+    /// The length of the header in bytes, including its line break.
+    pub length: usize,
+}
+
+impl SaveHeader {
+    /// Parses the header at the start of a save file.
+    ///
+    /// Only the header itself needs to be valid text, so this works on compressed and binary saves.
+    pub fn parse(bytes: &[u8]) -> Result<SaveHeader, Error> {
+        let line_end = bytes
+            .iter()
+            .take(SAV_HEADER_LENGTH + 2)
+            .position(|b| *b == b'\n')
+            .ok_or_else(|| header_error("missing save header"))?;
+        let length = line_end + 1;
+
+        let line = std::str::from_utf8(&bytes[..line_end])
+            .map(|line| line.trim_end_matches('\r'))
+            .map_err(|_| header_error("save header isn't valid text"))?;
+
+        if let Some(fields) = line.strip_prefix(SAV_MAGIC) {
+            return SaveHeader::parse_sav(fields, length);
+        }
+
+        let (game, binary) = match (line.strip_suffix("txt"), line.strip_suffix("bin")) {
+            (Some(game), _) => (game, false),
+            (_, Some(game)) => (game, true),
+            _ => return Err(header_error(format!("unrecognized save header '{}'", line))),
+        };
+
+        if game.is_empty() || !game.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return Err(header_error(format!("unrecognized save header '{}'", line)));
+        }
+
+        Ok(SaveHeader {
+            magic: line.to_owned(),
+            version: None,
+            flags: None,
+            binary,
+            compressed: false,
+            metadata_size: None,
+            length,
+        })
+    }
+
+    /// Parses the hex fields following `SAV` in a header.
+    fn parse_sav(fields: &str, length: usize) -> Result<SaveHeader, Error> {
+        if fields.len() != SAV_HEADER_LENGTH - SAV_MAGIC.len()
+            || !fields.chars().all(|c| c.is_ascii_hexdigit())
+        {
+            return Err(header_error(format!("invalid save header 'SAV{}'", fields)));
+        }
+
+        // SAV, then 2 digits of version, 2 of flags, 8 of random data, and 8 of metadata size
+        let hex = |range: std::ops::Range<usize>| usize::from_str_radix(&fields[range], 16);
+        let version = hex(0..2).unwrap() as u16;
+        let flags = hex(2..4).unwrap() as u16;
+        let metadata_size = hex(12..20).unwrap();
+
+        Ok(SaveHeader {
+            magic: SAV_MAGIC.to_owned(),
+            version: Some(version),
+            flags: Some(flags),
+            binary: flags % 2 == 1,
+            compressed: flags > 1,
+            metadata_size: Some(metadata_size),
+            length,
+        })
+    }
+
+    /// Returns true if the rest of the save is uncompressed text.
+    pub fn is_text(&self) -> bool {
+        !self.binary && !self.compressed
+    }
+}
+
+fn header_error(message: impl ToString) -> Error {
+    Error::new_positioned(ErrorType::SaveHeaderError, 0, message)
+}
+
+/// A text save file, made up of a [SaveHeader], the game state, and a checksum footer.
+///
+/// [Reader::new] can't read a save directly, since the header isn't valid Clausewitz syntax.
+/// [SaveFile::reader] returns a [Reader] that skips the header and the checksum, and
+/// [SaveFile::metadata_reader] returns one that only reads the metadata section.
+///
+/// [SaveFile::compute_checksum] takes the MD5 hash of every byte of the file before the
+/// `checksum = "..."` footer, including the header. This is a guess at the algorithm the games
+/// use that hasn't been checked against saves they've written, so a checksum it computes may
+/// not be one a game would accept.
+pub struct SaveFile<'a> {
+    text: Cow<'a, str>,
+    /// The original bytes of the file, if it was transcoded from another encoding.
+    bytes: Option<&'a [u8]>,
+    header: SaveHeader,
+    /// The byte offset of the start of the checksum footer, or the end of the text if there isn't one.
+    footer_start: usize,
+    checksum: Option<(usize, usize)>,
+}
+
+impl<'a> SaveFile<'a> {
+    /// Parses the header and footer of a text save.
+    ///
+    /// Returns an [Unsupported](`crate::error::ErrorType::Unsupported`) error if the save is
+    /// binary or compressed.
+    pub fn parse(text: &'a str) -> Result<SaveFile<'a>, Error> {
+        let header = SaveHeader::parse(text.as_bytes())?;
+        SaveFile::new(Cow::Borrowed(text), None, header)
+    }
+
+    /// Parses the header and footer of a text save from raw bytes in the given [Encoding].
+    pub fn from_bytes(bytes: &'a [u8], encoding: Encoding) -> Result<SaveFile<'a>, Error> {
+        let header = SaveHeader::parse(bytes)?;
+        if !header.is_text() {
+            return Err(unsupported_error(&header));
+        }
+
+        let text = encoding::decode(bytes, encoding)?;
+        let original = match text {
+            Cow::Owned(_) => Some(bytes),
+            Cow::Borrowed(_) => None,
+        };
+
+        SaveFile::new(text, original, header)
+    }
+
+    fn new(
+        text: Cow<'a, str>,
+        bytes: Option<&'a [u8]>,
+        header: SaveHeader,
+    ) -> Result<SaveFile<'a>, Error> {
+        if !header.is_text() {
+            return Err(unsupported_error(&header));
+        }
+
+        let (footer_start, checksum) = match find_checksum(&text) {
+            Some((start, value)) => (start, Some(value)),
+            None => (text.len(), None),
+        };
+
+        Ok(SaveFile {
+            text,
+            bytes,
+            header,
+            footer_start,
+            checksum,
+        })
+    }
+
+    /// Returns the header of this save.
+    pub fn header(&self) -> &SaveHeader {
+        &self.header
+    }
+
+    /// Returns the text of this save, including the header and footer.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Returns the text between the header and the checksum footer.
+    pub fn gamestate(&self) -> &str {
+        &self.text[self.header.length..self.footer_start]
+    }
+
+    /// Returns the text of the metadata section, if the header gives its size.
+    pub fn metadata(&self) -> Option<&str> {
+        self.metadata_end()
+            .map(|end| &self.text[self.header.length..end])
+    }
+
+    /// Creates a [Reader] for the game state, skipping the header and checksum footer.
+    ///
+    /// Positions in errors are byte offsets into the whole save.
+    pub fn reader(&self) -> Reader<'_> {
+        self.reader_until(self.footer_start)
+    }
+
+    /// Creates a [Reader] that only reads the metadata section, if the header gives its size.
+    ///
+    /// This avoids parsing the rest of the save when only the metadata is needed.
+    pub fn metadata_reader(&self) -> Option<Reader<'_>> {
+        self.metadata_end().map(|end| self.reader_until(end))
+    }
+
+    /// Returns the checksum stored in the footer of this save, if it has one.
+    pub fn checksum(&self) -> Option<&str> {
+        self.checksum.map(|(start, end)| &self.text[start..end])
+    }
+
+    /// Computes the MD5 hash of the contents of this save, which may be its checksum.
+    ///
+    /// Transcoded saves are hashed in their original encoding. See [SaveFile] for why this
+    /// might not match the checksum a game computes.
+    pub fn compute_checksum(&self) -> String {
+        let contents = match self.bytes {
+            // the footer is plain ASCII, so it's the same length before and after transcoding
+            Some(bytes) => &bytes[..bytes.len() - (self.text.len() - self.footer_start)],
+            None => self.text[..self.footer_start].as_bytes(),
+        };
+
+        md5_hex(contents)
+    }
+
+    /// Returns true if the checksum in the footer is the one [SaveFile::compute_checksum] computes.
+    ///
+    /// Returns false if the save doesn't have a checksum. This doesn't guarantee a game would
+    /// accept the save, since the checksum algorithm is a guess.
+    pub fn checksum_matches(&self) -> bool {
+        self.checksum()
+            .is_some_and(|checksum| checksum.eq_ignore_ascii_case(&self.compute_checksum()))
+    }
+
+    /// Returns the text of this save with its checksum recomputed, adding a footer if it doesn't
+    /// have one.
+    ///
+    /// The returned text is UTF-8 even if the save was transcoded, so the checksum is computed
+    /// over the returned text rather than the original bytes.
+    pub fn with_updated_checksum(&self) -> String {
+        match self.checksum {
+            Some((start, end)) => {
+                let checksum = md5_hex(self.text[..self.footer_start].as_bytes());
+                [&self.text[..start], &checksum, &self.text[end..]].concat()
+            }
+            None => {
+                let mut text = self.text.to_string();
+                if !text.is_empty() && !text.ends_with('\n') {
+                    text.push('\n');
+                }
+
+                let checksum = md5_hex(text.as_bytes());
+                text + &format!("{}=\"{}\"\n", CHECKSUM_KEY, checksum)
+            }
+        }
+    }
+
+    fn metadata_end(&self) -> Option<usize> {
+        let end = self.header.length + self.header.metadata_size?;
+        let mut end = usize::min(end, self.footer_start);
+        while !self.text.is_char_boundary(end) {
+            end -= 1;
+        }
+
+        Some(end)
+    }
+
+    fn reader_until(&self, end: usize) -> Reader<'_> {
+        let mut tokenizer = Tokenizer::new(&self.text[..end]);
+        tokenizer.position = self.header.length;
+        Reader::from_tokenizer(tokenizer)
+    }
+}
+
+fn unsupported_error(header: &SaveHeader) -> Error {
+    let format = match (header.binary, header.compressed) {
+        (true, true) => "compressed binary",
+        (true, false) => "binary",
+        _ => "compressed",
+    };
+
+    Error::new_positioned(
+        ErrorType::Unsupported,
+        0,
+        format!("{} saves aren't supported", format),
+    )
+}
+
+/// Returns the MD5 hash of `data` as a lowercase hex string.
+fn md5_hex(data: &[u8]) -> String {
+    Md5::digest(data)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// Finds the `checksum = "..."` footer at the end of a save, returning the byte offset of the
+/// start of the footer and the bounds of the checksum inside its quotes.
+fn find_checksum(text: &str) -> Option<(usize, (usize, usize))> {
+    let trimmed = text.trim_end();
+    if !trimmed.ends_with('"') {
+        return None;
+    }
+
+    let key_start = trimmed.rfind(CHECKSUM_KEY)?;
+    let footer_start = text[..key_start].trim_end_matches([' ', '\t']).len();
+    if footer_start > 0 && !text[..footer_start].ends_with('\n') {
+        return None;
+    }
+
+    let rest = text[key_start + CHECKSUM_KEY.len()..trimmed.len()].trim_start();
+    let rest = rest.strip_prefix('=')?.trim_start();
+    let value = rest.strip_prefix('"')?.strip_suffix('"')?;
+    if !value.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return None;
+    }
+
+    let value_start = trimmed.len() - 1 - value.len();
+    Some((footer_start, (value_start, value_start + value.len())))
+}
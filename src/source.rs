@@ -0,0 +1,301 @@
+use std::borrow::Cow;
+
+#[cfg(feature = "serde")]
+use serde::Serialize;
+
+use crate::{encoding::Encoding, error::Error, reader::Reader, tokenizer::Tokenizer};
+
+/// Identifies a source file registered with a [SourceMap].
+///
+/// Text that wasn't registered with a [SourceMap] uses [SourceId::UNKNOWN].
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct SourceId(u32);
+
+impl SourceId {
+    /// The source of text that wasn't registered with a [SourceMap].
+    pub const UNKNOWN: SourceId = SourceId(0);
+
+    /// Returns true if this is [SourceId::UNKNOWN].
+    pub fn is_unknown(&self) -> bool {
+        *self == SourceId::UNKNOWN
+    }
+}
+
+/// A range of bytes in a source file.
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Span {
+    /// The source file this span is in.
+    pub source: SourceId,
+    /// The byte offset of the start of this span.
+    pub start: usize,
+    /// The byte offset just past the end of this span.
+    pub end: usize,
+}
+
+impl Span {
+    /// Creates a new [Span] covering `start..end` in the given source.
+    pub fn new(source: SourceId, start: usize, end: usize) -> Span {
+        Span { source, start, end }
+    }
+
+    /// Creates an empty [Span] at the given position.
+    pub fn at(source: SourceId, position: usize) -> Span {
+        Span::new(source, position, position)
+    }
+
+    /// Returns the length of this span in bytes.
+    pub fn len(&self) -> usize {
+        self.end.saturating_sub(self.start)
+    }
+
+    /// Returns true if this span doesn't cover any bytes.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns true if `position` is inside this span.
+    pub fn contains(&self, position: usize) -> bool {
+        (self.start..self.end).contains(&position)
+    }
+
+    /// Returns the smallest span covering both this span and `other`.
+    ///
+    /// Both spans should be in the same source file.
+    pub fn to(&self, other: Span) -> Span {
+        Span::new(
+            self.source,
+            usize::min(self.start, other.start),
+            usize::max(self.end, other.end),
+        )
+    }
+}
+
+/// The byte offset of the start of every line in a piece of text.
+///
+/// Building the index scans the text once, after which a position can be turned into a
+/// line and column without rescanning the text before it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineIndex {
+    line_starts: Vec<usize>,
+}
+
+impl LineIndex {
+    /// Builds the line index for the given text.
+    pub fn new(text: &str) -> LineIndex {
+        let mut line_starts = vec![0];
+        line_starts.extend(
+            text.bytes()
+                .enumerate()
+                .filter(|(_, b)| *b == b'\n')
+                .map(|(i, _)| i + 1),
+        );
+
+        LineIndex { line_starts }
+    }
+
+    /// Returns the number of lines in the text.
+    pub fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+
+    /// Returns the line that `position` is on, starting at 1.
+    ///
+    /// A line break is counted as the end of the line it's on.
+    pub fn line(&self, position: usize) -> usize {
+        self.line_starts.partition_point(|start| *start <= position)
+    }
+
+    /// Returns the byte range of the given line, including its line break.
+    ///
+    /// Lines start at 1. Returns `None` if the text doesn't have that many lines.
+    pub fn line_range(&self, text: &str, line: usize) -> Option<(usize, usize)> {
+        let start = *self.line_starts.get(line.checked_sub(1)?)?;
+        let end = self.line_starts.get(line).copied().unwrap_or(text.len());
+        Some((start, end))
+    }
+
+    /// Turns a byte offset in `text` into a (line, col) pair.
+    ///
+    /// Both the line and the column start at 1. The column is counted in characters, not bytes.
+    pub fn line_col(&self, text: &str, position: usize) -> (usize, usize) {
+        let position = usize::min(position, text.len());
+        let line = self.line(position);
+        let line_start = self.line_starts[line - 1];
+
+        // count the characters by counting every byte that isn't a UTF-8 continuation byte
+        let col = text.as_bytes()[line_start..position]
+            .iter()
+            .filter(|b| (**b & 0xC0) != 0x80)
+            .count();
+
+        (line, col + 1)
+    }
+}
+
+/// A source file registered with a [SourceMap].
+#[derive(Debug)]
+pub struct SourceFile {
+    name: String,
+    text: String,
+    lines: LineIndex,
+}
+
+impl SourceFile {
+    /// Creates a new [SourceFile] with the given name and text, building its line index.
+    pub fn new(name: impl Into<String>, text: impl Into<String>) -> SourceFile {
+        let text = text.into();
+        SourceFile {
+            name: name.into(),
+            lines: LineIndex::new(&text),
+            text,
+        }
+    }
+
+    /// Returns the name of this file, usually its path.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the text of this file.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Returns the line index of this file.
+    pub fn line_index(&self) -> &LineIndex {
+        &self.lines
+    }
+
+    /// Turns a byte offset in this file into a (line, col) pair, both starting at 1.
+    pub fn line_col(&self, position: usize) -> (usize, usize) {
+        self.lines.line_col(&self.text, position)
+    }
+}
+
+/// A collection of source files, such as every file in a mod.
+///
+/// Each file added to the map is given a [SourceId]. Tokens, errors, and spans produced by a
+/// [Tokenizer] or [Reader] created from the map carry that ID, so the map can turn them back
+/// into `file:line:col` locations:
+///
+/// ```
+/// # use clauser::{source::SourceMap, value::Value};
+/// let mut sources = SourceMap::new();
+/// let id = sources.add("common/buildings/farm.txt", "farm = {\n    cost = }\n}");
+///
+/// let mut reader = sources.reader(id).unwrap();
+/// let error = Value::from_reader(&mut reader).unwrap_err();
+/// assert_eq!(
+///     sources.format_span(error.span.unwrap()).unwrap(),
+///     "common/buildings/farm.txt:2:12-2:13"
+/// );
+/// ```
+#[derive(Debug, Default)]
+pub struct SourceMap {
+    files: Vec<SourceFile>,
+}
+
+impl SourceMap {
+    /// Creates an empty [SourceMap].
+    pub fn new() -> SourceMap {
+        SourceMap::default()
+    }
+
+    /// Registers a source file, returning its [SourceId].
+    pub fn add(&mut self, name: impl Into<String>, text: impl Into<String>) -> SourceId {
+        self.files.push(SourceFile::new(name, text));
+        SourceId(self.files.len() as u32)
+    }
+
+    /// Registers a source file from raw bytes in the given [Encoding], returning its [SourceId].
+    pub fn add_bytes(
+        &mut self,
+        name: impl Into<String>,
+        bytes: &[u8],
+        encoding: Encoding,
+    ) -> Result<SourceId, Error> {
+        let text = Tokenizer::from_bytes(bytes, encoding)?.text().to_owned();
+        Ok(self.add(name, text))
+    }
+
+    /// Returns the source file with the given [SourceId].
+    pub fn get(&self, id: SourceId) -> Option<&SourceFile> {
+        match id.0 {
+            0 => None,
+            i => self.files.get(i as usize - 1),
+        }
+    }
+
+    /// Returns the [SourceId] of the first file registered with the given name.
+    pub fn find(&self, name: &str) -> Option<SourceId> {
+        self.files
+            .iter()
+            .position(|f| f.name == name)
+            .map(|i| SourceId(i as u32 + 1))
+    }
+
+    /// Returns an iterator over every registered file and its [SourceId].
+    pub fn files(&self) -> impl Iterator<Item = (SourceId, &SourceFile)> {
+        self.files
+            .iter()
+            .enumerate()
+            .map(|(i, f)| (SourceId(i as u32 + 1), f))
+    }
+
+    /// Returns the number of registered files.
+    pub fn len(&self) -> usize {
+        self.files.len()
+    }
+
+    /// Returns true if no files have been registered.
+    pub fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    /// Creates a [Tokenizer] for the file with the given [SourceId].
+    pub fn tokenizer(&self, id: SourceId) -> Option<Tokenizer<'_>> {
+        let file = self.get(id)?;
+        Some(
+            Tokenizer::new(&file.text)
+                .with_source(id)
+                .with_line_index(Cow::Borrowed(&file.lines)),
+        )
+    }
+
+    /// Creates a [Reader] for the file with the given [SourceId].
+    pub fn reader(&self, id: SourceId) -> Option<Reader<'_>> {
+        self.tokenizer(id).map(Reader::from_tokenizer)
+    }
+
+    /// Turns a position in a file into a (line, col) pair, both starting at 1.
+    pub fn line_col(&self, id: SourceId, position: usize) -> Option<(usize, usize)> {
+        self.get(id).map(|f| f.line_col(position))
+    }
+
+    /// Formats a [Span] as `file:line:col-line:col`, or `file:line:col` if the span is empty.
+    ///
+    /// Returns `None` if the span's file isn't registered with this map.
+    pub fn format_span(&self, span: Span) -> Option<String> {
+        let file = self.get(span.source)?;
+        let (line, col) = file.line_col(span.start);
+
+        Some(match span.is_empty() {
+            true => format!("{}:{}:{}", file.name, line, col),
+            false => {
+                let (end_line, end_col) = file.line_col(span.end);
+                format!("{}:{}:{}-{}:{}", file.name, line, col, end_line, end_col)
+            }
+        })
+    }
+
+    /// Formats an [Error] as a single line starting with its location, such as
+    /// `events/my_events.txt:12:5-12:9: unexpected token type ...`.
+    pub fn format_error(&self, error: &Error) -> String {
+        match error.span.and_then(|span| self.format_span(span)) {
+            Some(location) => format!("{}: {}", location, error.message),
+            None => error.message.clone(),
+        }
+    }
+}
@@ -0,0 +1,612 @@
+use serde::de::value::{SeqAccessDeserializer, SeqDeserializer};
+use serde::de::{self, DeserializeSeed, IntoDeserializer, MapAccess, SeqAccess, Visitor};
+use serde::Deserialize;
+
+use crate::de::{visit_cow_str, ColorAccessor, Items, KeyDeserializer, ITEMS_FIELD, MAX_DEPTH};
+use crate::tape::{Tape, TapeEntries, TapeEntry, TapeToken, TapeValue};
+use crate::template::TEMPLATE_STRUCT_NAME;
+use crate::types::{CollectionType, Operator, RealType, COLOR_STRUCT_NAME, COMPARISON_STRUCT_NAME};
+use crate::util::error::{Error, ErrorType, ParseCompleteResult};
+
+type Result<T> = ParseCompleteResult<T>;
+
+/// A [serde] deserializer for a single value on a [Tape].
+///
+/// This reads the same types as the [Deserializer](`crate::de::Deserializer`) for text, including
+/// [Comparison](`crate::types::Comparison`), [Color](`crate::types::Color`), and structs with an
+/// [ITEMS_FIELD]. Templates aren't supported, since a tape can't contain parameters.
+///
+/// Like the text deserializer, externally tagged enums can only be read from the name of a unit
+/// variant, such as `val = Value1`. Newtype, tuple, and struct variants are rejected with an
+/// [ErrorType::InvalidType], since Clausewitz files have no way of tagging them. Internally
+/// tagged, adjacently tagged, and untagged enums are read from their contents, and work with
+/// any kind of variant.
+///
+/// Values that are ignored, such as unknown fields, are skipped in constant time. Any value on
+/// the tape can be deserialized on its own with [from_value], without reading the rest of the file.
+///
+/// Deserializing recurses once for every nested collection, so collections nested more than
+/// [MAX_DEPTH] deep inside the value being deserialized are rejected with an
+/// [ErrorType::LimitError].
+pub struct Deserializer<'t, 'a> {
+    value: TapeValue<'t, 'a>,
+    /// The operator of the property this value belongs to, if any.
+    operator: Option<Operator>,
+    /// The number of collections this value is nested inside, since the value deserializing started at.
+    depth: usize,
+}
+
+impl<'t, 'a> Deserializer<'t, 'a> {
+    /// Creates a new [Deserializer] for the given value.
+    pub fn new(value: TapeValue<'t, 'a>) -> Self {
+        Deserializer::nested(value, 0)
+    }
+
+    fn nested(value: TapeValue<'t, 'a>, depth: usize) -> Self {
+        Deserializer {
+            value,
+            operator: None,
+            depth,
+        }
+    }
+
+    fn with_operator(value: TapeValue<'t, 'a>, operator: Operator, depth: usize) -> Self {
+        Deserializer {
+            value,
+            operator: Some(operator),
+            depth,
+        }
+    }
+
+    /// Returns the depth of the values inside this collection, checking that it's no more
+    /// than [MAX_DEPTH].
+    fn enter(&self) -> Result<usize> {
+        // the root of the tape isn't nested inside anything
+        if self.value.index() == 0 {
+            return Ok(self.depth);
+        }
+
+        let depth = self.depth + 1;
+        match depth > MAX_DEPTH {
+            true => Err(self.value.error(
+                ErrorType::LimitError,
+                format!("collections are nested more than {} deep", MAX_DEPTH),
+            )),
+            false => Ok(depth),
+        }
+    }
+
+    /// Reads a collection as a map, collecting its bare values into an [ITEMS_FIELD] as `items` says.
+    fn read_map<V>(self, visitor: V, items: Items) -> Result<V::Value>
+    where
+        V: Visitor<'a>,
+    {
+        if !matches!(
+            self.value.tape().tokens()[self.value.index()],
+            TapeToken::Open { .. }
+        ) {
+            return Err(self.value.type_error("object"));
+        }
+
+        visitor.visit_map(MapAccessor {
+            depth: self.enter()?,
+            entries: self.value.entries(),
+            items: (items != Items::Forbidden).then(Vec::new),
+            always_items: items == Items::Always,
+            pending: None,
+        })
+    }
+}
+
+/// Attempts to deserialize the input string into `T`, reading it into a [Tape] first.
+pub fn from_str<'a, T>(s: &'a str) -> Result<T>
+where
+    T: Deserialize<'a>,
+{
+    from_tape(&Tape::new(s)?)
+}
+
+/// Attempts to deserialize the root of a [Tape] into `T`.
+///
+/// Strings are borrowed from the input of the tape, so `T` can outlive it.
+pub fn from_tape<'a, T>(tape: &Tape<'a>) -> Result<T>
+where
+    T: Deserialize<'a>,
+{
+    from_value(tape.root())
+}
+
+/// Attempts to deserialize a single value on a [Tape] into `T`.
+///
+/// Unlike calling `T::deserialize` directly, this adds line context to any errors.
+///
+/// ```
+/// use clauser::tape::{de::from_value, Tape};
+///
+/// let tape = Tape::new("countries = { FRA = { ideas = { a b } } CAS = { ideas = { c } } }")?;
+/// let castile = tape.root().get("countries").unwrap().get("CAS").unwrap();
+/// let ideas: Vec<String> = from_value(castile.get("ideas").unwrap())?;
+/// assert_eq!(ideas, vec!["c"]);
+/// # Ok::<(), clauser::error::Error>(())
+/// ```
+pub fn from_value<'a, T>(value: TapeValue<'_, 'a>) -> Result<T>
+where
+    T: Deserialize<'a>,
+{
+    with_context(value, T::deserialize(Deserializer::new(value)))
+}
+
+/// Adds the position of `value` to an error that doesn't have one, such as a missing field.
+fn with_context<T>(value: TapeValue, result: Result<T>) -> Result<T> {
+    result.map_err(|e| match e.has_context() {
+        true => e,
+        false => e.with_context(value.tape(), value.span().start),
+    })
+}
+
+impl<'t, 'a> de::Deserializer<'a> for Deserializer<'t, 'a> {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'a>,
+    {
+        let Some(real_type) = self.value.real_type() else {
+            return visitor.visit_none();
+        };
+
+        match real_type {
+            RealType::Boolean => self.deserialize_bool(visitor),
+            RealType::Number => match self.value.raw_text().contains('.') {
+                true => visitor.visit_f64(self.value.as_number()?),
+                false => visitor.visit_i64(self.value.as_number()?),
+            },
+            RealType::Date => self.deserialize_u128(visitor),
+            RealType::String | RealType::Identifier => self.deserialize_str(visitor),
+            RealType::Color => visitor.visit_map(ColorAccessor::new(self.value.as_color()?)),
+            RealType::ObjectOrArray => match self.value.collection_type() {
+                Some(CollectionType::Array) => self.deserialize_seq(visitor),
+                _ => self.deserialize_map(visitor),
+            },
+        }
+    }
+
+    fn deserialize_bool<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'a>,
+    {
+        visitor.visit_bool(self.value.as_bool()?)
+    }
+
+    fn deserialize_i8<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'a>,
+    {
+        visitor.visit_i64(self.value.as_number()?)
+    }
+
+    fn deserialize_i16<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'a>,
+    {
+        visitor.visit_i64(self.value.as_number()?)
+    }
+
+    fn deserialize_i32<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'a>,
+    {
+        visitor.visit_i64(self.value.as_number()?)
+    }
+
+    fn deserialize_i64<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'a>,
+    {
+        visitor.visit_i64(self.value.as_number()?)
+    }
+
+    fn deserialize_u8<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'a>,
+    {
+        visitor.visit_u64(self.value.as_number()?)
+    }
+
+    fn deserialize_u16<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'a>,
+    {
+        visitor.visit_u64(self.value.as_number()?)
+    }
+
+    fn deserialize_u32<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'a>,
+    {
+        visitor.visit_u64(self.value.as_number()?)
+    }
+
+    fn deserialize_u64<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'a>,
+    {
+        visitor.visit_u64(self.value.as_number()?)
+    }
+
+    fn deserialize_u128<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'a>,
+    {
+        visitor.visit_u128(self.value.as_date()?.into())
+    }
+
+    fn deserialize_f32<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'a>,
+    {
+        visitor.visit_f64(self.value.as_number()?)
+    }
+
+    fn deserialize_f64<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'a>,
+    {
+        visitor.visit_f64(self.value.as_number()?)
+    }
+
+    fn deserialize_char<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'a>,
+    {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_str<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'a>,
+    {
+        // a missing value is an empty string
+        match self.value.is_empty() {
+            true => visitor.visit_borrowed_str(""),
+            false => visit_cow_str(visitor, self.value.as_str()?),
+        }
+    }
+
+    fn deserialize_string<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'a>,
+    {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_bytes<V>(self, _visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'a>,
+    {
+        Err(self
+            .value
+            .error(ErrorType::Unsupported, "bytes can't be deserialized"))
+    }
+
+    fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'a>,
+    {
+        self.deserialize_bytes(visitor)
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'a>,
+    {
+        match self.value.is_empty() {
+            true => visitor.visit_none(),
+            false => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'a>,
+    {
+        match self.value.is_empty() {
+            true => visitor.visit_unit(),
+            false => Err(self
+                .value
+                .error(ErrorType::InvalidType, "expected unit, found value")),
+        }
+    }
+
+    fn deserialize_unit_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'a>,
+    {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_newtype_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'a>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'a>,
+    {
+        if self.value.real_type() != Some(RealType::ObjectOrArray) {
+            return Err(self.value.type_error("array"));
+        }
+
+        visitor.visit_seq(ArrayAccessor {
+            entries: self.value.entries(),
+            depth: self.enter()?,
+        })
+    }
+
+    fn deserialize_tuple<V>(self, _len: usize, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'a>,
+    {
+        // colors can be read as RGB arrays, like [u8; 3]
+        if self.value.real_type() == Some(RealType::Color) {
+            let rgb = self.value.as_color()?.to_rgb();
+            return visitor.visit_seq(SeqDeserializer::new(rgb.into_iter()));
+        }
+
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V>(
+        self,
+        name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'a>,
+    {
+        if name == COMPARISON_STRUCT_NAME {
+            return visitor.visit_seq(ComparisonAccessor {
+                operator: Some(self.operator.unwrap_or(Operator::Equals)),
+                value: Some(self.value),
+                depth: self.depth,
+            });
+        }
+
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_map<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'a>,
+    {
+        self.read_map(visitor, Items::IfAny)
+    }
+
+    fn deserialize_struct<V>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'a>,
+    {
+        if name == TEMPLATE_STRUCT_NAME {
+            return Err(self
+                .value
+                .error(ErrorType::Unsupported, "tapes can't contain templates"));
+        }
+
+        if name == COLOR_STRUCT_NAME {
+            // unprefixed colors are plain arrays of RGB components
+            return match self.value.real_type() {
+                Some(RealType::Color) => {
+                    visitor.visit_map(ColorAccessor::new(self.value.as_color()?))
+                }
+                _ => self.deserialize_seq(visitor),
+            };
+        }
+
+        self.read_map(visitor, Items::for_struct(fields))
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value>
+    where
+        V: Visitor<'a>,
+    {
+        match self.value.real_type() {
+            Some(RealType::Identifier | RealType::String) => {
+                visitor.visit_enum(self.value.as_str()?.into_deserializer())
+            }
+            _ => Err(self.value.type_error("enum variant")),
+        }
+    }
+
+    fn deserialize_identifier<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'a>,
+    {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value>
+    where
+        V: Visitor<'a>,
+    {
+        // nothing needs to be read to skip a value on a tape
+        match self.value.is_empty() {
+            true => visitor.visit_none(),
+            false => visitor.visit_unit(),
+        }
+    }
+}
+
+struct ArrayAccessor<'t, 'a> {
+    entries: TapeEntries<'t, 'a>,
+    /// The depth of the entries.
+    depth: usize,
+}
+
+impl<'t, 'a> SeqAccess<'a> for ArrayAccessor<'t, 'a> {
+    type Error = Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
+    where
+        T: DeserializeSeed<'a>,
+    {
+        match self.entries.next() {
+            Some(TapeEntry::Item(value)) => with_context(
+                value,
+                seed.deserialize(Deserializer::nested(value, self.depth)),
+            )
+            .map(Some),
+            Some(TapeEntry::Property(key, ..)) => Err(key.error(
+                ErrorType::UnexpectedTokenError,
+                "expected an array value, found a property",
+            )),
+            None => Ok(None),
+        }
+    }
+}
+
+struct MapAccessor<'t, 'a> {
+    entries: TapeEntries<'t, 'a>,
+    /// The depth of the entries.
+    depth: usize,
+    /// The bare values of the object, if they're collected into an [ITEMS_FIELD].
+    items: Option<Vec<TapeValue<'t, 'a>>>,
+    /// Whether to return an [ITEMS_FIELD] key even if the object has no bare values.
+    always_items: bool,
+    /// The value to read for the key that was just returned.
+    pending: Option<Pending<'t, 'a>>,
+}
+
+/// The value of a key returned by [MapAccessor].
+enum Pending<'t, 'a> {
+    Property(TapeValue<'t, 'a>, Operator),
+    Items(Vec<TapeValue<'t, 'a>>),
+}
+
+impl<'t, 'a> MapAccess<'a> for MapAccessor<'t, 'a> {
+    type Error = Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>>
+    where
+        K: DeserializeSeed<'a>,
+    {
+        loop {
+            match self.entries.next() {
+                Some(TapeEntry::Property(key, operator, value)) => {
+                    let result = seed.deserialize(KeyDeserializer(key.as_key()?));
+                    self.pending = Some(Pending::Property(value, operator));
+                    return with_context(key, result).map(Some);
+                }
+                Some(TapeEntry::Item(value)) => match &mut self.items {
+                    Some(items) => items.push(value),
+                    None => {
+                        return Err(value.error(
+                            ErrorType::UnexpectedTokenError,
+                            "expected a property, found a bare value",
+                        ))
+                    }
+                },
+                None => {
+                    // the items are read once every property has been
+                    return match self.items.take() {
+                        Some(items) if !items.is_empty() || self.always_items => {
+                            self.pending = Some(Pending::Items(items));
+                            seed.deserialize(ITEMS_FIELD.into_deserializer()).map(Some)
+                        }
+                        _ => Ok(None),
+                    };
+                }
+            }
+        }
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value>
+    where
+        V: DeserializeSeed<'a>,
+    {
+        match self.pending.take() {
+            Some(Pending::Property(value, operator)) => with_context(
+                value,
+                seed.deserialize(Deserializer::with_operator(value, operator, self.depth)),
+            ),
+            Some(Pending::Items(items)) => {
+                seed.deserialize(SeqAccessDeserializer::new(ItemsAccessor {
+                    items: items.into_iter(),
+                    depth: self.depth,
+                }))
+            }
+            None => Err(Error::new_unanchored(
+                ErrorType::InvalidState,
+                "value read before its key",
+            )),
+        }
+    }
+}
+
+/// Reads the bare values of an object collected by [MapAccessor] as a sequence.
+struct ItemsAccessor<'t, 'a> {
+    items: std::vec::IntoIter<TapeValue<'t, 'a>>,
+    /// The depth of the items.
+    depth: usize,
+}
+
+impl<'t, 'a> SeqAccess<'a> for ItemsAccessor<'t, 'a> {
+    type Error = Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
+    where
+        T: DeserializeSeed<'a>,
+    {
+        match self.items.next() {
+            Some(value) => with_context(
+                value,
+                seed.deserialize(Deserializer::nested(value, self.depth)),
+            )
+            .map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Provides the operator and value of a [Comparison](`crate::types::Comparison`) as a sequence.
+struct ComparisonAccessor<'t, 'a> {
+    operator: Option<Operator>,
+    value: Option<TapeValue<'t, 'a>>,
+    /// The depth of the value.
+    depth: usize,
+}
+
+impl<'t, 'a> SeqAccess<'a> for ComparisonAccessor<'t, 'a> {
+    type Error = Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>>
+    where
+        T: DeserializeSeed<'a>,
+    {
+        if let Some(operator) = self.operator.take() {
+            return seed
+                .deserialize(operator.as_str().into_deserializer())
+                .map(Some);
+        }
+
+        match self.value.take() {
+            Some(value) => seed
+                .deserialize(Deserializer::nested(value, self.depth))
+                .map(Some),
+            None => Ok(None),
+        }
+    }
+}
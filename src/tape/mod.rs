@@ -0,0 +1,833 @@
+use std::{borrow::Cow, fmt, path::Path, str::FromStr};
+
+use crate::{
+    encoding::Encoding,
+    limits::Limits,
+    source::{SourceId, Span},
+    token::{Token, TokenType},
+    tokenizer::Tokenizer,
+    types::{CollectionType, Color, ColorSpace, Date, ObjectKey, Operator, RealType},
+    util::{
+        error::{Error, ErrorContext, ErrorContextProvider, ErrorType},
+        text_helpers::unescape,
+    },
+};
+
+/// [serde] deserializer for values on a [Tape].
+#[cfg(feature = "serde")]
+#[doc(cfg(feature = "serde"))]
+pub mod de;
+
+/// A single token on a [Tape].
+///
+/// Open and close brackets hold the tape index of their matching bracket, so a whole collection
+/// can be skipped at once.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TapeToken {
+    /// An open bracket, or the start of the input.
+    Open {
+        /// The tape index of the matching [TapeToken::Close].
+        close: usize,
+        /// The byte offset of the bracket in the source text.
+        index: usize,
+    },
+    /// A close bracket, or the end of the input.
+    Close {
+        /// The tape index of the matching [TapeToken::Open].
+        open: usize,
+        /// The byte offset of the bracket in the source text.
+        index: usize,
+    },
+    /// Any other token, such as an identifier, a number, or an operator.
+    Token {
+        token_type: TokenType,
+        /// The byte offset of the token in the source text.
+        index: usize,
+        /// The length of the token in bytes.
+        length: usize,
+    },
+    /// The missing value of a property at the end of a collection, like the value of `b`
+    /// in `{ a = 1 b = }`.
+    Empty {
+        /// The byte offset in the source text where the value would be.
+        index: usize,
+    },
+}
+
+impl TapeToken {
+    /// Returns the byte offset of this token in the source text.
+    pub fn index(&self) -> usize {
+        match self {
+            TapeToken::Open { index, .. }
+            | TapeToken::Close { index, .. }
+            | TapeToken::Token { index, .. }
+            | TapeToken::Empty { index } => *index,
+        }
+    }
+}
+
+/// A whole Clausewitz file, tokenized in a single pass and stored as a flat list of [TapeToken]s.
+///
+/// Every open bracket on the tape holds the index of its matching close bracket, so skipping over
+/// a value or jumping to any part of the file takes constant time, however large it is. The input
+/// is wrapped in an extra pair of brackets, so the [TapeValue] at index 0 is the root object.
+///
+/// Values are read with [TapeValue], which borrows strings from the input where possible,
+/// or deserialized with the [Deserializer](`de::Deserializer`) for tapes.
+///
+/// Tapes are meant for data files and saves. Scripted variables, inline math, and conditional
+/// parameter blocks aren't supported, and building a tape from input containing them returns
+/// an [ErrorType::Unsupported] error. Comments are discarded, and the first syntax error ends
+/// the build, like [ParseMode::Strict](`crate::reader::ParseMode::Strict`).
+///
+/// A property can be missing its value at the end of a collection or the input, such as `b`
+/// in `{ a = 1 b = }`. Its value is a [TapeToken::Empty].
+///
+/// ```
+/// use clauser::tape::Tape;
+///
+/// let tape = Tape::new("name = \"Castile\" ideas = { a b c } army = { size = 10 }")?;
+/// let root = tape.root();
+/// assert_eq!(root.get("name").unwrap().as_str()?, "Castile");
+/// assert_eq!(root.get("ideas").unwrap().entries().count(), 3);
+/// assert_eq!(root.get("army").unwrap().get("size").unwrap().as_number::<i32>()?, 10);
+/// # Ok::<(), clauser::error::Error>(())
+/// ```
+pub struct Tape<'a> {
+    tokenizer: Tokenizer<'a>,
+    tokens: Vec<TapeToken>,
+    /// The deepest any collection is nested, not counting the root.
+    depth: usize,
+}
+
+impl<'a> Tape<'a> {
+    /// Builds a [Tape] from the input text.
+    pub fn new(text: &'a str) -> Result<Tape<'a>, Error> {
+        Tape::from_tokenizer(Tokenizer::new(text))
+    }
+
+    /// Builds a [Tape] from raw bytes in the given [Encoding].
+    ///
+    /// If the input has to be transcoded, the tape owns its text, and strings can't be borrowed
+    /// from the input.
+    pub fn from_bytes(bytes: &'a [u8], encoding: Encoding) -> Result<Tape<'a>, Error> {
+        Tape::from_tokenizer(Tokenizer::from_bytes(bytes, encoding)?)
+    }
+
+    /// Builds a [Tape] from every token of the given [Tokenizer], which can have its own
+    /// [Dialect](`crate::dialect::Dialect`) or [SourceId].
+    ///
+    /// Since the tape keeps the whole text, this returns an [ErrorType::Unsupported] error for
+    /// a tokenizer created with [Tokenizer::from_read].
+    pub fn from_tokenizer(tokenizer: Tokenizer<'a>) -> Result<Tape<'a>, Error> {
+        Tape::from_tokenizer_with_limits(tokenizer, Limits::NONE)
+    }
+
+    /// Builds a [Tape] from every token of the given [Tokenizer], rejecting input that goes over
+    /// the given [Limits] with an [ErrorType::LimitError].
+    ///
+    /// Building a tape doesn't recurse, so a tape can be nested as deep as the limits allow.
+    /// Deserializing from one does, so it never reads collections nested more than
+    /// [MAX_DEPTH](`crate::de::MAX_DEPTH`) deep.
+    pub fn from_tokenizer_with_limits(
+        mut tokenizer: Tokenizer<'a>,
+        limits: Limits,
+    ) -> Result<Tape<'a>, Error> {
+        if tokenizer.is_streaming() {
+            return Err(Error::new_unanchored(
+                ErrorType::Unsupported,
+                "tapes can't be built from a streaming tokenizer",
+            ));
+        }
+
+        let mut builder = Builder::new(limits);
+        while let Some(token) = tokenizer.next()? {
+            builder.push(&tokenizer, token)?;
+        }
+
+        let end = tokenizer.text().len();
+        let (tokens, depth) = builder.finish(&tokenizer, end)?;
+
+        Ok(Tape {
+            tokenizer,
+            tokens,
+            depth,
+        })
+    }
+
+    /// Returns the root object of the input.
+    pub fn root(&self) -> TapeValue<'_, 'a> {
+        TapeValue {
+            tape: self,
+            index: 0,
+        }
+    }
+
+    /// Returns the value starting at the tape index `index`, or [None] if no value starts there,
+    /// such as at a close bracket or an operator.
+    pub fn value_at(&self, index: usize) -> Option<TapeValue<'_, 'a>> {
+        match self.tokens.get(index)? {
+            TapeToken::Close { .. } => None,
+            TapeToken::Token { token_type, .. }
+                if Operator::from_token_type(token_type).is_some() =>
+            {
+                None
+            }
+            _ => Some(TapeValue { tape: self, index }),
+        }
+    }
+
+    /// Returns every token on the tape, including the brackets around the root.
+    pub fn tokens(&self) -> &[TapeToken] {
+        &self.tokens
+    }
+
+    /// Returns the deepest that collections in the input are nested, not counting the root.
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Returns the source text of the tape.
+    pub fn text(&self) -> &str {
+        self.tokenizer.text()
+    }
+
+    /// Creates a new [Error] covering the given range of the source text.
+    fn error(&self, error_type: ErrorType, span: Span, message: impl ToString) -> Error {
+        self.tokenizer
+            .parse_error_pos(error_type, span.start, message)
+            .with_end(span.end)
+    }
+}
+
+impl<'a> ErrorContextProvider for Tape<'a> {
+    fn get_line_context(&self, position: usize, max_lines: usize) -> Option<ErrorContext> {
+        self.tokenizer.get_line_context(position, max_lines)
+    }
+
+    fn source_id(&self) -> SourceId {
+        self.tokenizer.source()
+    }
+
+    fn path(&self) -> Option<&Path> {
+        self.tokenizer.path()
+    }
+}
+
+/// Returns whether the token at `index..index + length` is a color prefix in the tokenizer's dialect.
+fn is_color_prefix(tokenizer: &Tokenizer, index: usize, length: usize) -> bool {
+    let prefix = tokenizer.str_for_range((index, index + length));
+    ColorSpace::from_prefix(prefix).is_some()
+        && tokenizer.dialect().color_prefixes.contains(&prefix)
+}
+
+/// Checks the syntax of the input as it's pushed onto a tape, one token at a time.
+struct Builder {
+    tokens: Vec<TapeToken>,
+    limits: Limits,
+    /// The tape index of each collection that's still open, starting with the root, and how many
+    /// entries it has.
+    open: Vec<(usize, usize)>,
+    /// Whether the last token was an operator, so a value has to come next.
+    after_operator: bool,
+    /// Whether the last token started an entry, so it could be the key of a property.
+    after_key: bool,
+    read_tokens: usize,
+    depth: usize,
+}
+
+impl Builder {
+    fn new(limits: Limits) -> Builder {
+        Builder {
+            tokens: vec![TapeToken::Open { close: 0, index: 0 }],
+            limits,
+            open: vec![(0, 0)],
+            after_operator: false,
+            after_key: false,
+            read_tokens: 0,
+            depth: 0,
+        }
+    }
+
+    fn push(&mut self, tokenizer: &Tokenizer, token: Token) -> Result<(), Error> {
+        self.count_token(tokenizer, &token)?;
+
+        let operator = Operator::from_token_type(&token.token_type).is_some();
+        match token.token_type {
+            TokenType::Comment => return Ok(()),
+            TokenType::Variable
+            | TokenType::InlineMath
+            | TokenType::ParameterBlockStart
+            | TokenType::ParameterBlockEnd => {
+                return Err(tokenizer.parse_error_token(
+                    &token,
+                    ErrorType::Unsupported,
+                    format!("{:?} tokens can't be read into a tape", token.token_type),
+                ))
+            }
+            TokenType::CloseBracket => return self.close(tokenizer, &token),
+            TokenType::OpenBracket => {
+                // the block of a color belongs to the entry its prefix started
+                let is_color = matches!(
+                    self.tokens.last(),
+                    Some(TapeToken::Token { token_type: TokenType::Identifier, index, length })
+                        if is_color_prefix(tokenizer, *index, *length)
+                );
+                if !self.after_operator && !is_color {
+                    self.count_entry(tokenizer, &token)?;
+                }
+
+                self.check_depth(tokenizer, &token)?;
+                self.open.push((self.tokens.len(), 0));
+                self.tokens.push(TapeToken::Open {
+                    close: 0,
+                    index: token.index,
+                });
+                self.after_operator = false;
+                self.after_key = false;
+                return Ok(());
+            }
+            _ if operator => {
+                if !self.after_key {
+                    return Err(tokenizer.parse_error_token(
+                        &token,
+                        ErrorType::UnexpectedTokenError,
+                        format!("expected a key before {:?}", token.token_type),
+                    ));
+                }
+
+                self.after_operator = true;
+                self.after_key = false;
+            }
+            _ => {
+                self.after_key = !self.after_operator;
+                if !self.after_operator {
+                    self.count_entry(tokenizer, &token)?;
+                }
+                self.after_operator = false;
+            }
+        }
+
+        self.tokens.push(TapeToken::Token {
+            token_type: token.token_type,
+            index: token.index,
+            length: token.length,
+        });
+
+        Ok(())
+    }
+
+    fn close(&mut self, tokenizer: &Tokenizer, token: &Token) -> Result<(), Error> {
+        if self.open.len() == 1 {
+            return Err(tokenizer.parse_error_token(
+                token,
+                ErrorType::UnexpectedTokenError,
+                "found a close bracket without a collection to close",
+            ));
+        }
+
+        self.end_property(token.index);
+        let (open, _) = self.open.pop().unwrap();
+        self.link(open);
+        self.tokens.push(TapeToken::Close {
+            open,
+            index: token.index,
+        });
+
+        Ok(())
+    }
+
+    /// Ends the tape at the byte offset `end`, returning its tokens and depth.
+    fn finish(
+        mut self,
+        tokenizer: &Tokenizer,
+        end: usize,
+    ) -> Result<(Vec<TapeToken>, usize), Error> {
+        if let Some((open, _)) = self.open.get(1) {
+            let index = self.tokens[*open].index();
+            return Err(tokenizer
+                .parse_error_pos(
+                    ErrorType::UnexpectedTokenError,
+                    index,
+                    "collection isn't closed before the end of the input",
+                )
+                .with_end(index + 1));
+        }
+
+        self.end_property(end);
+        self.link(0);
+        self.tokens.push(TapeToken::Close {
+            open: 0,
+            index: end,
+        });
+
+        Ok((self.tokens, self.depth))
+    }
+
+    /// Gives the property being read an empty value if it's missing one.
+    fn end_property(&mut self, index: usize) {
+        if self.after_operator {
+            self.tokens.push(TapeToken::Empty { index });
+        }
+
+        self.after_operator = false;
+        self.after_key = false;
+    }
+
+    /// Points the open bracket at `open` to the close bracket about to be pushed.
+    fn link(&mut self, open: usize) {
+        let next = self.tokens.len();
+        if let TapeToken::Open { close, .. } = &mut self.tokens[open] {
+            *close = next;
+        }
+    }
+
+    fn count_token(&mut self, tokenizer: &Tokenizer, token: &Token) -> Result<(), Error> {
+        self.read_tokens += 1;
+        if self.read_tokens > self.limits.max_tokens {
+            return Err(tokenizer.parse_error_token(
+                token,
+                ErrorType::LimitError,
+                format!("input has more than {} tokens", self.limits.max_tokens),
+            ));
+        }
+
+        let is_string = matches!(
+            token.token_type,
+            TokenType::String | TokenType::Identifier | TokenType::InlineMath
+        );
+        if is_string && token.length > self.limits.max_string_length {
+            return Err(tokenizer.parse_error_token(
+                token,
+                ErrorType::LimitError,
+                format!(
+                    "{:?} is longer than {} bytes",
+                    token.token_type, self.limits.max_string_length
+                ),
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn count_entry(&mut self, tokenizer: &Tokenizer, token: &Token) -> Result<(), Error> {
+        let max = self.limits.max_collection_size;
+        let (_, entries) = self.open.last_mut().expect("the root is never closed");
+        *entries += 1;
+
+        match *entries > max {
+            true => Err(tokenizer.parse_error_pos(
+                ErrorType::LimitError,
+                token.index,
+                format!("collection has more than {} entries", max),
+            )),
+            false => Ok(()),
+        }
+    }
+
+    fn check_depth(&mut self, tokenizer: &Tokenizer, token: &Token) -> Result<(), Error> {
+        // the root is always open, but doesn't count
+        let depth = self.open.len();
+        if depth > self.limits.max_depth {
+            return Err(tokenizer.parse_error_token(
+                token,
+                ErrorType::LimitError,
+                format!(
+                    "collections are nested more than {} deep",
+                    self.limits.max_depth
+                ),
+            ));
+        }
+
+        self.depth = self.depth.max(depth);
+        Ok(())
+    }
+}
+
+/// A single value on a [Tape], such as a number, a color, or a whole collection.
+///
+/// [TapeValue] is a cheap, copyable view into the tape. Reading it doesn't allocate unless
+/// a string has escape sequences in it, or the tape owns its text.
+#[derive(Clone, Copy)]
+pub struct TapeValue<'t, 'a> {
+    tape: &'t Tape<'a>,
+    index: usize,
+}
+
+impl<'t, 'a> TapeValue<'t, 'a> {
+    /// Returns the [Tape] this value is on.
+    pub fn tape(&self) -> &'t Tape<'a> {
+        self.tape
+    }
+
+    /// Returns the tape index of the first token of this value.
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    /// Returns the tape index of the last token of this value, such as the close bracket of
+    /// a collection or color.
+    pub fn end_index(&self) -> usize {
+        let open = match self.tape.tokens[self.index] {
+            TapeToken::Open { .. } => self.index,
+            _ => match self.color_block() {
+                Some(open) => open,
+                None => return self.index,
+            },
+        };
+
+        match self.tape.tokens[open] {
+            TapeToken::Open { close, .. } => close,
+            _ => unreachable!("color blocks start with an open bracket"),
+        }
+    }
+
+    /// Returns the type of this value, or [None] if it's a missing value.
+    pub fn real_type(&self) -> Option<RealType> {
+        match &self.tape.tokens[self.index] {
+            TapeToken::Open { .. } => Some(RealType::ObjectOrArray),
+            TapeToken::Token { .. } if self.color_block().is_some() => Some(RealType::Color),
+            TapeToken::Token { token_type, .. } => RealType::from_token_type(token_type),
+            TapeToken::Close { .. } | TapeToken::Empty { .. } => None,
+        }
+    }
+
+    /// Returns whether this is the missing value of a property, like the value of `b` in `{ a = 1 b = }`.
+    pub fn is_empty(&self) -> bool {
+        matches!(self.tape.tokens[self.index], TapeToken::Empty { .. })
+    }
+
+    /// Returns the [Span] of source text covered by this value.
+    ///
+    /// The span of the root covers the whole input.
+    pub fn span(&self) -> Span {
+        let start = self.tape.tokens[self.index].index();
+        let end = match &self.tape.tokens[self.end_index()] {
+            TapeToken::Close { open: 0, index } => *index,
+            TapeToken::Open { index, .. } | TapeToken::Close { index, .. } => index + 1,
+            TapeToken::Token { index, length, .. } => index + length,
+            TapeToken::Empty { index } => *index,
+        };
+
+        Span::new(self.tape.tokenizer.source(), start, end)
+    }
+
+    /// Returns the source text of this value, exactly as it's written in the input.
+    pub fn raw_text(&self) -> &'t str {
+        let span = self.span();
+        self.tape.tokenizer.str_for_range((span.start, span.end))
+    }
+
+    /// Returns the contents of this value as a string if it's a string or an identifier.
+    ///
+    /// Escape sequences in strings are replaced, in which case the returned string is owned.
+    pub fn as_str(&self) -> Result<Cow<'a, str>, Error> {
+        match self.token_type() {
+            Some(TokenType::String) => Ok(unescape(self.cow())),
+            Some(TokenType::Identifier) => Ok(self.cow()),
+            _ => Err(self.type_error("string or identifier")),
+        }
+    }
+
+    /// Returns this value as a boolean if it's `yes` or `no`.
+    pub fn as_bool(&self) -> Result<bool, Error> {
+        match self.token_type() {
+            Some(TokenType::Boolean) => Ok(self.raw_text().starts_with('y')),
+            _ => Err(self.type_error("boolean")),
+        }
+    }
+
+    /// Parses this value as a number.
+    pub fn as_number<T: FromStr>(&self) -> Result<T, Error> {
+        if self.token_type() != Some(&TokenType::Number) {
+            return Err(self.type_error("number"));
+        }
+
+        let str = self.raw_text();
+        str.parse::<T>().map_err(|_| {
+            self.error(
+                ErrorType::InvalidNumberError,
+                format!("failed to parse number from token '{}'", str),
+            )
+        })
+    }
+
+    /// Returns this value as a [Date] if it's a date.
+    pub fn as_date(&self) -> Result<Date, Error> {
+        match self.tape.tokens[self.index] {
+            TapeToken::Token {
+                token_type: TokenType::Date,
+                index,
+                length,
+            } => self
+                .tape
+                .tokenizer
+                .date_for_token(&Token::new(TokenType::Date, index, length)),
+            _ => Err(self.type_error("date")),
+        }
+    }
+
+    /// Returns this value as a [Color] if it's a color block, such as `rgb { 255 128 0 }`.
+    pub fn as_color(&self) -> Result<Color, Error> {
+        let Some(open) = self.color_block() else {
+            return Err(self.type_error("color"));
+        };
+
+        let prefix = self.tape.tokenizer.str_for_range(self.token_range());
+        let space = ColorSpace::from_prefix(prefix).expect("color blocks have a color prefix");
+
+        let mut values = Vec::with_capacity(4);
+        for entry in (TapeValue {
+            index: open,
+            ..*self
+        })
+        .entries()
+        {
+            match entry {
+                TapeEntry::Item(value) => values.push(value),
+                TapeEntry::Property(key, ..) => return Err(key.type_error("color component")),
+            }
+        }
+
+        if space == ColorSpace::Hex {
+            return match values[..] {
+                [value] if value.is_hex_color() => {
+                    Color::from_hex(value.raw_text()).ok_or_else(|| {
+                        value.error(
+                            ErrorType::InvalidValue,
+                            format!("invalid hex color '{}'", value.raw_text()),
+                        )
+                    })
+                }
+                [value, ..] => Err(value.type_error("hex color")),
+                [] => Err(self.error(ErrorType::InvalidLength, "expected a hex color, found none")),
+            };
+        }
+
+        let values = values
+            .iter()
+            .map(|value| value.as_number::<f64>())
+            .collect::<Result<Vec<_>, _>>()?;
+
+        match values[..] {
+            [a, b, c] => Ok(Color::new(space, [a, b, c])),
+            [a, b, c, alpha] => Ok(Color::new(space, [a, b, c]).with_alpha(alpha)),
+            _ => Err(self.error(
+                ErrorType::InvalidLength,
+                format!("expected 3 or 4 color components, found {}", values.len()),
+            )),
+        }
+    }
+
+    /// Returns this value as the key of a property.
+    pub fn as_key(&self) -> Result<ObjectKey<'a>, Error> {
+        match self.token_type() {
+            Some(TokenType::Identifier) => Ok(ObjectKey::Identifier(self.cow())),
+            Some(TokenType::Date) => Ok(ObjectKey::Date(self.as_date()?)),
+            Some(TokenType::Number) => {
+                let str = self.raw_text();
+                str.parse::<i64>().map(ObjectKey::Integer).map_err(|_| {
+                    self.error(
+                        ErrorType::InvalidNumberError,
+                        format!("expected an integer key, found '{}'", str),
+                    )
+                })
+            }
+            Some(TokenType::String) => Ok(ObjectKey::String(unescape(self.cow()))),
+            Some(TokenType::Boolean) => Ok(ObjectKey::Boolean(self.raw_text().starts_with('y'))),
+            _ => Err(self.type_error("key")),
+        }
+    }
+
+    /// Returns whether this collection is an object, an array, or a mix of both, or [None]
+    /// if it isn't a collection.
+    ///
+    /// This looks at every entry of the collection, skipping over their values. Empty collections
+    /// (`{}`) are arrays, except for the root, which is an object unless it has bare values.
+    pub fn collection_type(&self) -> Option<CollectionType> {
+        if !matches!(self.tape.tokens[self.index], TapeToken::Open { .. }) {
+            return None;
+        }
+
+        let (mut properties, mut items) = (false, false);
+        for entry in self.entries() {
+            match entry {
+                TapeEntry::Property(..) => properties = true,
+                TapeEntry::Item(..) => items = true,
+            }
+        }
+
+        Some(match (properties || self.index == 0, items) {
+            (true, true) => CollectionType::Mixed,
+            (true, false) => CollectionType::Object,
+            (false, _) => CollectionType::Array,
+        })
+    }
+
+    /// Returns an iterator over the entries of this collection, or an empty iterator if it
+    /// isn't a collection.
+    ///
+    /// Moving to the next entry skips over the value of the current one in constant time.
+    pub fn entries(&self) -> TapeEntries<'t, 'a> {
+        let end = match self.tape.tokens[self.index] {
+            TapeToken::Open { close, .. } => close,
+            _ => self.index + 1,
+        };
+
+        TapeEntries {
+            tape: self.tape,
+            next: self.index + 1,
+            end,
+        }
+    }
+
+    /// Returns the value of the first property of this collection with the given key, if any.
+    ///
+    /// Keys are compared with their unescaped text, so `get("1444.11.11")` finds a date key.
+    pub fn get(&self, key: &str) -> Option<TapeValue<'t, 'a>> {
+        self.entries().find_map(|entry| match entry {
+            TapeEntry::Property(k, _, value) if k.is_key(key) => Some(value),
+            _ => None,
+        })
+    }
+
+    /// Returns whether this value is a key with the given text.
+    fn is_key(&self, key: &str) -> bool {
+        match self.token_type() {
+            Some(TokenType::String) => unescape(Cow::Borrowed(self.raw_text())) == key,
+            Some(_) => self.raw_text() == key,
+            None => false,
+        }
+    }
+
+    /// Returns the type of the token this value is made of, if it's a single token.
+    fn token_type(&self) -> Option<&'t TokenType> {
+        match &self.tape.tokens[self.index] {
+            TapeToken::Token { token_type, .. } if self.color_block().is_none() => Some(token_type),
+            _ => None,
+        }
+    }
+
+    fn is_hex_color(&self) -> bool {
+        matches!(
+            self.token_type(),
+            Some(TokenType::Identifier | TokenType::Number)
+        )
+    }
+
+    /// Returns the range of source text covered by the first token of this value.
+    fn token_range(&self) -> (usize, usize) {
+        match self.tape.tokens[self.index] {
+            TapeToken::Token { index, length, .. } => (index, index + length),
+            ref token => (token.index(), token.index()),
+        }
+    }
+
+    /// Returns the contents of the first token of this value, borrowed from the source text
+    /// if the tape doesn't own it.
+    fn cow(&self) -> Cow<'a, str> {
+        self.tape.tokenizer.cow_for_range(self.token_range())
+    }
+
+    /// Returns the tape index of the block of a color value, like the `{` in `rgb { 1 2 3 }`.
+    fn color_block(&self) -> Option<usize> {
+        match (
+            &self.tape.tokens[self.index],
+            self.tape.tokens.get(self.index + 1),
+        ) {
+            (
+                TapeToken::Token {
+                    token_type: TokenType::Identifier,
+                    index,
+                    length,
+                },
+                Some(TapeToken::Open { .. }),
+            ) if is_color_prefix(&self.tape.tokenizer, *index, *length) => Some(self.index + 1),
+            _ => None,
+        }
+    }
+
+    /// Creates a new [Error] covering this value.
+    pub(crate) fn error(&self, error_type: ErrorType, message: impl ToString) -> Error {
+        self.tape.error(error_type, self.span(), message)
+    }
+
+    /// Creates a new [Error] for a value that isn't of the `expected` type.
+    pub(crate) fn type_error(&self, expected: &str) -> Error {
+        let found = match self.real_type() {
+            Some(real_type) => format!("{:?}", real_type),
+            None => String::from("empty value"),
+        };
+
+        self.error(
+            ErrorType::UnexpectedTokenError,
+            format!("expected {}, found {}", expected, found),
+        )
+    }
+}
+
+impl<'t, 'a> fmt::Debug for TapeValue<'t, 'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TapeValue")
+            .field("index", &self.index)
+            .field("text", &self.raw_text())
+            .finish()
+    }
+}
+
+/// An entry of a collection on a [Tape].
+#[derive(Debug, Clone, Copy)]
+pub enum TapeEntry<'t, 'a> {
+    /// A bare value, like the items of an array.
+    Item(TapeValue<'t, 'a>),
+    /// A property with a key, an operator, and a value.
+    Property(TapeValue<'t, 'a>, Operator, TapeValue<'t, 'a>),
+}
+
+/// An iterator over the entries of a collection on a [Tape], returned by [TapeValue::entries].
+#[derive(Clone)]
+pub struct TapeEntries<'t, 'a> {
+    tape: &'t Tape<'a>,
+    /// The tape index of the next entry.
+    next: usize,
+    /// The tape index of the close bracket of the collection.
+    end: usize,
+}
+
+impl<'t, 'a> Iterator for TapeEntries<'t, 'a> {
+    type Item = TapeEntry<'t, 'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.next >= self.end {
+            return None;
+        }
+
+        let tape = self.tape;
+        let first = TapeValue {
+            tape,
+            index: self.next,
+        };
+
+        // the tape was checked as it was built, so every operator has a key and a value
+        let operator = match (&tape.tokens[self.next], tape.tokens.get(self.next + 1)) {
+            (TapeToken::Token { .. }, Some(TapeToken::Token { token_type, .. })) => {
+                Operator::from_token_type(token_type)
+            }
+            _ => None,
+        };
+
+        let (entry, value) = match operator {
+            Some(operator) => {
+                let value = TapeValue {
+                    tape,
+                    index: self.next + 2,
+                };
+                (TapeEntry::Property(first, operator, value), value)
+            }
+            None => (TapeEntry::Item(first), first),
+        };
+
+        self.next = value.end_index() + 1;
+        Some(entry)
+    }
+}
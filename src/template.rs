@@ -0,0 +1,334 @@
+use std::{borrow::Cow, fmt};
+
+#[cfg(feature = "serde")]
+use serde::{
+    de::{self, Visitor},
+    Deserialize, Deserializer,
+};
+
+use crate::{
+    util::error::{Error, ErrorType},
+    value::Value,
+};
+
+/// A parameterized definition, such as the body of a scripted effect or trigger.
+///
+/// Templates can contain parameters (`$PARAM$`, or `$PARAM|default$` to give a default value)
+/// anywhere in their text, including inside identifiers and strings. They can also contain
+/// conditional blocks, which are only included if a parameter was given (`[[PARAM] ... ]`)
+/// or wasn't given (`[[!PARAM] ... ]`).
+///
+/// Since a template isn't valid Clausewitz syntax until it's expanded, its text is kept as-is.
+/// [Template::expand] substitutes a set of arguments into the template, producing text that
+/// can be read by a [Reader](`crate::reader::Reader`), [Value], or
+/// [Deserializer](`crate::de::Deserializer`).
+///
+/// Positions in errors produced by [Template::parse] and [Template::expand] are byte offsets
+/// into the template's text.
+#[derive(Debug, PartialEq, PartialOrd, Clone)]
+pub struct Template<'a> {
+    text: Cow<'a, str>,
+    parameters: Vec<String>,
+}
+
+impl<'a> Template<'a> {
+    /// Parses the text of a template, checking that its parameters and conditional blocks are well-formed.
+    pub fn parse(text: impl Into<Cow<'a, str>>) -> Result<Template<'a>, Error> {
+        let text = text.into();
+        let mut expander = Expander::new(&text, &[]);
+        expander.expand(&mut String::new(), false, None)?;
+        let parameters = expander.parameters;
+
+        Ok(Template { text, parameters })
+    }
+
+    /// Returns the text of this template.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Consumes this template, returning its text.
+    pub fn into_text(self) -> Cow<'a, str> {
+        self.text
+    }
+
+    /// Returns the names of the parameters used by this template, in the order they first appear.
+    pub fn parameters(&self) -> &[String] {
+        &self.parameters
+    }
+
+    /// Expands this template using the given `(name, value)` arguments, returning the resulting text.
+    ///
+    /// Parameters without an argument use their default value. If a parameter has no argument and no
+    /// default, and isn't inside a conditional block that was left out, a
+    /// [ParameterError](`crate::error::ErrorType::ParameterError`) is returned.
+    pub fn expand(&self, arguments: &[(&str, &str)]) -> Result<String, Error> {
+        let mut out = String::with_capacity(self.text.len());
+        Expander::new(&self.text, arguments).expand(&mut out, true, None)?;
+        Ok(out)
+    }
+
+    /// Expands this template like [Template::expand], then parses the result into a [Value].
+    pub fn expand_value(&self, arguments: &[(&str, &str)]) -> Result<Value<'static>, Error> {
+        let text = self.expand(arguments)?;
+        let value = Value::from_str(&text)?.into_owned();
+        Ok(value)
+    }
+
+    /// Converts this template into one that owns its text.
+    pub fn into_owned(self) -> Template<'static> {
+        Template {
+            text: Cow::Owned(self.text.into_owned()),
+            parameters: self.parameters,
+        }
+    }
+}
+
+impl<'a> fmt::Display for Template<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.text)
+    }
+}
+
+/// The name that [Deserializer](`crate::de::Deserializer`) uses to recognize a [Template].
+#[cfg(feature = "serde")]
+pub(crate) const TEMPLATE_STRUCT_NAME: &str = "$clauser::Template";
+
+/// [Deserializer](`crate::de::Deserializer`) reads a [Template] from the contents of a block,
+/// such as `{ add_gold = $AMOUNT$ }`. Other deserializers see a [Template] as a string.
+#[cfg(feature = "serde")]
+impl<'de: 'a, 'a> Deserialize<'de> for Template<'a> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct TemplateVisitor;
+
+        impl<'de> Visitor<'de> for TemplateVisitor {
+            type Value = Template<'de>;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a template")
+            }
+
+            fn visit_borrowed_str<E: de::Error>(self, v: &'de str) -> Result<Template<'de>, E> {
+                Template::parse(v).map_err(E::custom)
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<Template<'de>, E> {
+                Template::parse(v.to_owned()).map_err(E::custom)
+            }
+
+            fn visit_string<E: de::Error>(self, v: String) -> Result<Template<'de>, E> {
+                Template::parse(v).map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_struct(TEMPLATE_STRUCT_NAME, &[], TemplateVisitor)
+    }
+}
+
+/// Scans the text of a template, optionally writing out its expansion.
+struct Expander<'t> {
+    text: &'t str,
+    position: usize,
+    arguments: &'t [(&'t str, &'t str)],
+    /// Every parameter name seen so far.
+    parameters: Vec<String>,
+}
+
+impl<'t> Expander<'t> {
+    fn new(text: &'t str, arguments: &'t [(&'t str, &'t str)]) -> Expander<'t> {
+        Expander {
+            text,
+            position: 0,
+            arguments,
+            parameters: Vec::new(),
+        }
+    }
+
+    /// Expands text until the end of the template, or until the end of the conditional block
+    /// starting at `block_start`, if given. Nothing is written if `emit` is false.
+    fn expand(
+        &mut self,
+        out: &mut String,
+        emit: bool,
+        block_start: Option<usize>,
+    ) -> Result<(), Error> {
+        let bytes = self.text.as_bytes();
+        // depth of square brackets that aren't conditional blocks, like inline math
+        let mut depth = 0;
+
+        while self.position < bytes.len() {
+            let start = self.position;
+            while self.position < bytes.len()
+                && !matches!(bytes[self.position], b'"' | b'#' | b'$' | b'[' | b']')
+            {
+                self.position += 1;
+            }
+            self.emit(out, emit, start);
+
+            if self.position >= bytes.len() {
+                break;
+            }
+
+            match bytes[self.position] {
+                b'"' => self.expand_string(out, emit)?,
+                b'#' => {
+                    // comments are copied as-is
+                    let start = self.position;
+                    self.position = self.text[start..]
+                        .find('\n')
+                        .map_or(bytes.len(), |end| start + end);
+                    self.emit(out, emit, start);
+                }
+                b'$' => self.expand_parameter(out, emit)?,
+                b'[' if bytes.get(self.position + 1) == Some(&b'[') => {
+                    self.expand_block(out, emit)?
+                }
+                b'[' => {
+                    depth += 1;
+                    self.position += 1;
+                    self.emit(out, emit, self.position - 1);
+                }
+                _ if depth == 0 && block_start.is_some() => {
+                    // end of the conditional block
+                    self.position += 1;
+                    return Ok(());
+                }
+                _ if depth == 0 => return Err(self.error(self.position, "unexpected ]")),
+                _ => {
+                    depth -= 1;
+                    self.position += 1;
+                    self.emit(out, emit, self.position - 1);
+                }
+            }
+        }
+
+        match block_start {
+            Some(start) => Err(self.error(start, "unterminated conditional block")),
+            None => Ok(()),
+        }
+    }
+
+    /// Expands a quoted string, substituting any parameters inside it.
+    fn expand_string(&mut self, out: &mut String, emit: bool) -> Result<(), Error> {
+        let bytes = self.text.as_bytes();
+        let string_start = self.position;
+        self.position += 1;
+        self.emit(out, emit, string_start);
+
+        loop {
+            let start = self.position;
+            while self.position < bytes.len()
+                && !matches!(bytes[self.position], b'"' | b'\\' | b'$')
+            {
+                self.position += 1;
+            }
+            self.emit(out, emit, start);
+
+            match bytes.get(self.position) {
+                Some(b'"') => {
+                    self.position += 1;
+                    self.emit(out, emit, self.position - 1);
+                    return Ok(());
+                }
+                Some(b'\\') => {
+                    // keep escape sequences intact
+                    let start = self.position;
+                    self.position += 1;
+                    if let Some(c) = self.text[self.position..].chars().next() {
+                        self.position += c.len_utf8();
+                    }
+                    self.emit(out, emit, start);
+                }
+                Some(_) => self.expand_parameter(out, emit)?,
+                None => return Err(self.error(string_start, "unterminated string")),
+            }
+        }
+    }
+
+    /// Expands a parameter like `$NAME$` or `$NAME|default$`.
+    fn expand_parameter(&mut self, out: &mut String, emit: bool) -> Result<(), Error> {
+        let start = self.position;
+        let end = match self.text[start + 1..].find(['$', '\n']) {
+            Some(end) if self.text.as_bytes()[start + 1 + end] == b'$' => start + 1 + end,
+            _ => return Err(self.error(start, "unterminated parameter")),
+        };
+
+        let contents = &self.text[start + 1..end];
+        let (name, default) = match contents.split_once('|') {
+            Some((name, default)) => (name, Some(default)),
+            None => (contents, None),
+        };
+
+        if name.is_empty() {
+            return Err(self.error(start, "expected parameter name"));
+        }
+
+        self.add_parameter(name);
+        self.position = end + 1;
+
+        if emit {
+            match self.argument(name).or(default) {
+                Some(value) => out.push_str(value),
+                None => {
+                    return Err(
+                        self.error(start, format!("missing argument for parameter '{}'", name))
+                    )
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Expands a conditional block like `[[NAME] ... ]` or `[[!NAME] ... ]`.
+    fn expand_block(&mut self, out: &mut String, emit: bool) -> Result<(), Error> {
+        let start = self.position;
+        let header_end = match self.text[start..].find(']') {
+            Some(end) => start + end,
+            None => return Err(self.error(start, "unterminated conditional block")),
+        };
+
+        let condition = &self.text[start + 2..header_end];
+        let (negated, name) = match condition.strip_prefix('!') {
+            Some(name) => (true, name),
+            None => (false, condition),
+        };
+
+        if name.is_empty() {
+            return Err(self.error(start, "expected parameter name"));
+        }
+
+        self.add_parameter(name);
+        self.position = header_end + 1;
+
+        let included = self.argument(name).is_some() != negated;
+        self.expand(out, emit && included, Some(start))
+    }
+
+    fn argument(&self, name: &str) -> Option<&'t str> {
+        self.arguments
+            .iter()
+            .find(|(argument, _)| *argument == name)
+            .map(|(_, value)| *value)
+    }
+
+    fn add_parameter(&mut self, name: &str) {
+        if !self.parameters.iter().any(|p| p == name) {
+            self.parameters.push(name.to_owned());
+        }
+    }
+
+    /// Writes the text from `start` up to the current position, if `emit` is set.
+    fn emit(&self, out: &mut String, emit: bool, start: usize) {
+        if emit {
+            out.push_str(&self.text[start..self.position]);
+        }
+    }
+
+    fn error(&self, position: usize, message: impl ToString) -> Error {
+        Error::new_positioned(ErrorType::ParameterError, position, message)
+    }
+}
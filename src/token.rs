@@ -1,6 +1,9 @@
 use std::fmt;
 
-use crate::tokenizer::Tokenizer;
+use crate::{
+    source::{SourceId, Span},
+    tokenizer::Tokenizer,
+};
 
 /// A token that can be produced by a `Tokenizer`.
 pub trait ConstructableToken {
@@ -16,9 +19,16 @@ pub enum TokenType {
     /// An integer or decimal number.
     Number,
     /// A string wrapped in double quotes.
+    ///
+    /// The token's contents don't include the quotes, and any escape sequences
+    /// in the string are left as-is.
     String,
     /// The `=` symbol.
     Equals,
+    /// The `==` symbol.
+    DoubleEquals,
+    /// The `!=` or `<>` symbols.
+    NotEquals,
     /// The `{` symbol.
     OpenBracket,
     /// The `}` symbol.
@@ -37,17 +47,36 @@ pub enum TokenType {
     Boolean,
     /// A token in the form `\d+.\d+.\d+(.\d+)?`.`
     Date,
+    /// A reference to a scripted variable, such as `@cost`.
+    ///
+    /// The token's contents include the `@`.
+    Variable,
+    /// An inline math expression, such as `@[ cost * 2 ]`.
+    ///
+    /// The token's contents include the `@[` and `]`.
+    InlineMath,
+    /// The start of a conditional parameter block, such as `[[PARAM]` or `[[!PARAM]`.
+    ParameterBlockStart,
+    /// The `]` symbol ending a conditional parameter block.
+    ParameterBlockEnd,
+    /// A comment, such as `# text`.
+    ///
+    /// The token's contents include the `#`, but not the line break ending the comment.
+    /// Comments are only produced if enabled with [Tokenizer::with_comments].
+    Comment,
 }
 
 /// A single [Token] obtained from a [Tokenizer].
 #[derive(Debug)]
 pub struct Token {
-    /// The index of this token in source text.
+    /// The byte offset of this token in source text.
     pub index: usize,
-    /// The length of this token in chars.
+    /// The length of this token in bytes.
     pub length: usize,
     /// The type of this token.
     pub token_type: TokenType,
+    /// The source file this token was read from.
+    pub source: SourceId,
 }
 
 impl Token {
@@ -57,13 +86,22 @@ impl Token {
             index,
             length,
             token_type,
+            source: SourceId::UNKNOWN,
         }
     }
+
+    /// Returns the [Span] of source text covered by this token.
+    pub fn span(&self) -> Span {
+        Span::new(self.source, self.index, self.index + self.length)
+    }
 }
 
 impl ConstructableToken for Token {
-    fn from_tokenizer(_t: &Tokenizer, token_type: TokenType, index: usize, length: usize) -> Self {
-        Token::new(token_type, index, length)
+    fn from_tokenizer(t: &Tokenizer, token_type: TokenType, index: usize, length: usize) -> Self {
+        Token {
+            source: t.source(),
+            ..Token::new(token_type, index, length)
+        }
     }
 }
 
@@ -80,12 +118,21 @@ impl fmt::Display for Token {
 /// A [Token] that owns its value.
 #[derive(Debug)]
 pub struct OwnedToken {
-    /// The index of this token in the source text.
+    /// The byte offset of this token in the source text.
     pub index: usize,
     /// The type of this token.
     pub token_type: TokenType,
     /// The value of this token.
     pub value: String,
+    /// The source file this token was read from.
+    pub source: SourceId,
+}
+
+impl OwnedToken {
+    /// Returns the [Span] of source text covered by this token.
+    pub fn span(&self) -> Span {
+        Span::new(self.source, self.index, self.index + self.value.len())
+    }
 }
 
 impl ConstructableToken for OwnedToken {
@@ -94,6 +141,7 @@ impl ConstructableToken for OwnedToken {
             index,
             token_type,
             value: t.str_for_range((index, index + length)).to_owned(),
+            source: t.source(),
         }
     }
 }
@@ -1,35 +1,231 @@
-use std::{marker::PhantomData, str::FromStr};
+use std::{
+    borrow::Cow,
+    cell::OnceCell,
+    io::Read,
+    marker::PhantomData,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
 
 use crate::{
+    dialect::Dialect,
+    encoding::{self, Encoding},
+    source::{LineIndex, SourceId},
     token::{ConstructableToken, OwnedToken, Token, TokenType},
     types::Date,
     util::error::{Error, ErrorContext, ErrorContextProvider, ErrorType, ParseResult},
-    util::text_helpers::CharHelper,
+    util::{
+        file::{self, Text},
+        scan,
+        stream::Stream,
+        text_helpers::CharHelper,
+    },
 };
 
-const COMMENT_CHAR: char = '#';
-const NEW_LINE: char = '\n';
+const COMMENT_CHAR: u8 = b'#';
+
+/// How close to the end of the buffered text a token can end before a streaming [Tokenizer]
+/// reads more input and tokenizes it again, since the token might continue past the end.
+const STREAM_LOOKAHEAD: usize = 8;
 
 /// Tokenizes an input string into a sequence of [Token] objects.
 ///
-/// For efficient lookups, the input string will be copied when the tokenizer
-/// is created. However, the tokenizer will not perform any copies or allocations
-/// after it's created.
+/// The tokenizer works directly on the UTF-8 bytes of the input string, so it
+/// doesn't perform any copies or allocations. All positions used by the tokenizer,
+/// including [Token::index] and [Token::length], are byte offsets into the input string.
+///
+/// A [Tokenizer] created with [Tokenizer::from_bytes] owns its text if the input had to be
+/// transcoded to UTF-8.
+///
+/// A [Tokenizer] created with [Tokenizer::from_path] owns its text. One created with
+/// [Tokenizer::from_path_mapped] memory-maps the file when it can.
+///
+/// A [Tokenizer] created with [Tokenizer::from_read] reads its input a chunk at a time, only keeping
+/// the part of the text around the current position in memory. Positions are still byte offsets into
+/// the whole input.
 pub struct Tokenizer<'a> {
-    /// The current position of the [Tokenizer] in the input text.
+    /// The current position of the [Tokenizer] in the input text, as a byte offset.
+    ///
+    /// This should always point to the start of a UTF-8 character.
     pub position: usize,
-    chars: Vec<char>,
-    text: &'a str,
+    text: Text<'a>,
+    dialect: Dialect,
+    source: SourceId,
+    /// The path of the file the text was read from, if known.
+    path: Option<PathBuf>,
+    /// Whether comments are returned as tokens instead of being skipped.
+    comments: bool,
+    /// The line index of the text, built the first time it's needed.
+    line_index: OnceCell<Cow<'a, LineIndex>>,
+    /// The position of the start of `text` in the input, which is only above 0 when streaming.
+    offset: usize,
+    /// The input that `text` is read from, if created with [Tokenizer::from_read].
+    stream: Option<Stream>,
+    /// The start of the last call to [Tokenizer::next], which is kept buffered so the last token
+    /// can still be read after looking ahead.
+    previous_start: usize,
+    /// A position that's kept buffered however far the tokenizer moves past it.
+    retained: Option<usize>,
 }
 
 impl<'a> Tokenizer<'a> {
     /// Creates a new [Tokenizer] from the input text.
-    pub fn new(text: &'a str) -> Tokenizer {
+    pub fn new(text: &'a str) -> Tokenizer<'a> {
+        Tokenizer::from_text(Text::Borrowed(text))
+    }
+
+    /// Creates a new [Tokenizer] from raw bytes in the given [Encoding].
+    ///
+    /// Any byte order mark is stripped, and the input is only copied if it needs to be transcoded.
+    pub fn from_bytes(bytes: &'a [u8], encoding: Encoding) -> Result<Tokenizer<'a>, Error> {
+        Ok(Tokenizer::from_text(
+            encoding::decode(bytes, encoding)?.into(),
+        ))
+    }
+
+    /// Creates a new [Tokenizer] that reads the file at `path` in the given [Encoding].
+    ///
+    /// The file is read into memory, and the contents of tokens are owned, since they can't
+    /// outlive the tokenizer.
+    ///
+    /// The path is recorded in every [Error] the tokenizer produces, including errors opening
+    /// and reading the file, which are returned as [ErrorType::IoError].
+    pub fn from_path(path: impl AsRef<Path>, encoding: Encoding) -> Result<Tokenizer<'a>, Error> {
+        let path = path.as_ref();
+        let text = file::read(path, encoding).map_err(|e| e.with_path(path))?;
+        Ok(Tokenizer::from_text(text).with_path(path))
+    }
+
+    /// Creates a new [Tokenizer] that reads the file at `path` in the given [Encoding], mapping
+    /// it into memory instead of reading it if it's large enough.
+    ///
+    /// This is like [Tokenizer::from_path], except that files that don't have to be transcoded
+    /// aren't copied. Anything that can't be mapped, such as a pipe, is read into memory instead.
+    /// Use [Reader::from_tokenizer](`crate::reader::Reader::from_tokenizer`) to read a mapped file.
+    ///
+    /// # Safety
+    ///
+    /// The file mustn't be modified or truncated, by this process or any other, while the
+    /// tokenizer is alive. The mapped text is only checked to be valid UTF-8 once, so changing it
+    /// is undefined behavior, and reading a part of the file that was truncated away can crash
+    /// the process.
+    pub unsafe fn from_path_mapped(
+        path: impl AsRef<Path>,
+        encoding: Encoding,
+    ) -> Result<Tokenizer<'a>, Error> {
+        let path = path.as_ref();
+        // safety: the caller guarantees the file won't change while the tokenizer is alive
+        let text = unsafe { file::map(path, encoding) }.map_err(|e| e.with_path(path))?;
+        Ok(Tokenizer::from_text(text).with_path(path))
+    }
+
+    /// Creates a new [Tokenizer] that reads its input from `read` in the given [Encoding].
+    ///
+    /// The input is read a chunk at a time as tokens are needed, and text far enough behind the
+    /// current position is discarded, so memory use doesn't depend on the size of the input.
+    /// Any byte order mark is stripped, and [Encoding::Detect] detects the encoding from the first chunk.
+    ///
+    /// Since the text isn't kept, the contents of tokens are always owned. Looking ahead or moving
+    /// the position back only works within [Tokenizer::with_buffer_size] bytes of the current token,
+    /// and moving back any further returns an [ErrorType::InvalidState] error. Errors reading
+    /// the input are returned as [ErrorType::IoError].
+    pub fn from_read(read: impl Read + Send + 'static, encoding: Encoding) -> Tokenizer<'a> {
+        Tokenizer {
+            stream: Some(Stream::new(read, encoding)),
+            ..Tokenizer::from_text(Text::Owned(String::new()))
+        }
+    }
+
+    fn from_text(text: Text<'a>) -> Tokenizer<'a> {
         Tokenizer {
             position: 0,
-            chars: text.chars().collect(),
             text,
+            dialect: Dialect::default(),
+            source: SourceId::UNKNOWN,
+            path: None,
+            comments: false,
+            line_index: OnceCell::new(),
+            offset: 0,
+            stream: None,
+            previous_start: 0,
+            retained: None,
+        }
+    }
+
+    /// Sets how many bytes a [Tokenizer] created with [Tokenizer::from_read] reads at a time,
+    /// which is also how far behind the current token text is kept.
+    ///
+    /// The default is 64 KiB. This has no effect on other tokenizers.
+    pub fn with_buffer_size(mut self, size: usize) -> Tokenizer<'a> {
+        if let Some(stream) = &mut self.stream {
+            stream.buffer_size = size.max(1);
         }
+
+        self
+    }
+
+    /// Returns whether this [Tokenizer] reads its input from a stream.
+    pub fn is_streaming(&self) -> bool {
+        self.stream.is_some()
+    }
+
+    /// Sets the [Dialect] used to tokenize the input text.
+    pub fn with_dialect(mut self, dialect: Dialect) -> Tokenizer<'a> {
+        self.dialect = dialect;
+        self
+    }
+
+    /// Returns the [Dialect] used to tokenize the input text.
+    pub fn dialect(&self) -> &Dialect {
+        &self.dialect
+    }
+
+    /// Sets whether comments are returned as [TokenType::Comment] tokens instead of being skipped.
+    pub fn with_comments(mut self, comments: bool) -> Tokenizer<'a> {
+        self.comments = comments;
+        self
+    }
+
+    /// Returns whether comments are returned as [TokenType::Comment] tokens.
+    pub fn has_comments(&self) -> bool {
+        self.comments
+    }
+
+    /// Sets the [SourceId] used for the spans of tokens and errors produced by this tokenizer.
+    pub fn with_source(mut self, source: SourceId) -> Tokenizer<'a> {
+        self.source = source;
+        self
+    }
+
+    /// Returns the [SourceId] of the input text.
+    pub fn source(&self) -> SourceId {
+        self.source
+    }
+
+    /// Sets the path of the file the input text was read from, which is recorded in every [Error]
+    /// this tokenizer produces.
+    pub fn with_path(mut self, path: impl Into<PathBuf>) -> Tokenizer<'a> {
+        self.path = Some(path.into());
+        self
+    }
+
+    /// Returns the path of the file the input text was read from, if known.
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Uses an existing [LineIndex] for the input text instead of building a new one.
+    pub(crate) fn with_line_index(mut self, line_index: Cow<'a, LineIndex>) -> Tokenizer<'a> {
+        self.line_index = OnceCell::from(line_index);
+        self
+    }
+
+    /// Returns the [LineIndex] of the input text, building it if needed.
+    ///
+    /// When streaming, this only covers the text that's currently buffered.
+    pub fn line_index(&self) -> &LineIndex {
+        self.line_index
+            .get_or_init(|| Cow::Owned(LineIndex::new(&self.text)))
     }
 
     /// Parses every [Token] in the input text and returns them in a vector.
@@ -39,21 +235,156 @@ impl<'a> Tokenizer<'a> {
         Ok(tokens?)
     }
 
+    /// Parses every token in the given text, skipping over invalid tokens.
+    ///
+    /// Unlike [Tokenizer::parse_all], this doesn't stop at the first error. Every error
+    /// encountered is returned along with the tokens that could be read.
+    pub fn parse_all_lenient(text: &str) -> (Vec<OwnedToken>, Vec<Error>) {
+        let mut tokenizer = Tokenizer::new(text);
+        let mut tokens = Vec::new();
+        let mut errors = Vec::new();
+
+        loop {
+            match tokenizer.next_owned() {
+                Ok(Some(token)) => tokens.push(token),
+                Ok(None) => return (tokens, errors),
+                Err(e) => {
+                    tokenizer.skip_error(&e);
+                    errors.push(e);
+                }
+            }
+        }
+    }
+
+    /// Moves past the character that caused the given error, so that tokenizing can
+    /// continue after an invalid token.
+    pub fn skip_error(&mut self, error: &Error) {
+        let position = error.position.unwrap_or(self.position);
+        let length = self.char_at(position).map_or(1, char::len_utf8);
+        self.position = usize::max(self.position, position + length).min(self.end());
+    }
+
+    /// Updates a list of tokens for an edit to the text, only re-tokenizing the region affected by the edit.
+    ///
+    /// `tokens` must be the tokens of the text before the edit, as produced by a [Tokenizer] with the
+    /// same settings as this one, and this [Tokenizer] must be reading the text after the edit.
+    /// Tokenizing starts just before the edit, and stops at the first token after the edit that
+    /// matches a token from before the edit. This handles edits that change how the text after them
+    /// is read, such as adding a quote that opens a string or a `#` that starts a comment.
+    ///
+    /// Returns the window of `tokens` that was replaced. Tokens after the window keep their contents,
+    /// but their positions are shifted by the change in the text's length.
+    ///
+    /// If the edited text can't be tokenized, an error is returned and `tokens` is left unchanged.
+    pub fn relex(
+        &mut self,
+        tokens: &mut Vec<OwnedToken>,
+        edit: &TextEdit,
+    ) -> Result<TokenWindow, Error> {
+        let delta = edit.new_end as isize - edit.old_end as isize;
+
+        // start at the token before the first one touching the edit, since the edit could join them
+        let first_touching = tokens.partition_point(|t| token_bounds(t).1 < edit.start);
+        let start = first_touching.saturating_sub(1);
+        self.position = match start {
+            0 => 0,
+            i => token_bounds(&tokens[i - 1]).1,
+        };
+
+        let mut inserted = Vec::new();
+        let mut old = start;
+        let end = loop {
+            let token = match self.next_owned()? {
+                Some(token) => token,
+                None => break tokens.len(),
+            };
+
+            if token.index >= edit.new_end {
+                // skip old tokens that start before this one
+                while old < tokens.len()
+                    && (token_bounds(&tokens[old]).0 < edit.old_end
+                        || ((tokens[old].index as isize) + delta) < token.index as isize)
+                {
+                    old += 1;
+                }
+
+                let unchanged = tokens.get(old).is_some_and(|existing| {
+                    (existing.index as isize) + delta == token.index as isize
+                        && existing.token_type == token.token_type
+                        && existing.value == token.value
+                });
+
+                if unchanged {
+                    // the rest of the tokens are unchanged
+                    break old;
+                }
+            }
+
+            inserted.push(token);
+        };
+
+        for token in &mut tokens[end..] {
+            token.index = (token.index as isize + delta) as usize;
+        }
+
+        let window = TokenWindow {
+            start,
+            removed: end - start,
+            inserted: inserted.len(),
+        };
+        tokens.splice(start..end, inserted);
+
+        Ok(window)
+    }
+
     /// Checks if this [Tokenizer] has hit the end of the character stream.
     pub fn is_done(&self) -> bool {
-        self.position >= self.chars.len()
+        self.position >= self.end() && self.stream.as_ref().is_none_or(|stream| stream.eof)
     }
 
     /// Checks if the given character is the next one in the character stream.
     ///
     /// This checks the next character, not the next token. It doesn't skip whitespace or comments.
     pub fn is_next_char(&self, c: char) -> bool {
-        match self.position >= self.chars.len() - 1 {
-            true => false,
-            false => self.chars[self.position + 1] == c,
+        let bytes = self.text.as_bytes();
+        let position = self.local(self.position);
+        match bytes.get(position) {
+            None => false,
+            // fast path for ASCII characters, which are always a single byte
+            Some(b) if b.is_ascii() && c.is_ascii() => bytes.get(position + 1) == Some(&(c as u8)),
+            Some(_) => match self.char_at(self.position) {
+                Some(current) => self.char_at(self.position + current.len_utf8()) == Some(c),
+                None => false,
+            },
         }
     }
 
+    /// Returns the source text this [Tokenizer] is reading from.
+    ///
+    /// When streaming, this is only the text that's currently buffered.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Returns the position of the end of the buffered text.
+    fn end(&self) -> usize {
+        self.offset + self.text.len()
+    }
+
+    /// Converts a position in the input into an index into the buffered text.
+    ///
+    /// Positions before the buffered text wrap around to past the end of it, so looking them up
+    /// finds nothing.
+    fn local(&self, position: usize) -> usize {
+        position.wrapping_sub(self.offset)
+    }
+
+    /// Keeps the text from `position` onwards buffered until this is called again with [None],
+    /// however far the tokenizer moves past it.
+    pub(crate) fn retain(&mut self, position: Option<usize>) {
+        self.retained = position;
+    }
+
     /// Creates a new iterator from this [Tokenizer].
     pub fn iter_generic<T: ConstructableToken>(&'a mut self) -> TokenIterator<T> {
         TokenIterator::new(self)
@@ -81,40 +412,212 @@ impl<'a> Tokenizer<'a> {
 
     /// Obtains the next [ConstructableToken] from the character stream, advancing the internal position.
     fn next_generic<T: ConstructableToken>(&mut self) -> ParseResult<T> {
-        if self.is_done() {
-            return Ok(None);
+        if self.stream.is_none() {
+            return self.read_token();
+        }
+
+        let start = self.position;
+        loop {
+            if start < self.offset {
+                return Err(Error::new_positioned(
+                    ErrorType::InvalidState,
+                    start,
+                    "position is no longer buffered",
+                ));
+            }
+
+            let result = self.read_token();
+            // a token ending near the end of the buffered text might continue past it
+            if self.position + STREAM_LOOKAHEAD <= self.end()
+                || self.stream.as_ref().is_some_and(|stream| stream.eof)
+            {
+                self.previous_start = start;
+                return result;
+            }
+
+            self.position = start;
+            self.refill(start)?;
+        }
+    }
+
+    /// Discards buffered text that's no longer needed and reads more of the stream,
+    /// keeping the text of the token starting at `start`.
+    fn refill(&mut self, start: usize) -> Result<(), Error> {
+        let Some(stream) = &mut self.stream else {
+            return Ok(());
+        };
+
+        let text = self.text.to_mut();
+        let mut keep = start
+            .saturating_sub(stream.buffer_size)
+            .min(self.previous_start);
+        if let Some(retained) = self.retained {
+            keep = keep.min(retained);
         }
 
-        let mut c: char = self.skip_comments()?;
-        // skip whitespace
-        while !self.is_done() && char::is_whitespace(c) {
-            self.position = self.position + 1;
-            c = self.skip_comments()?;
+        let mut discard = keep.saturating_sub(self.offset).min(text.len());
+        while !text.is_char_boundary(discard) {
+            discard -= 1;
         }
 
-        if self.is_done() {
-            return Ok(None);
+        if discard > 0 {
+            stream.discard(&text[..discard]);
+            text.drain(..discard);
+            self.offset += discard;
         }
 
+        // read at least as much as is buffered after the start of the token, so a long token
+        // is only tokenized again a few times
+        let end = self.offset + text.len();
+        let min_len = usize::max(stream.buffer_size, end - start);
+        let result = stream.fill(text, min_len, end);
+        self.line_index = OnceCell::new();
+        result
+    }
+
+    /// Reads the next token from the buffered text.
+    fn read_token<T: ConstructableToken>(&mut self) -> ParseResult<T> {
+        let c = match self.skip_comments() {
+            Some(c) => c,
+            None => return Ok(None),
+        };
+
         match c {
-            '=' => Ok(Some(self.new_token_incr(TokenType::Equals, 1))),
+            '#' => {
+                // only reached if comments are enabled
+                let start_pos = self.position;
+                let end = self.find_comment_end(start_pos);
+                let length = self
+                    .str_for_range((start_pos, end))
+                    .trim_end_matches('\r')
+                    .len();
+                self.position = end;
+                Ok(Some(self.new_token(TokenType::Comment, start_pos, length)))
+            }
+            '=' => Ok(Some(match self.is_next_char('=') {
+                true => self.new_token_incr(TokenType::DoubleEquals, 2),
+                false => self.new_token_incr(TokenType::Equals, 1),
+            })),
             '{' => Ok(Some(self.new_token_incr(TokenType::OpenBracket, 1))),
             '}' => Ok(Some(self.new_token_incr(TokenType::CloseBracket, 1))),
             '>' => Ok(Some(match self.is_next_char('=') {
                 true => self.new_token_incr(TokenType::GreaterThanEq, 2),
                 false => self.new_token_incr(TokenType::GreaterThan, 1),
             })),
-            '<' => Ok(Some(match self.is_next_char('=') {
-                true => self.new_token_incr(TokenType::LessThanEq, 2),
-                false => self.new_token_incr(TokenType::LessThan, 1),
-            })),
+            '<' => Ok(Some(
+                match (self.is_next_char('='), self.is_next_char('>')) {
+                    (true, _) => self.new_token_incr(TokenType::LessThanEq, 2),
+                    (_, true) => self.new_token_incr(TokenType::NotEquals, 2),
+                    _ => self.new_token_incr(TokenType::LessThan, 1),
+                },
+            )),
+            '!' => match self.is_next_char('=') {
+                true => Ok(Some(self.new_token_incr(TokenType::NotEquals, 2))),
+                false => Err(self.parse_error(ErrorType::TokenizerError, "unexpected char !")),
+            },
             '?' => match self.is_next_char('=') {
+                true if !self.dialect.existence_check => Err(self.dialect_error("the ?= operator")),
                 true => Ok(Some(self.new_token_incr(TokenType::ExistenceCheck, 2))),
                 false => Err(self.parse_error(ErrorType::TokenizerError, "unexpected char ?")),
             },
-            c if (c == '-' || char::is_digit(c, 10)) => {
+            '@' if !self.dialect.scripted_variables => {
+                Err(self.dialect_error("scripted variables"))
+            }
+            '@' if self.is_next_char('[') => {
+                if !self.dialect.inline_math {
+                    return Err(self.dialect_error("inline math"));
+                }
+
+                // inline math, like @[ cost * 2 ], which ends at the matching close bracket
+                let start_pos = self.position;
+                let bytes = self.text.as_bytes();
+                let mut depth = 0;
+                self.position += 1;
+
+                while let Some(&b) = bytes.get(self.local(self.position)) {
+                    match b {
+                        b'[' => depth += 1,
+                        b']' => depth -= 1,
+                        _ => (),
+                    }
+
+                    self.position += 1;
+
+                    if depth == 0 {
+                        return Ok(Some(self.new_token(
+                            TokenType::InlineMath,
+                            start_pos,
+                            self.position - start_pos,
+                        )));
+                    }
+                }
+
+                Err(self.parse_error_pos(
+                    ErrorType::TokenizerError,
+                    start_pos,
+                    "unterminated inline math expression",
+                ))
+            }
+            '@' => {
+                // scripted variable reference, like @cost
+                let start_pos = self.position;
+                self.position = scan::identifier_end(&self.text, self.local(start_pos + 1), false)
+                    + self.offset;
+
+                if self.position == start_pos + 1 {
+                    return Err(self.parse_error(
+                        ErrorType::TokenizerError,
+                        "expected variable name after '@'",
+                    ));
+                }
+
+                Ok(Some(self.new_token(
+                    TokenType::Variable,
+                    start_pos,
+                    self.position - start_pos,
+                )))
+            }
+            '$' if !self.dialect.parameters => Err(self.dialect_error("parameters")),
+            '[' if !self.dialect.parameter_blocks => {
+                Err(self.dialect_error("conditional parameter blocks"))
+            }
+            '[' if self.is_next_char('[') => {
+                // start of a conditional parameter block, like [[PARAM] or [[!PARAM]
+                let start_pos = self.position;
+                match self.text[self.local(start_pos + 2)..].find(']') {
+                    Some(end) => {
+                        let length = end + 3;
+                        self.position += length;
+                        Ok(Some(self.new_token(
+                            TokenType::ParameterBlockStart,
+                            start_pos,
+                            length,
+                        )))
+                    }
+                    None => {
+                        let error = self.parse_error(
+                            ErrorType::TokenizerError,
+                            "unterminated conditional parameter block",
+                        );
+                        // the close bracket might not have been read yet
+                        if self.stream.is_some() {
+                            self.position = self.end();
+                        }
+
+                        Err(error)
+                    }
+                }
+            }
+            ']' if self.dialect.parameter_blocks => {
+                Ok(Some(self.new_token_incr(TokenType::ParameterBlockEnd, 1)))
+            }
+            // parameters like $PARAM$ can be all or part of an identifier
+            '$' => Ok(Some(self.identifier_from(self.position))),
+            c if (c == '-' || c.is_ascii_digit()) => {
                 // number handling
+                // every character in a number is ASCII, so we can work on the bytes directly
 
+                let bytes = self.text.as_bytes();
                 let is_negative = c == '-';
 
                 let mut num_digits = match c {
@@ -127,13 +630,13 @@ impl<'a> Tokenizer<'a> {
                 let mut num_decimal_places: usize = 0;
                 let mut last_decimal_index: usize = 0;
 
-                if !self.is_done() {
-                    self.position = self.position + 1;
-                }
+                self.position += 1;
 
-                while !self.is_done() {
-                    let num_c = self.chars[self.position];
-                    if num_c == '.' {
+                while let Some(&num_c) = bytes.get(self.local(self.position)) {
+                    if num_c.is_ascii_digit() {
+                        num_digits += 1;
+                        this_num_digits = num_digits;
+                    } else if num_c == b'.' {
                         // don't accept a 5..0 as a date
                         if this_num_digits < 1 {
                             return Err(
@@ -149,16 +652,22 @@ impl<'a> Tokenizer<'a> {
                         }
 
                         this_num_digits = 0;
-                        num_decimal_places = num_decimal_places + 1;
+                        num_decimal_places += 1;
                         last_decimal_index = self.position;
-                    } else if char::is_digit(num_c, 10) {
-                        num_digits = num_digits + 1;
-                        this_num_digits = num_digits;
                     } else {
                         break;
                     }
 
-                    self.position = self.position + 1;
+                    self.position += 1;
+                }
+
+                // identifiers can start with a number, like 1st_army
+                if !is_negative
+                    && self
+                        .char_at(self.position)
+                        .is_some_and(scan::is_identifier_start)
+                {
+                    return Ok(Some(self.identifier_from(start_pos)));
                 }
 
                 // a bare - isn't allowed, and neither is 15. as a number
@@ -185,22 +694,18 @@ impl<'a> Tokenizer<'a> {
             }
             '"' => {
                 let start_pos = self.position;
-
-                loop {
-                    self.position = self.position + 1;
-                    if self.is_done() || self.chars[self.position] == '"' {
-                        break;
+                match scan::string_end(self.text.as_bytes(), self.local(start_pos + 1)) {
+                    None => {
+                        self.position = self.end();
+                        Err(self.parse_error(
+                            ErrorType::TokenizerError,
+                            "unexpected EOF while reading string",
+                        ))
                     }
-                }
-
-                match self.is_done() {
-                    true => Err(self.parse_error(
-                        ErrorType::TokenizerError,
-                        "unexpected EOF while reading string",
-                    )),
-                    false => {
-                        let length = self.position - start_pos - 1;
-                        self.position = self.position + 1;
+                    Some(end) => {
+                        let end = end + self.offset;
+                        let length = end - start_pos - 1;
+                        self.position = end + 1;
                         Ok(Some(self.new_token(
                             TokenType::String,
                             start_pos + 1,
@@ -209,34 +714,7 @@ impl<'a> Tokenizer<'a> {
                     }
                 }
             }
-            c if c == '_' || c.is_alphabetic() => {
-                let start_pos = self.position;
-                loop {
-                    self.position = self.position + 1;
-                    if self.is_done()
-                        || (self.chars[self.position] != '_'
-                            && self.chars[self.position] != ':'
-                            && !char::is_alphanumeric(self.chars[self.position]))
-                    {
-                        break;
-                    }
-                }
-
-                let length = self.position - start_pos;
-                let token = match (length == 3
-                    && self.chars[start_pos] == 'y'
-                    && self.chars[start_pos + 1] == 'e'
-                    && self.chars[start_pos + 2] == 's')
-                    || (length == 2
-                        && self.chars[start_pos] == 'n'
-                        && self.chars[start_pos + 1] == 'o')
-                {
-                    true => self.new_token(TokenType::Boolean, start_pos, length),
-                    false => self.new_token(TokenType::Identifier, start_pos, length),
-                };
-
-                Ok(Some(token))
-            }
+            c if scan::is_identifier_start(c) => Ok(Some(self.identifier_from(self.position))),
             _ => ParseResult::Err(self.parse_error(
                 ErrorType::TokenizerError,
                 format!("unexpected character {} in input", c),
@@ -244,6 +722,21 @@ impl<'a> Tokenizer<'a> {
         }
     }
 
+    /// Reads an identifier or boolean starting at `start_pos`, continuing from the current position.
+    fn identifier_from<T: ConstructableToken>(&mut self, start_pos: usize) -> T {
+        self.position = scan::identifier_end(
+            &self.text,
+            self.local(self.position),
+            self.dialect.parameters,
+        ) + self.offset;
+
+        let length = self.position - start_pos;
+        match self.str_for_range((start_pos, self.position)) {
+            "yes" | "no" => self.new_token(TokenType::Boolean, start_pos, length),
+            _ => self.new_token(TokenType::Identifier, start_pos, length),
+        }
+    }
+
     /// Obtains the next [Token] from the character stream without changing the internal position.
     pub fn peek(&mut self) -> ParseResult<Token> {
         let pos = self.position;
@@ -253,133 +746,154 @@ impl<'a> Tokenizer<'a> {
     }
 
     /// Returns a borrowed string slice of the [Token]'s contents.
-    pub fn str_for_token(&self, t: &Token) -> &'a str {
-        let end = t.index + t.length;
-        return &self.text[t.index..end];
+    pub fn str_for_token(&self, t: &Token) -> &str {
+        self.str_for_range((t.index, t.index + t.length))
     }
 
     /// Returns a borrowed string slice of the contents of `range`.
     ///
     /// `range` is a tuple of `(start_index, end_index)`.
-    pub fn str_for_range(&self, range: (usize, usize)) -> &'a str {
+    pub fn str_for_range(&self, range: (usize, usize)) -> &str {
         let (start, end) = range;
-        return &self.text[start..end];
+        &self.text[self.local(start)..self.local(end)]
+    }
+
+    /// Returns the [Token]'s contents, borrowed from the source text if the [Tokenizer] doesn't own it.
+    pub fn cow_for_token(&self, t: &Token) -> Cow<'a, str> {
+        self.cow_for_range((t.index, t.index + t.length))
+    }
+
+    /// Returns the contents of `range`, borrowed from the source text if the [Tokenizer] doesn't own it.
+    ///
+    /// `range` is a tuple of `(start_index, end_index)`.
+    pub fn cow_for_range(&self, range: (usize, usize)) -> Cow<'a, str> {
+        let (start, end) = (self.local(range.0), self.local(range.1));
+        match &self.text {
+            Text::Borrowed(text) => Cow::Borrowed(&text[start..end]),
+            text => Cow::Owned(text[start..end].to_owned()),
+        }
     }
 
     /// Returns a new [Date] created from the contents of [Token].
     pub fn date_for_token(&self, t: &Token) -> Result<Date, Error> {
+        self.parse_date(self.str_for_token(t), t.index)
+    }
+
+    /// Parses a [Date] from the text of a date token that starts at `position`.
+    pub(crate) fn parse_date(&self, str: &str, position: usize) -> Result<Date, Error> {
         let mut values: [u32; 4] = [0, 0, 0, 0];
-        let mut value_index: usize = 0;
-        let mut pos = t.index;
-        let mut last_start_pos = pos;
+        let mut parts = str.split('.');
+        let mut start = position;
 
-        fn to_next_val(t: &Tokenizer, start: usize, end: usize) -> Result<u32, Error> {
-            u32::from_str(t.str_for_range((start, end))).map_err(|_| {
-                t.parse_error_pos(
+        for (value, part) in values.iter_mut().zip(&mut parts) {
+            *value = u32::from_str(part).map_err(|_| {
+                self.parse_error_pos(
                     ErrorType::InvalidNumberError,
                     start,
-                    format!(
-                        "failed to parse number from token '{}'",
-                        &t.text[start..end]
-                    ),
+                    format!("failed to parse number from token '{}'", part),
                 )
-            })
-        }
-
-        while pos < t.index + t.length && value_index < 4 {
-            if self.chars[pos] == '.' {
-                let val = to_next_val(&self, last_start_pos, pos)?;
-                values[value_index] = val;
-                value_index = value_index + 1;
-                last_start_pos = pos + 1;
-            }
-
-            pos = pos + 1;
+            })?;
+            start += part.len() + 1;
         }
 
-        if pos - last_start_pos > 0 {
-            if pos > t.index + t.length || value_index >= 4 {
-                return Err(self.parse_error_pos(
-                    ErrorType::InvalidState,
-                    pos,
-                    "Read past end of token",
-                ));
-            }
-
-            values[value_index] = to_next_val(&self, last_start_pos, pos)?;
-        }
-
-        if pos != t.index + t.length {
+        if parts.next().is_some() {
             return Err(self.parse_error_pos(
                 ErrorType::InvalidState,
-                pos,
+                start - 1,
                 "Read date but token continues?",
             ));
         }
 
-        return Ok(Date {
-            years: values[0],
-            months: values[1],
-            days: values[2],
-            hours: values[3],
-        });
+        Ok(values.into())
     }
 
     /// Returns the index of the end of the line that position is on
     /// (either a new line character or the last character of `text`)
     pub fn find_end_of_line(&self, position: usize) -> usize {
-        let helper = CharHelper(&self.chars);
-        helper.find_line_end(position)
+        let helper = CharHelper(&self.text);
+        helper.find_line_end(self.local(position)) + self.offset
     }
 
     /// Creates a new [Error] using the given position.
     pub fn parse_error_pos(
-        &'a self,
+        &self,
         error_type: ErrorType,
         position: usize,
         message: impl ToString,
     ) -> Error {
         // clamp position to length to avoid panicking
-        let position = usize::min(position, self.chars.len() - 1);
+        let position = match position.checked_sub(self.offset) {
+            Some(position) => CharHelper(&self.text).clamp_position(position) + self.offset,
+            None => position,
+        };
         Error::new(Some(self), error_type, position, message)
     }
 
+    /// Creates a new [Error] for syntax that isn't supported by the current [Dialect].
+    fn dialect_error(&self, construct: &str) -> Error {
+        self.parse_error(
+            ErrorType::DialectError,
+            format!("{} doesn't support {}", self.dialect.name, construct),
+        )
+    }
+
     /// Creates a new [Error] using the current position in the [Tokenizer].
-    pub fn parse_error(&'a self, error_type: ErrorType, message: impl ToString) -> Error {
+    pub fn parse_error(&self, error_type: ErrorType, message: impl ToString) -> Error {
         self.parse_error_pos(error_type, self.position, message)
     }
 
     /// Creates a new [Error] using the position of the given token.
     pub fn parse_error_token(
-        &'a self,
+        &self,
         t: &Token,
         error_type: ErrorType,
         message: impl ToString,
     ) -> Error {
         self.parse_error_pos(error_type, t.index, message)
+            .with_end(t.index + t.length)
     }
 
     /// Returns whether the previous char was `c`.
     pub fn last_char_was(&self, c: char) -> bool {
-        if self.position == 0 || (self.position - 1) >= self.chars.len() {
-            return false;
+        match self.text.get(..self.local(self.position)) {
+            Some(before) => before.ends_with(c),
+            None => false,
         }
-
-        return self.chars[self.position - 1] == c;
     }
 
-    fn skip_comments(&mut self) -> Result<char, Error> {
-        if self.is_done() {
-            return Ok(*self.chars.last().unwrap_or(&'\0'));
+    /// Returns the character starting at the byte offset `position`, if any.
+    fn char_at(&self, position: usize) -> Option<char> {
+        let bytes = self.text.as_bytes();
+        let position = self.local(position);
+        match bytes.get(position) {
+            None => None,
+            // fast path for ASCII characters
+            Some(b) if b.is_ascii() => Some(*b as char),
+            Some(_) => self.text.get(position..).and_then(|s| s.chars().next()),
         }
-        let mut c = self.chars[self.position];
-        if c == COMMENT_CHAR {
-            while self.position < self.chars.len() - 1 && c != NEW_LINE {
-                self.position = self.position + 1;
-                c = self.chars[self.position];
+    }
+
+    /// Skips over any whitespace and comments, returning the next character in the stream.
+    ///
+    /// If comments are enabled, they're returned instead of skipped.
+    fn skip_comments(&mut self) -> Option<char> {
+        loop {
+            self.position =
+                scan::skip_whitespace(&self.text, self.local(self.position)) + self.offset;
+
+            match *self.text.as_bytes().get(self.local(self.position))? {
+                COMMENT_CHAR if !self.comments => {
+                    self.position = self.find_comment_end(self.position)
+                }
+                b if b.is_ascii() => return Some(b as char),
+                _ => return self.char_at(self.position),
             }
         }
-        Ok(c)
+    }
+
+    /// Returns the position of the new line ending the comment starting at `start`, or EOF if there isn't one.
+    fn find_comment_end(&self, start: usize) -> usize {
+        scan::line_end(self.text.as_bytes(), self.local(start)) + self.offset
     }
 
     fn new_token<T: ConstructableToken>(
@@ -393,19 +907,95 @@ impl<'a> Tokenizer<'a> {
 
     fn new_token_incr<T: ConstructableToken>(&mut self, token_type: TokenType, length: usize) -> T {
         let token = self.new_token(token_type, self.position, length);
-        self.position = self.position + length;
+        self.position += length;
         token
     }
 }
 
+/// Returns the range of source text covered by a token, including the quotes around strings.
+fn token_bounds(token: &OwnedToken) -> (usize, usize) {
+    let quote = (token.token_type == TokenType::String) as usize;
+    (token.index - quote, token.index + token.value.len() + quote)
+}
+
 impl<'a> ErrorContextProvider for Tokenizer<'a> {
     fn get_line_context(&self, position: usize, max_lines: usize) -> Option<ErrorContext> {
-        Some(ErrorContext::from_chars(
-            self.text,
-            &self.chars,
-            position,
+        let context = ErrorContext::from_line_index(
+            &self.text,
+            self.line_index(),
+            position.checked_sub(self.offset)?,
             max_lines,
-        ))
+        );
+
+        match &self.stream {
+            Some(stream) => Some(context.starting_at(stream.location.0, stream.location.1)),
+            None => Some(context),
+        }
+    }
+
+    fn source_id(&self) -> SourceId {
+        self.source
+    }
+
+    fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+}
+
+/// An edit to a piece of text, used by [Tokenizer::relex].
+///
+/// The bytes `start..old_end` of the text before the edit were replaced with the bytes
+/// `start..new_end` of the text after the edit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextEdit {
+    /// The byte offset where the edit starts, in both the old and new text.
+    pub start: usize,
+    /// The byte offset of the end of the replaced text in the old text.
+    pub old_end: usize,
+    /// The byte offset of the end of the replacement text in the new text.
+    pub new_end: usize,
+}
+
+impl TextEdit {
+    /// Creates a [TextEdit] replacing `range` of the old text with `length` bytes of new text.
+    pub fn new(range: std::ops::Range<usize>, length: usize) -> TextEdit {
+        TextEdit {
+            start: range.start,
+            old_end: range.end,
+            new_end: range.start + length,
+        }
+    }
+
+    /// Applies this edit to `text`, replacing the edited range with `replacement`.
+    pub fn apply(
+        text: &str,
+        range: std::ops::Range<usize>,
+        replacement: &str,
+    ) -> (String, TextEdit) {
+        let mut result = String::with_capacity(text.len() + replacement.len());
+        result.push_str(&text[..range.start]);
+        result.push_str(replacement);
+        result.push_str(&text[range.end..]);
+
+        (result, TextEdit::new(range, replacement.len()))
+    }
+}
+
+/// The window of tokens replaced by [Tokenizer::relex].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TokenWindow {
+    /// The index of the first replaced token.
+    pub start: usize,
+    /// The number of tokens from before the edit that were removed.
+    pub removed: usize,
+    /// The number of new tokens inserted at `start`.
+    pub inserted: usize,
+}
+
+impl TokenWindow {
+    /// Returns the range of the new tokens in the updated token list.
+    pub fn range(&self) -> std::ops::Range<usize> {
+        self.start..self.start + self.inserted
     }
 }
 
@@ -1,7 +1,13 @@
-use std::borrow::Cow;
+use std::{borrow::Cow, fmt};
 
 #[cfg(feature = "serde")]
-use serde::Deserialize;
+use std::marker::PhantomData;
+
+#[cfg(feature = "serde")]
+use serde::{
+    de::{self, MapAccess, SeqAccess, Visitor},
+    Deserialize, Deserializer,
+};
 
 use zerocopy::transmute;
 use zerocopy_derive::{AsBytes, FromBytes, FromZeroes};
@@ -9,7 +15,7 @@ use zerocopy_derive::{AsBytes, FromBytes, FromZeroes};
 use crate::token::TokenType;
 
 /// Types that are actually differentiable purely from tokens.
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
 pub enum RealType {
     /// An object or an array.
     ///
@@ -26,15 +32,20 @@ pub enum RealType {
     Identifier,
     /// A date.
     Date,
+    /// A color block with a color space prefix, such as `rgb { 255 128 0 }`.
+    Color,
 }
 
 /// The possible kinds of collections in a Clausewitz file.
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
 pub enum CollectionType {
     /// A key-value map.
     Object,
     /// A sequence of values.
     Array,
+    /// A collection containing both key-value pairs and bare values, like `{ 1 2 3 a = b }`
+    /// or `{ key = 1 flag }`.
+    Mixed,
 }
 
 impl RealType {
@@ -54,6 +65,466 @@ impl RealType {
     }
 }
 
+/// An operator separating the key of a property from its value.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash)]
+pub enum Operator {
+    /// The `=` operator.
+    Equals,
+    /// The `==` operator.
+    DoubleEquals,
+    /// The `!=` or `<>` operators.
+    NotEquals,
+    /// The `>` operator.
+    GreaterThan,
+    /// The `>=` operator.
+    GreaterThanEq,
+    /// The `<` operator.
+    LessThan,
+    /// The `<=` operator.
+    LessThanEq,
+    /// The `?=` operator.
+    ExistenceCheck,
+}
+
+impl Operator {
+    /// Every [TokenType] that represents an [Operator].
+    pub const TOKEN_TYPES: [TokenType; 8] = [
+        TokenType::Equals,
+        TokenType::DoubleEquals,
+        TokenType::NotEquals,
+        TokenType::GreaterThan,
+        TokenType::GreaterThanEq,
+        TokenType::LessThan,
+        TokenType::LessThanEq,
+        TokenType::ExistenceCheck,
+    ];
+
+    /// Creates an [Operator] from a [TokenType], if possible.
+    pub fn from_token_type(t: &TokenType) -> Option<Operator> {
+        match *t {
+            TokenType::Equals => Some(Operator::Equals),
+            TokenType::DoubleEquals => Some(Operator::DoubleEquals),
+            TokenType::NotEquals => Some(Operator::NotEquals),
+            TokenType::GreaterThan => Some(Operator::GreaterThan),
+            TokenType::GreaterThanEq => Some(Operator::GreaterThanEq),
+            TokenType::LessThan => Some(Operator::LessThan),
+            TokenType::LessThanEq => Some(Operator::LessThanEq),
+            TokenType::ExistenceCheck => Some(Operator::ExistenceCheck),
+            _ => None,
+        }
+    }
+
+    /// Returns the symbol for this operator.
+    ///
+    /// [Operator::NotEquals] is always returned as `!=`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Operator::Equals => "=",
+            Operator::DoubleEquals => "==",
+            Operator::NotEquals => "!=",
+            Operator::GreaterThan => ">",
+            Operator::GreaterThanEq => ">=",
+            Operator::LessThan => "<",
+            Operator::LessThanEq => "<=",
+            Operator::ExistenceCheck => "?=",
+        }
+    }
+
+    /// Creates an [Operator] from its symbol, if possible.
+    pub fn from_symbol(symbol: &str) -> Option<Operator> {
+        match symbol {
+            "=" => Some(Operator::Equals),
+            "==" => Some(Operator::DoubleEquals),
+            "!=" | "<>" => Some(Operator::NotEquals),
+            ">" => Some(Operator::GreaterThan),
+            ">=" => Some(Operator::GreaterThanEq),
+            "<" => Some(Operator::LessThan),
+            "<=" => Some(Operator::LessThanEq),
+            "?=" => Some(Operator::ExistenceCheck),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Operator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for Operator {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct OperatorVisitor;
+
+        impl<'de> Visitor<'de> for OperatorVisitor {
+            type Value = Operator;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("an operator")
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<Operator, E> {
+                Operator::from_symbol(v)
+                    .ok_or_else(|| E::invalid_value(de::Unexpected::Str(v), &self))
+            }
+        }
+
+        deserializer.deserialize_str(OperatorVisitor)
+    }
+}
+
+/// A value along with the [Operator] used to assign it, such as `> 30` in `age > 30`.
+///
+/// When deserialized by [Deserializer](`crate::de::Deserializer`), the operator is taken
+/// from the property being deserialized. Values that aren't part of a property, such as
+/// array items, use [Operator::Equals].
+///
+/// Other deserializers see a [Comparison] as a tuple of `(operator, value)`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Comparison<T> {
+    /// The operator separating the property's key from its value.
+    pub operator: Operator,
+    /// The value of the property.
+    pub value: T,
+}
+
+impl<T> Comparison<T> {
+    /// Creates a new [Comparison] from the given operator and value.
+    pub fn new(operator: Operator, value: T) -> Comparison<T> {
+        Comparison { operator, value }
+    }
+}
+
+/// The name that [Deserializer](`crate::de::Deserializer`) uses to recognize a [Comparison].
+#[cfg(feature = "serde")]
+pub(crate) const COMPARISON_STRUCT_NAME: &str = "$clauser::Comparison";
+
+#[cfg(feature = "serde")]
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Comparison<T> {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct ComparisonVisitor<T>(PhantomData<T>);
+
+        impl<'de, T: Deserialize<'de>> Visitor<'de> for ComparisonVisitor<T> {
+            type Value = Comparison<T>;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("an operator and a value")
+            }
+
+            fn visit_seq<A>(self, mut seq: A) -> Result<Comparison<T>, A::Error>
+            where
+                A: SeqAccess<'de>,
+            {
+                let operator = seq
+                    .next_element()?
+                    .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+                let value = seq
+                    .next_element()?
+                    .ok_or_else(|| de::Error::invalid_length(1, &self))?;
+                Ok(Comparison { operator, value })
+            }
+        }
+
+        deserializer.deserialize_tuple_struct(
+            COMPARISON_STRUCT_NAME,
+            2,
+            ComparisonVisitor(PhantomData),
+        )
+    }
+}
+
+/// The color space of a [Color], given by the prefix of its block.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash)]
+pub enum ColorSpace {
+    /// `rgb { r g b }`, with components from 0 to 255, or from 0 to 1 if any of them
+    /// have a fractional part.
+    Rgb,
+    /// `hsv { h s v }`, with components from 0 to 1.
+    Hsv,
+    /// `hsv360 { h s v }`, with a hue from 0 to 360 and a saturation and value from 0 to 100.
+    Hsv360,
+    /// `hex { rrggbb }`, stored as red, green, and blue components from 0 to 255.
+    Hex,
+}
+
+impl ColorSpace {
+    /// Creates a [ColorSpace] from the prefix of a color block, if possible.
+    pub fn from_prefix(prefix: &str) -> Option<ColorSpace> {
+        match prefix {
+            "rgb" => Some(ColorSpace::Rgb),
+            "hsv" => Some(ColorSpace::Hsv),
+            "hsv360" => Some(ColorSpace::Hsv360),
+            "hex" => Some(ColorSpace::Hex),
+            _ => None,
+        }
+    }
+
+    /// Returns the prefix used for color blocks in this color space.
+    pub fn prefix(&self) -> &'static str {
+        match self {
+            ColorSpace::Rgb => "rgb",
+            ColorSpace::Hsv => "hsv",
+            ColorSpace::Hsv360 => "hsv360",
+            ColorSpace::Hex => "hex",
+        }
+    }
+}
+
+impl fmt::Display for ColorSpace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.prefix())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for ColorSpace {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct ColorSpaceVisitor;
+
+        impl<'de> Visitor<'de> for ColorSpaceVisitor {
+            type Value = ColorSpace;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a color space")
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<ColorSpace, E> {
+                ColorSpace::from_prefix(v)
+                    .ok_or_else(|| E::invalid_value(de::Unexpected::Str(v), &self))
+            }
+        }
+
+        deserializer.deserialize_str(ColorSpaceVisitor)
+    }
+}
+
+/// A color, such as `rgb { 255 128 0 }` or `hsv { 0.5 0.4 0.9 }`.
+///
+/// The components are kept in the units of the color's [ColorSpace]. Use [Color::to_rgb]
+/// or [Color::to_hsv] to convert between color spaces.
+///
+/// When deserialized by [Deserializer](`crate::de::Deserializer`), unprefixed blocks such as
+/// `{ 255 128 0 }` are read as [ColorSpace::Rgb]. Other deserializers see a [Color] as a
+/// struct with `space` and `values` fields, or as a sequence of RGB components.
+#[derive(Debug, PartialEq, PartialOrd, Clone, Copy)]
+pub struct Color {
+    /// The color space of the components.
+    pub space: ColorSpace,
+    /// The three components of the color.
+    pub values: [f64; 3],
+    /// The alpha component of the color, if present, in the same units as the other components.
+    pub alpha: Option<f64>,
+}
+
+impl Color {
+    /// Creates a new [Color] from the given color space and components.
+    pub fn new(space: ColorSpace, values: [f64; 3]) -> Color {
+        Color {
+            space,
+            values,
+            alpha: None,
+        }
+    }
+
+    /// Returns this color with the given alpha component.
+    pub fn with_alpha(mut self, alpha: f64) -> Color {
+        self.alpha = Some(alpha);
+        self
+    }
+
+    /// Creates a [Color] from a string of six or eight hex digits, optionally prefixed with `0x`.
+    pub fn from_hex(hex: &str) -> Option<Color> {
+        let digits = hex
+            .strip_prefix("0x")
+            .or_else(|| hex.strip_prefix("0X"))
+            .unwrap_or(hex);
+        if !(digits.len() == 6 || digits.len() == 8) || !digits.is_ascii() {
+            return None;
+        }
+
+        let mut channels = [0.0; 4];
+        for (i, channel) in channels.iter_mut().take(digits.len() / 2).enumerate() {
+            *channel = u8::from_str_radix(&digits[i * 2..i * 2 + 2], 16).ok()? as f64;
+        }
+
+        let color = Color::new(ColorSpace::Hex, [channels[0], channels[1], channels[2]]);
+        Some(if digits.len() == 8 {
+            color.with_alpha(channels[3])
+        } else {
+            color
+        })
+    }
+
+    /// Converts this color to red, green, and blue components from 0 to 255.
+    pub fn to_rgb(&self) -> [u8; 3] {
+        self.normalized_rgb()
+            .map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8)
+    }
+
+    /// Converts this color to hue, saturation, and value components from 0 to 1.
+    pub fn to_hsv(&self) -> [f64; 3] {
+        let [h, s, v] = self.values;
+        match self.space {
+            ColorSpace::Hsv => self.values,
+            ColorSpace::Hsv360 => [h / 360.0, s / 100.0, v / 100.0],
+            ColorSpace::Rgb | ColorSpace::Hex => rgb_to_hsv(self.normalized_rgb()),
+        }
+    }
+
+    /// Converts this color to red, green, and blue components from 0 to 1.
+    fn normalized_rgb(&self) -> [f64; 3] {
+        match self.space {
+            ColorSpace::Rgb
+                if self.values.iter().all(|c| (0.0..=1.0).contains(c))
+                    && self.values.iter().any(|c| c.fract() != 0.0) =>
+            {
+                self.values
+            }
+            ColorSpace::Rgb | ColorSpace::Hex => self.values.map(|c| c / 255.0),
+            ColorSpace::Hsv | ColorSpace::Hsv360 => hsv_to_rgb(self.to_hsv()),
+        }
+    }
+}
+
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.space == ColorSpace::Hex {
+            let [r, g, b] = self.values.map(|c| c as u8);
+            write!(f, "hex {{ {:02x}{:02x}{:02x}", r, g, b)?;
+            if let Some(alpha) = self.alpha {
+                write!(f, "{:02x}", alpha as u8)?;
+            }
+            return f.write_str(" }");
+        }
+
+        let [a, b, c] = self.values;
+        write!(f, "{} {{ {} {} {}", self.space, a, b, c)?;
+        if let Some(alpha) = self.alpha {
+            write!(f, " {}", alpha)?;
+        }
+        f.write_str(" }")
+    }
+}
+
+/// Converts normalized HSV components to normalized RGB components.
+fn hsv_to_rgb([h, s, v]: [f64; 3]) -> [f64; 3] {
+    let h = h.rem_euclid(1.0) * 6.0;
+    let f = h.fract();
+    let p = v * (1.0 - s);
+    let q = v * (1.0 - s * f);
+    let t = v * (1.0 - s * (1.0 - f));
+
+    match h as u8 {
+        0 => [v, t, p],
+        1 => [q, v, p],
+        2 => [p, v, t],
+        3 => [p, q, v],
+        4 => [t, p, v],
+        _ => [v, p, q],
+    }
+}
+
+/// Converts normalized RGB components to normalized HSV components.
+fn rgb_to_hsv([r, g, b]: [f64; 3]) -> [f64; 3] {
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let delta = max - min;
+
+    let h = if delta == 0.0 {
+        0.0
+    } else if max == r {
+        ((g - b) / delta).rem_euclid(6.0) / 6.0
+    } else if max == g {
+        ((b - r) / delta + 2.0) / 6.0
+    } else {
+        ((r - g) / delta + 4.0) / 6.0
+    };
+    let s = if max == 0.0 { 0.0 } else { delta / max };
+
+    [h, s, max]
+}
+
+/// The name that [Deserializer](`crate::de::Deserializer`) uses to recognize a [Color].
+#[cfg(feature = "serde")]
+pub(crate) const COLOR_STRUCT_NAME: &str = "$clauser::Color";
+
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for Color {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct ColorVisitor;
+
+        impl ColorVisitor {
+            fn with_components<E: de::Error>(
+                self,
+                space: ColorSpace,
+                values: &[f64],
+            ) -> Result<Color, E> {
+                match *values {
+                    [a, b, c] => Ok(Color::new(space, [a, b, c])),
+                    [a, b, c, alpha] => Ok(Color::new(space, [a, b, c]).with_alpha(alpha)),
+                    _ => Err(E::invalid_length(values.len(), &self)),
+                }
+            }
+        }
+
+        impl<'de> Visitor<'de> for ColorVisitor {
+            type Value = Color;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a color with three or four components")
+            }
+
+            fn visit_seq<A>(self, mut seq: A) -> Result<Color, A::Error>
+            where
+                A: SeqAccess<'de>,
+            {
+                let mut values = Vec::with_capacity(4);
+                while let Some(value) = seq.next_element::<f64>()? {
+                    values.push(value);
+                }
+                self.with_components(ColorSpace::Rgb, &values)
+            }
+
+            fn visit_map<A>(self, mut map: A) -> Result<Color, A::Error>
+            where
+                A: MapAccess<'de>,
+            {
+                let mut space = None;
+                let mut values: Option<Vec<f64>> = None;
+                while let Some(key) = map.next_key::<String>()? {
+                    match key.as_str() {
+                        "space" => space = Some(map.next_value()?),
+                        "values" => values = Some(map.next_value()?),
+                        _ => return Err(de::Error::unknown_field(&key, COLOR_FIELDS)),
+                    }
+                }
+                let space = space.ok_or_else(|| de::Error::missing_field("space"))?;
+                let values = values.ok_or_else(|| de::Error::missing_field("values"))?;
+                self.with_components(space, &values)
+            }
+        }
+
+        deserializer.deserialize_struct(COLOR_STRUCT_NAME, COLOR_FIELDS, ColorVisitor)
+    }
+}
+
+/// The fields of a [Color] as seen by a deserializer.
+#[cfg(feature = "serde")]
+pub(crate) const COLOR_FIELDS: &[&str] = &["space", "values"];
+
 /// A value specifying years, months, days, and possibly hours.
 #[derive(
     Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, FromZeroes, FromBytes, AsBytes, Hash,
@@ -84,6 +555,17 @@ impl Date {
     }
 }
 
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.years, self.months, self.days)?;
+        if self.hours != 0 {
+            write!(f, ".{}", self.hours)?;
+        }
+
+        Ok(())
+    }
+}
+
 impl From<[u32; 4]> for Date {
     fn from(value: [u32; 4]) -> Self {
         Date {
@@ -129,6 +611,49 @@ pub enum ObjectKey<'src> {
     Identifier(Cow<'src, str>),
     /// A Date key
     Date(Date),
+    /// An integer key, such as a province ID
+    Integer(i64),
+    /// A quoted string key
+    String(Cow<'src, str>),
+    /// A `yes` or `no` key
+    Boolean(bool),
+}
+
+impl<'src> ObjectKey<'src> {
+    /// Converts this key into one that owns its strings.
+    pub fn into_owned(self) -> ObjectKey<'static> {
+        match self {
+            ObjectKey::Identifier(s) => ObjectKey::Identifier(Cow::Owned(s.into_owned())),
+            ObjectKey::String(s) => ObjectKey::String(Cow::Owned(s.into_owned())),
+            ObjectKey::Date(d) => ObjectKey::Date(d),
+            ObjectKey::Integer(i) => ObjectKey::Integer(i),
+            ObjectKey::Boolean(b) => ObjectKey::Boolean(b),
+        }
+    }
+}
+
+impl<'src> fmt::Display for ObjectKey<'src> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ObjectKey::Identifier(s) | ObjectKey::String(s) => f.write_str(s),
+            ObjectKey::Date(d) => d.fmt(f),
+            ObjectKey::Integer(i) => i.fmt(f),
+            ObjectKey::Boolean(true) => f.write_str("yes"),
+            ObjectKey::Boolean(false) => f.write_str("no"),
+        }
+    }
+}
+
+impl<'src> From<i64> for ObjectKey<'src> {
+    fn from(value: i64) -> Self {
+        ObjectKey::Integer(value)
+    }
+}
+
+impl<'src> From<bool> for ObjectKey<'src> {
+    fn from(value: bool) -> Self {
+        ObjectKey::Boolean(value)
+    }
 }
 
 impl<'src> From<Date> for ObjectKey<'src> {
@@ -143,6 +668,12 @@ impl<'src> From<&'src str> for ObjectKey<'src> {
     }
 }
 
+impl<'src> From<Cow<'src, str>> for ObjectKey<'src> {
+    fn from(value: Cow<'src, str>) -> Self {
+        ObjectKey::Identifier(value)
+    }
+}
+
 impl<'src> From<String> for ObjectKey<'src> {
     fn from(value: String) -> Self {
         ObjectKey::Identifier(value.into())
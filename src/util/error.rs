@@ -1,4 +1,7 @@
-use std::fmt::{self, Debug, Display, Write};
+use std::{
+    fmt::{self, Debug, Display, Write},
+    path::{Path, PathBuf},
+};
 
 use pad::PadStr;
 
@@ -9,7 +12,8 @@ use serde::{
     Serialize,
 };
 
-use super::text_helpers::{count_tabs_before, CharHelper, StringBuilder};
+use super::text_helpers::{count_tabs_before, StringBuilder};
+use crate::source::{LineIndex, SourceId, Span};
 
 const ERROR_CONTEXT_MAX_LINES: usize = 5;
 
@@ -68,6 +72,22 @@ pub enum ErrorType {
     MissingField,
     /// More than one of the same field was present in the input.
     DuplicateField,
+    /// The input couldn't be decoded using the requested text encoding.
+    EncodingError,
+    /// The input uses syntax that isn't supported by the [Dialect](`crate::dialect::Dialect`) being read.
+    DialectError,
+    /// A scripted variable was referenced without being defined.
+    UndefinedVariableError,
+    /// An inline math expression couldn't be parsed or evaluated.
+    ExpressionError,
+    /// A template couldn't be parsed, or was expanded without a required parameter.
+    ParameterError,
+    /// The input couldn't be read from a stream.
+    IoError,
+    /// The input exceeded one of the [Limits](`crate::limits::Limits`) of the reader.
+    LimitError,
+    /// The header of a save file is missing or malformed.
+    SaveHeaderError,
 }
 
 pub struct ErrorContext {
@@ -82,36 +102,63 @@ impl ErrorContext {
         ErrorContext { lines, location }
     }
 
-    pub fn from_chars(
+    /// Creates a new [ErrorContext] for the byte offset `position` in `text`, using the
+    /// text's [LineIndex] to find the lines around it.
+    pub fn from_line_index(
         text: &str,
-        chars: &Vec<char>,
+        line_index: &LineIndex,
         position: usize,
         max_lines: usize,
     ) -> ErrorContext {
-        let helper = CharHelper(chars);
+        let location = line_index.line_col(text, position);
+        let first_line = usize::max(location.0.saturating_sub(max_lines), 1);
 
-        let lines = helper
-            .find_line_and_context(position, max_lines)
-            .iter()
-            .map(|(start, len)| (&text[*start..(*start + *len)]).to_owned())
+        let lines = (first_line..=location.0)
+            .filter_map(|line| line_index.line_range(text, line))
+            .map(|(start, end)| text[start..end].to_owned())
             .collect();
 
-        let location = helper.position_to_line_col(position);
-
         ErrorContext { lines, location }
     }
+
+    /// Moves the location of this context for text that starts partway through a file, at the
+    /// given line and column.
+    pub(crate) fn starting_at(mut self, line: usize, col: usize) -> ErrorContext {
+        if self.location.0 == 1 {
+            self.location.1 += col - 1;
+        }
+
+        self.location.0 += line - 1;
+        self
+    }
 }
 
 pub trait ErrorContextProvider {
     /// Returns the line that the given position is on as well as up to `max_lines` previous lines.
     fn get_line_context(&self, position: usize, max_lines: usize) -> Option<ErrorContext>;
+
+    /// Returns the [SourceId] of the text that positions refer to.
+    fn source_id(&self) -> SourceId {
+        SourceId::UNKNOWN
+    }
+
+    /// Returns the path of the file the text was read from, if known.
+    fn path(&self) -> Option<&Path> {
+        None
+    }
 }
 
 pub struct Error {
     pub error_type: ErrorType,
     pub position: Option<usize>,
+    /// The range of source text this error refers to, if known.
+    ///
+    /// The span starts at `position`, and is empty unless the error refers to a specific token.
+    pub span: Option<Span>,
     pub message: String,
-    context: Option<ErrorContext>,
+    /// The path of the file this error was encountered in, if known.
+    pub path: Option<PathBuf>,
+    context: Option<Box<ErrorContext>>,
 }
 
 impl Error {
@@ -121,11 +168,29 @@ impl Error {
         position: usize,
         message: impl ToString,
     ) -> Error {
+        let source = context.map_or(SourceId::UNKNOWN, |p| p.source_id());
+
+        Error {
+            error_type,
+            position: Some(position),
+            span: Some(Span::at(source, position)),
+            message: message.to_string(),
+            path: context.and_then(|p| p.path()).map(Path::to_path_buf),
+            context: context
+                .and_then(|p| p.get_line_context(position, ERROR_CONTEXT_MAX_LINES))
+                .map(Box::new),
+        }
+    }
+
+    /// Creates a new [Error] at the given position, without any line context.
+    pub fn new_positioned(error_type: ErrorType, position: usize, message: impl ToString) -> Error {
         Error {
             error_type,
             position: Some(position),
+            span: Some(Span::at(SourceId::UNKNOWN, position)),
             message: message.to_string(),
-            context: context.and_then(|p| p.get_line_context(position, ERROR_CONTEXT_MAX_LINES)),
+            path: None,
+            context: None,
         }
     }
 
@@ -133,7 +198,9 @@ impl Error {
         Error {
             error_type,
             position: None,
+            span: None,
             message: message.to_string(),
+            path: None,
             context: None,
         }
     }
@@ -142,24 +209,62 @@ impl Error {
         Error {
             error_type: self.error_type.clone(),
             position: Some(position),
-            context: context.get_line_context(position, ERROR_CONTEXT_MAX_LINES),
+            span: Some(Span::at(context.source_id(), position)),
+            context: context
+                .get_line_context(position, ERROR_CONTEXT_MAX_LINES)
+                .map(Box::new),
             message: self.message.clone(),
+            path: context
+                .path()
+                .map(Path::to_path_buf)
+                .or_else(|| self.path.clone()),
+        }
+    }
+
+    /// Sets the path of the file this error was encountered in.
+    pub fn with_path(mut self, path: impl Into<PathBuf>) -> Error {
+        self.path = Some(path.into());
+        self
+    }
+
+    /// Extends this error's span to end at the byte offset `end`.
+    pub fn with_end(mut self, end: usize) -> Error {
+        if let Some(span) = &mut self.span {
+            span.end = usize::max(span.start, end);
         }
+
+        self
     }
 
     /// Does this error currently have a `context` provided?
     pub fn has_context(&self) -> bool {
         self.context.is_some()
     }
+
+    /// Returns the (line, column) pair this error was encountered at, if known.
+    ///
+    /// Lines and columns start at 1, and columns are counted in characters.
+    pub fn location(&self) -> Option<(usize, usize)> {
+        self.context.as_ref().map(|c| c.location)
+    }
+
+    /// Describes the file this error was encountered in, if known, for error messages.
+    fn file_description(&self) -> String {
+        match &self.path {
+            Some(path) => format!(" in {}", path.display()),
+            None => String::new(),
+        }
+    }
 }
 
 impl Debug for Error {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let file = self.file_description();
         if self.position.is_none() {
             return write!(
                 f,
-                "ErrorType::{:?} encountered at an unknown position: {}",
-                self.error_type, self.message
+                "ErrorType::{:?} encountered{} at an unknown position: {}",
+                self.error_type, file, self.message
             );
         }
 
@@ -212,15 +317,15 @@ impl Debug for Error {
 
             return write!(
                 f,
-                "ErrorType::{:?} encountered at line {} column {}: {}",
-                self.error_type, line, col, self.message
+                "ErrorType::{:?} encountered{} at line {} column {}: {}",
+                self.error_type, file, line, col, self.message
             );
         }
 
         write!(
             f,
-            "ErrorType::{:?} encountered at index {}: {}",
-            self.error_type, position, self.message
+            "ErrorType::{:?} encountered{} at index {}: {}",
+            self.error_type, file, position, self.message
         )
     }
 }
@@ -229,7 +334,8 @@ impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(
             f,
-            "Clauser parse error at position {}: {}",
+            "Clauser parse error{} at position {}: {}",
+            self.file_description(),
             self.position
                 .and_then(|p| Some(p.to_string()))
                 .unwrap_or(String::from("unknown")),
@@ -244,14 +350,23 @@ impl serde::Serialize for Error {
     where
         S: ser::Serializer,
     {
-        let prop_count: usize =
-            2 + self.position.map_or(0, |_| 1) + self.context.as_ref().map_or(0, |_| 2);
+        let prop_count: usize = 2
+            + self.position.map_or(0, |_| 1)
+            + self.span.map_or(0, |_| 1)
+            + self.path.as_ref().map_or(0, |_| 1)
+            + self.context.as_ref().map_or(0, |_| 2);
         let mut map = serializer.serialize_map(Some(prop_count))?;
         map.serialize_entry("error_type", &self.error_type)?;
         map.serialize_entry("message", &self.message)?;
         if let Some(pos) = self.position {
             map.serialize_entry("index", &pos)?;
         }
+        if let Some(span) = &self.span {
+            map.serialize_entry("span", span)?;
+        }
+        if let Some(path) = &self.path {
+            map.serialize_entry("path", &path.display().to_string())?;
+        }
         if let Some(context) = &self.context {
             map.serialize_entry("context", &context.lines)?;
             map.serialize_entry("location", &context.location)?;
@@ -0,0 +1,119 @@
+use std::{borrow::Cow, fs::File, io::Read, ops::Deref, path::Path};
+
+use memmap2::Mmap;
+
+use crate::{
+    encoding::{self, Encoding},
+    util::error::{Error, ErrorType},
+};
+
+/// Files smaller than this are read into memory instead of being mapped, since mapping
+/// a small file is slower than reading it.
+const MIN_MAP_SIZE: u64 = 64 * 1024;
+
+/// The text a [Tokenizer](`crate::tokenizer::Tokenizer`) reads from.
+pub enum Text<'a> {
+    /// Text borrowed from the caller.
+    Borrowed(&'a str),
+    /// Text owned by the tokenizer, such as transcoded or streamed input.
+    Owned(String),
+    /// A memory-mapped file, which was checked to be valid UTF-8 from `start` onwards
+    /// when it was mapped, and which the creator of the map promised won't change.
+    Mapped { map: Mmap, start: usize },
+}
+
+impl<'a> Text<'a> {
+    /// Returns the text as a mutable string, copying it if it isn't owned.
+    pub fn to_mut(&mut self) -> &mut String {
+        if !matches!(self, Text::Owned(_)) {
+            *self = Text::Owned(self.to_string());
+        }
+
+        match self {
+            Text::Owned(text) => text,
+            _ => unreachable!("text was made owned above"),
+        }
+    }
+}
+
+impl<'a> Deref for Text<'a> {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        match self {
+            Text::Borrowed(text) => text,
+            Text::Owned(text) => text,
+            // safety: the mapped bytes were checked to be valid UTF-8 when the file was mapped,
+            // and the caller of `map` guaranteed that the file won't change while it's mapped
+            Text::Mapped { map, start } => unsafe { std::str::from_utf8_unchecked(&map[*start..]) },
+        }
+    }
+}
+
+impl<'a> From<Cow<'a, str>> for Text<'a> {
+    fn from(text: Cow<'a, str>) -> Self {
+        match text {
+            Cow::Borrowed(text) => Text::Borrowed(text),
+            Cow::Owned(text) => Text::Owned(text),
+        }
+    }
+}
+
+/// Reads the file at `path` into memory in the given [Encoding].
+pub fn read(path: &Path, encoding: Encoding) -> Result<Text<'static>, Error> {
+    let mut file = File::open(path).map_err(|e| io_error(path, e))?;
+    read_file(path, &mut file, encoding)
+}
+
+/// Reads the file at `path` in the given [Encoding], mapping it into memory if it's a regular file
+/// that's large enough and doesn't have to be transcoded.
+///
+/// Anything else, such as a pipe, or a file on a system that can't map it, is read into memory.
+///
+/// # Safety
+///
+/// The file mustn't be modified or truncated while the returned [Text] is alive. See
+/// [Tokenizer::from_path_mapped](`crate::tokenizer::Tokenizer::from_path_mapped`).
+pub unsafe fn map(path: &Path, encoding: Encoding) -> Result<Text<'static>, Error> {
+    let mut file = File::open(path).map_err(|e| io_error(path, e))?;
+    let metadata = file.metadata().map_err(|e| io_error(path, e))?;
+
+    if metadata.is_file() && metadata.len() >= MIN_MAP_SIZE {
+        // safety: the caller guarantees the file isn't changed while the map is alive
+        if let Ok(map) = unsafe { Mmap::map(&file) } {
+            let start = match encoding::decode(&map, encoding)? {
+                Cow::Borrowed(text) => map.len() - text.len(),
+                Cow::Owned(text) => return Ok(Text::Owned(text)),
+            };
+
+            return Ok(Text::Mapped { map, start });
+        }
+    }
+
+    read_file(path, &mut file, encoding)
+}
+
+/// Reads the rest of an open file into memory in the given [Encoding].
+fn read_file(path: &Path, file: &mut File, encoding: Encoding) -> Result<Text<'static>, Error> {
+    let mut bytes = Vec::new();
+    file.read_to_end(&mut bytes)
+        .map_err(|e| io_error(path, e))?;
+
+    let start = match encoding::decode(&bytes, encoding)? {
+        Cow::Borrowed(text) => bytes.len() - text.len(),
+        Cow::Owned(text) => return Ok(Text::Owned(text)),
+    };
+
+    // the text is valid UTF-8 after any byte order mark, so the bytes don't need to be copied
+    bytes.drain(..start);
+    Ok(Text::Owned(
+        String::from_utf8(bytes).expect("text was checked to be valid UTF-8"),
+    ))
+}
+
+fn io_error(path: &Path, error: std::io::Error) -> Error {
+    Error::new_unanchored(
+        ErrorType::IoError,
+        format!("failed to read {}: {}", path.display(), error),
+    )
+}
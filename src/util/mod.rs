@@ -1,2 +1,5 @@
 pub mod error;
+pub mod file;
+pub mod scan;
+pub mod stream;
 pub mod text_helpers;
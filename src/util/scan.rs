@@ -0,0 +1,131 @@
+//! Byte-level scanning helpers used by the [Tokenizer](`crate::tokenizer::Tokenizer`).
+//!
+//! Runs of whitespace and identifier characters are classified with a lookup table, only decoding
+//! UTF-8 when a non-ASCII byte is found. Comments and strings are scanned with `memchr`, which
+//! searches many bytes at a time.
+//!
+//! Every character the tokenizer treats specially is ASCII, and no byte of a multi-byte UTF-8
+//! sequence is ASCII, so searching the bytes can never stop in the middle of a character.
+
+use memchr::{memchr, memchr2};
+
+/// The byte is ASCII whitespace, as defined by [char::is_whitespace].
+const WHITESPACE: u8 = 1 << 0;
+/// The byte can appear after the first character of an identifier.
+///
+/// Besides alphanumerics and `_`, identifiers can contain characters used by event IDs (`flavor_fra.100`),
+/// scope chains (`scope:actor.primary_title`), file paths (`gfx/interface/icon.dds`), and the like.
+const IDENTIFIER: u8 = 1 << 1;
+/// The byte can start an identifier.
+const IDENTIFIER_START: u8 = 1 << 2;
+
+/// The character classes of every byte. Non-ASCII bytes have no classes, and have to be decoded.
+static CLASSES: [u8; 256] = build_classes();
+
+const fn build_classes() -> [u8; 256] {
+    let mut classes = [0; 256];
+    let mut b = 0;
+    while b < 128 {
+        let c = b as u8;
+        let mut class = 0;
+
+        if matches!(c, b'\t' | b'\n' | 0x0b | 0x0c | b'\r' | b' ') {
+            class |= WHITESPACE;
+        }
+
+        if c.is_ascii_alphabetic() || c == b'_' {
+            class |= IDENTIFIER | IDENTIFIER_START;
+        } else if c.is_ascii_digit()
+            || matches!(c, b':' | b'.' | b'/' | b'@' | b'\'' | b'-' | b'|' | b'%')
+        {
+            class |= IDENTIFIER;
+        }
+
+        classes[b] = class;
+        b += 1;
+    }
+
+    classes
+}
+
+/// Returns the character starting at the byte offset `position` of `text`, which must be a char boundary.
+#[inline]
+fn decode_at(text: &str, position: usize) -> Option<char> {
+    text.get(position..).and_then(|s| s.chars().next())
+}
+
+/// Returns the position of the first character at or after `position` that isn't whitespace.
+#[inline]
+pub fn skip_whitespace(text: &str, mut position: usize) -> usize {
+    let bytes = text.as_bytes();
+    while let Some(&b) = bytes.get(position) {
+        if CLASSES[b as usize] & WHITESPACE != 0 {
+            position += 1;
+        } else if b.is_ascii() {
+            break;
+        } else {
+            match decode_at(text, position) {
+                Some(c) if c.is_whitespace() => position += c.len_utf8(),
+                _ => break,
+            }
+        }
+    }
+
+    position
+}
+
+/// Returns the end of the run of identifier characters starting at `position`.
+///
+/// If `parameters` is true, `$` is also accepted, for identifiers like `k_$TITLE$`.
+#[inline]
+pub fn identifier_end(text: &str, mut position: usize, parameters: bool) -> usize {
+    let bytes = text.as_bytes();
+    while let Some(&b) = bytes.get(position) {
+        if CLASSES[b as usize] & IDENTIFIER != 0 || (b == b'$' && parameters) {
+            position += 1;
+        } else if b.is_ascii() {
+            break;
+        } else {
+            match decode_at(text, position) {
+                Some(c) if c.is_alphanumeric() => position += c.len_utf8(),
+                _ => break,
+            }
+        }
+    }
+
+    position
+}
+
+/// Returns true if `c` can start an identifier.
+#[inline]
+pub fn is_identifier_start(c: char) -> bool {
+    match c.is_ascii() {
+        true => CLASSES[c as usize] & IDENTIFIER_START != 0,
+        false => c.is_alphabetic(),
+    }
+}
+
+/// Returns the position of the new line at or after `position`, or the end of `bytes` if there isn't one.
+#[inline]
+pub fn line_end(bytes: &[u8], position: usize) -> usize {
+    match memchr(b'\n', &bytes[position..]) {
+        Some(offset) => position + offset,
+        None => bytes.len(),
+    }
+}
+
+/// Returns the position of the quote closing a string whose contents start at `position`,
+/// skipping over escaped characters.
+#[inline]
+pub fn string_end(bytes: &[u8], mut position: usize) -> Option<usize> {
+    while position < bytes.len() {
+        let offset = memchr2(b'"', b'\\', &bytes[position..])?;
+        match bytes[position + offset] {
+            // skip over whatever character is being escaped
+            b'\\' => position += offset + 2,
+            _ => return Some(position + offset),
+        }
+    }
+
+    None
+}
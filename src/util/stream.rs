@@ -0,0 +1,148 @@
+use std::io::{ErrorKind, Read};
+
+use memchr::{memchr_iter, memrchr};
+
+use crate::{
+    encoding::{decode_windows_1252, Encoding, UTF8_BOM},
+    util::error::{Error, ErrorType},
+};
+
+/// The number of bytes read from a stream at a time, unless changed with
+/// [Tokenizer::with_buffer_size](`crate::tokenizer::Tokenizer::with_buffer_size`).
+pub const DEFAULT_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Reads the input of a streaming [Tokenizer](`crate::tokenizer::Tokenizer`) and decodes it
+/// into text a chunk at a time.
+pub struct Stream {
+    read: Box<dyn Read + Send>,
+    /// The encoding of the input, which is detected from the first chunk if it's [Encoding::Detect].
+    encoding: Encoding,
+    /// Bytes that have been read but not decoded yet, such as a UTF-8 sequence split between chunks.
+    pending: Vec<u8>,
+    /// Whether the start of the input has been decoded.
+    started: bool,
+    /// Whether the whole input has been read.
+    pub eof: bool,
+    /// How many bytes are read at a time, and how many are kept buffered before the current token.
+    pub buffer_size: usize,
+    /// The line and column of the first character of the buffered text, both starting at 1.
+    pub location: (usize, usize),
+}
+
+impl Stream {
+    pub fn new(read: impl Read + Send + 'static, encoding: Encoding) -> Stream {
+        Stream {
+            read: Box::new(read),
+            encoding,
+            pending: Vec::new(),
+            started: false,
+            eof: false,
+            buffer_size: DEFAULT_BUFFER_SIZE,
+            location: (1, 1),
+        }
+    }
+
+    /// Reads at least `min_len` more bytes, unless the input ends first, and appends them to `text`.
+    ///
+    /// `position` is the position of the end of `text` in the whole input, which is used for errors.
+    pub fn fill(
+        &mut self,
+        text: &mut String,
+        min_len: usize,
+        position: usize,
+    ) -> Result<(), Error> {
+        let mut len = self.pending.len();
+        let target = len + min_len;
+        self.pending.resize(target, 0);
+
+        while len < target && !self.eof {
+            match self.read.read(&mut self.pending[len..]) {
+                Ok(0) => self.eof = true,
+                Ok(read) => len += read,
+                Err(e) if e.kind() == ErrorKind::Interrupted => (),
+                Err(e) => {
+                    self.pending.truncate(len);
+                    return Err(Error::new_positioned(
+                        ErrorType::IoError,
+                        position,
+                        format!("failed to read input: {}", e),
+                    ));
+                }
+            }
+        }
+
+        self.pending.truncate(len);
+        self.decode(text, position)
+    }
+
+    /// Decodes as much of the pending input as possible into `text`.
+    fn decode(&mut self, text: &mut String, position: usize) -> Result<(), Error> {
+        if !self.started {
+            // wait until there's enough input to tell if it starts with a byte order mark
+            if !self.eof && UTF8_BOM.starts_with(&self.pending) {
+                return Ok(());
+            }
+
+            self.started = true;
+            if self.encoding == Encoding::Detect {
+                self.encoding = detect_start(&self.pending, self.eof);
+            }
+
+            if self.encoding == Encoding::Utf8 && self.pending.starts_with(UTF8_BOM) {
+                self.pending.drain(..UTF8_BOM.len());
+            }
+        }
+
+        if self.encoding == Encoding::Windows1252 {
+            text.push_str(&decode_windows_1252(&self.pending));
+            self.pending.clear();
+            return Ok(());
+        }
+
+        let decoded = match std::str::from_utf8(&self.pending) {
+            Ok(decoded) => decoded,
+            // a sequence split between chunks is finished by the next one
+            Err(e) if e.error_len().is_none() && !self.eof => {
+                std::str::from_utf8(&self.pending[..e.valid_up_to()]).unwrap()
+            }
+            Err(e) => {
+                let position = position + e.valid_up_to();
+                return Err(Error::new_positioned(
+                    ErrorType::EncodingError,
+                    position,
+                    format!("invalid UTF-8 sequence at byte {}", position),
+                ));
+            }
+        };
+
+        let len = decoded.len();
+        text.push_str(decoded);
+        self.pending.drain(..len);
+        Ok(())
+    }
+
+    /// Moves [Stream::location] past `text`, which was removed from the start of the buffered text.
+    pub fn discard(&mut self, text: &str) {
+        let bytes = text.as_bytes();
+        // count the characters by counting every byte that isn't a UTF-8 continuation byte
+        let chars = |bytes: &[u8]| bytes.iter().filter(|b| (**b & 0xC0) != 0x80).count();
+
+        match memrchr(b'\n', bytes) {
+            Some(last) => {
+                self.location.0 += memchr_iter(b'\n', bytes).count();
+                self.location.1 = 1 + chars(&bytes[last + 1..]);
+            }
+            None => self.location.1 += chars(bytes),
+        }
+    }
+}
+
+/// Detects the encoding of a stream from its first chunk, which might end partway through
+/// a UTF-8 sequence.
+fn detect_start(bytes: &[u8], eof: bool) -> Encoding {
+    match std::str::from_utf8(bytes) {
+        Ok(_) => Encoding::Utf8,
+        Err(e) if e.error_len().is_none() && !eof => Encoding::Utf8,
+        Err(_) => Encoding::detect(bytes),
+    }
+}
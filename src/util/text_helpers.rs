@@ -1,104 +1,41 @@
-pub struct CharHelper<'a>(pub &'a Vec<char>);
+use std::borrow::Cow;
 
-const NEW_LINE: char = '\n';
+/// Helper for finding lines and positions within a source string.
+///
+/// All positions are byte offsets into the string. Lines and columns are found with
+/// [LineIndex](`crate::source::LineIndex`) instead.
+pub struct CharHelper<'a>(pub &'a str);
+
+const NEW_LINE: u8 = b'\n';
 const TAB: char = '\t';
 
 impl<'a> CharHelper<'a> {
-    /// Find the index of the first character of this line.
-    /// If `position` points to a line break, it will be counted as the end of the previous line,
-    /// rather than the start of a new one.
-    pub fn find_line_start(&self, position: usize) -> usize {
-        let mut current_pos = usize::min(position, self.0.len() - 1);
-
-        if self.0[current_pos] == NEW_LINE {
-            if current_pos == 0 {
-                return current_pos;
-            }
-
-            current_pos = current_pos - 1;
-        }
-
-        loop {
-            if self.0[current_pos] == NEW_LINE {
-                return current_pos + 1;
-            }
-
-            if current_pos == 0 {
-                break;
-            }
-
-            current_pos = current_pos - 1;
+    /// Clamps `position` to the bounds of the text and moves it back to the start
+    /// of the character it points into.
+    pub fn clamp_position(&self, position: usize) -> usize {
+        let mut position = usize::min(position, self.0.len().saturating_sub(1));
+        while !self.0.is_char_boundary(position) {
+            position -= 1;
         }
 
-        0
+        position
     }
 
     /// Find the index of the last character of the line, usually the line break.
     pub fn find_line_end(&self, position: usize) -> usize {
-        let mut current_pos = position;
-        while current_pos < self.0.len() {
-            if self.0[current_pos] == NEW_LINE {
-                return current_pos;
-            }
-
-            current_pos = current_pos + 1;
-        }
-
-        self.0.len() - 1
-    }
-
-    /// Returns the bounds of up to `max_lines` before the line pointed to by `position`.
-    ///
-    /// `position` here is assumed to be the index of the first character of the line
-    ///  following the lines to be obtained.
-    ///
-    /// The return value is a vector of tuples denoting (first_char_index, length) for each line.
-    ///
-    pub fn find_lines_before(&self, position: usize, max_lines: usize) -> Vec<(usize, usize)> {
-        let mut lines = Vec::with_capacity(max_lines);
-        let mut last_line_start = position;
-
-        while last_line_start > 0 && (max_lines - lines.len()) > 0 {
-            let new_start = self.find_line_start(last_line_start - 1);
-            lines.push((new_start, last_line_start - new_start));
-            last_line_start = new_start;
+        let bytes = self.0.as_bytes();
+        match bytes.get(position..) {
+            Some(rest) => match rest.iter().position(|b| *b == NEW_LINE) {
+                Some(offset) => position + offset,
+                None => self.last_char_start(),
+            },
+            None => self.last_char_start(),
         }
-
-        // need to reverse since we read the lines backwards
-        lines.reverse();
-        lines
-    }
-
-    /// Like [CharHelper::find_lines_before], this method returns the bounds of up to `max_lines`
-    /// before the line pointed to by `position`. Unlike [CharHelper::find_lines_before], though, this
-    /// method also returns the line containing `position` as the last element in the vec.
-    ///
-    /// `position` can be any index between the start and end of the line.
-    pub fn find_line_and_context(&self, position: usize, max_lines: usize) -> Vec<(usize, usize)> {
-        let this_start = self.find_line_start(position);
-        let this_end = self.find_line_end(position);
-
-        let mut lines = self.find_lines_before(this_start, max_lines);
-        lines.push((this_start, this_end - this_start + 1));
-        lines
     }
 
-    /// Turns an index into a (line, col) pair.
-    pub fn position_to_line_col(&self, position: usize) -> (usize, usize) {
-        let mut current_pos: usize = 0;
-        let mut current_line: usize = 1;
-        let mut last_line_start: usize = 0;
-
-        while current_pos < position {
-            if self.0[current_pos] == NEW_LINE {
-                current_line = current_line + 1;
-                last_line_start = current_pos + 1;
-            }
-
-            current_pos = current_pos + 1;
-        }
-
-        (current_line, current_pos - last_line_start + 1)
+    /// Returns the byte index of the start of the last character in the text.
+    fn last_char_start(&self) -> usize {
+        self.0.char_indices().next_back().map_or(0, |(i, _)| i)
     }
 }
 
@@ -150,3 +87,37 @@ pub fn count_tabs_before(str: &String, end_index: usize) -> usize {
         .filter(|c| c == &TAB)
         .count()
 }
+
+/// Replaces the escape sequences `\"`, `\\`, `\n` and `\t` in the contents of a quoted string.
+///
+/// Unrecognized escape sequences are left as-is. The string is only copied if it contains
+/// an escape sequence.
+pub fn unescape(str: Cow<'_, str>) -> Cow<'_, str> {
+    if !str.contains('\\') {
+        return str;
+    }
+
+    let mut result = String::with_capacity(str.len());
+    let mut chars = str.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('"') => result.push('"'),
+            Some('\\') => result.push('\\'),
+            Some('n') => result.push('\n'),
+            Some('t') => result.push('\t'),
+            Some(other) => {
+                result.push('\\');
+                result.push(other);
+            }
+            None => result.push('\\'),
+        }
+    }
+
+    Cow::Owned(result)
+}
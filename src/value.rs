@@ -1,21 +1,53 @@
 use std::borrow::Cow;
+use std::cmp::Ordering;
 use std::fmt::Debug;
+use std::mem;
+use std::path::Path;
 
-use crate::types::ObjectKey;
+use crate::template::Template;
+use crate::types::{Color, ObjectKey, Operator};
 use crate::{
+    comments::Comments,
+    de::capped,
+    encoding::Encoding,
     error::Error,
-    reader::Reader,
+    reader::{Checkpoint, Entry, ParseMode, Reader},
     types::Date,
     types::{CollectionType, RealType},
 };
 
 /// A single (key, value) entry in an object value.
+///
+/// Entries using an operator other than `=` have their value wrapped in [Value::Comparison].
 pub type ObjectEntry<'src> = (ObjectKey<'src>, Value<'src>);
 
 /// An object value containing (key, value) pairs.
 /// Duplicate keys are allowed.
 pub type ObjectMap<'src> = Vec<ObjectEntry<'src>>;
 
+/// An entry of a [Value::Mixed] collection.
+#[derive(Debug, PartialEq, PartialOrd, Clone)]
+pub enum MixedEntry<'src> {
+    /// A value without a key, like the `1` in `{ 1 a = b }`.
+    Item(Value<'src>),
+    /// A key-value pair, like the `a = b` in `{ 1 a = b }`.
+    ///
+    /// Like in [ObjectEntry], values using an operator other than `=` are wrapped in [Value::Comparison].
+    Property(ObjectKey<'src>, Value<'src>),
+}
+
+impl<'src> MixedEntry<'src> {
+    /// Converts this entry into one that owns all of its strings.
+    pub fn into_owned(self) -> MixedEntry<'static> {
+        match self {
+            MixedEntry::Item(value) => MixedEntry::Item(value.into_owned()),
+            MixedEntry::Property(key, value) => {
+                MixedEntry::Property(key.into_owned(), value.into_owned())
+            }
+        }
+    }
+}
+
 /// A variant that represents a Clausewitz source file as a tree of types and values.
 /// [from_reader](`Value::from_reader`) and [from_str](`Value::from_str`) can be used to deserialize
 /// a Clausewitz source file into a Value tree.
@@ -25,7 +57,13 @@ pub type ObjectMap<'src> = Vec<ObjectEntry<'src>>;
 /// It also makes no guarantees about the validity of the data, as long as it's parseable.
 ///
 /// You should only use [Value] for situations where the schema of the data can't be known beforehand.
-#[derive(Debug, PartialEq, PartialOrd, Clone)]
+///
+/// Dropping a value and formatting it with [Debug] recurse once for every nested collection, so
+/// values are never read with collections nested more than [MAX_DEPTH](`crate::de::MAX_DEPTH`) deep, whatever
+/// [Limits](`crate::limits::Limits`) are set. Values are cloned, compared, and converted with
+/// [Value::into_owned] without recursion, so values nested deeper than that can still be built
+/// by hand, but they should be dropped with [Value::drop_iteratively] and never formatted.
+#[derive(Debug)]
 pub enum Value<'src> {
     None,
     Integer(i64),
@@ -36,77 +74,465 @@ pub enum Value<'src> {
     Identifier(Cow<'src, str>),
     Object(ObjectMap<'src>),
     Array(Vec<Value<'src>>),
+    /// A collection containing both key-value pairs and bare values, like `{ 1 2 3 a = b }`,
+    /// with its entries in the order they appear.
+    ///
+    /// Collections are only read as [Value::Mixed] if they contain both kinds of entries.
+    Mixed(Vec<MixedEntry<'src>>),
+    /// A color block with a color space prefix, such as `rgb { 255 128 0 }`.
+    Color(Color),
+    /// A block containing parameters (`$PARAM$`) or conditional blocks (`[[PARAM] ... ]`),
+    /// such as the body of a scripted effect.
+    Template(Template<'src>),
+    /// The value of a property that used an [Operator] other than `=`, such as `age > 30`.
+    Comparison(Operator, Box<Value<'src>>),
 }
 
 impl<'reader, 'src: 'reader> Value<'src> {
-    /// Recursively reads a source file from a [Reader] into a [Value].
+    /// Reads a source file from a [Reader] into a [Value].
+    ///
+    /// If the [Reader] is in [ParseMode::Lenient], values that can't be read are replaced
+    /// with [Value::None] or skipped, and the errors are left in [Reader::diagnostics].
+    ///
+    /// Collections nested more than [MAX_DEPTH](`crate::de::MAX_DEPTH`) deep are rejected with a
+    /// [LimitError](`crate::error::ErrorType::LimitError`), even if the reader's
+    /// [Limits](`crate::limits::Limits`) allow them. Untrusted input should still be read with
+    /// limits, to bound the size of the tree.
     pub fn from_reader(reader: &'reader mut Reader<'src>) -> Result<Value<'src>, Error> {
-        let mut values = Vec::new();
+        Ok(Self::read(reader, true, false)?.0)
+    }
 
-        while let Some((name, _)) = reader.next_property()? {
-            values.push((name.into(), Self::next_from_reader(reader)?))
-        }
+    /// Reads a source file from a [Reader] into a [Value], along with the [Comments]
+    /// attached to each of its entries.
+    ///
+    /// The [Reader] must have comments enabled with [Reader::with_comments], or no comments
+    /// will be found.
+    pub fn from_reader_with_comments(
+        reader: &'reader mut Reader<'src>,
+    ) -> Result<(Value<'src>, Comments<'src>), Error> {
+        let (value, comments) = Self::read(reader, true, true)?;
+        Ok((value, comments.unwrap_or_default()))
+    }
 
-        return Ok(Self::Object(values));
+    /// Attaches the comments read since `start` to the entries of a collection that's been read.
+    fn attach_comments(
+        reader: &mut Reader<'src>,
+        comments: &mut Comments<'src>,
+        start: usize,
+        entry_ends: &[usize],
+    ) {
+        // the comments of nested collections have already been taken, and the reader
+        // hasn't read past the end of this collection
+        let found = reader.take_comments_in(start, reader.current_position());
+        comments.attach(found, entry_ends, |end, position| {
+            !reader.new_line_between(end, position)
+        });
     }
 
-    /// Recursively reads the next available value from a [Reader] into a [Value].
+    /// Reads the next available value from a [Reader] into a [Value].
+    ///
+    /// Like [Value::from_reader], nested collections are read without recursion.
     pub fn next_from_reader(reader: &'reader mut Reader<'src>) -> Result<Value<'src>, Error> {
+        Ok(Self::read(reader, false, false)?.0)
+    }
+
+    /// Reads the next value from a [Reader], or the properties at the root of the input if `root`
+    /// is true, along with the comments attached to its entries if `comments` is true.
+    ///
+    /// The reader's [Limits::max_depth](`crate::limits::Limits::max_depth`) is lowered to
+    /// [MAX_DEPTH](`crate::de::MAX_DEPTH`) while the value is read, since dropping and formatting values recurses.
+    fn read(
+        reader: &mut Reader<'src>,
+        root: bool,
+        comments: bool,
+    ) -> Result<(Value<'src>, Option<Comments<'src>>), Error> {
+        let limits = *reader.limits();
+        reader.set_limits(capped(limits));
+        let result = Self::read_capped(reader, root, comments);
+        reader.set_limits(limits);
+        result
+    }
+
+    /// Reads a value like [Value::read], once the reader's limits have been capped.
+    ///
+    /// The collections being read are kept on a stack of [Frame]s. Reading a value either
+    /// finishes it right away, or opens a collection and pushes a frame for its entries, which is
+    /// popped and added to the collection below it once the collection ends.
+    ///
+    /// If the [Reader] detects templates, a [Checkpoint] is kept at the start of the outermost
+    /// block being read. When a parameter is found anywhere inside it, its frames are dropped and
+    /// the block is read again as a [Value::Template].
+    fn read_capped(
+        reader: &mut Reader<'src>,
+        root: bool,
+        comments: bool,
+    ) -> Result<(Value<'src>, Option<Comments<'src>>), Error> {
+        // the number of frames below the outermost block
+        let outer = root as usize;
+        let mut template_start = None;
+        let mut stack = Vec::new();
+        match root {
+            true => stack.push(Frame::new(reader, None, comments)),
+            false => {
+                template_start = Self::template_start(reader);
+                match Self::read_start(reader) {
+                    Ok(Start::Value(value)) => return Ok((value, None)),
+                    Ok(Start::Collection(collection_type)) => {
+                        stack.push(Frame::new(reader, Some(collection_type), comments))
+                    }
+                    Err(e) => {
+                        return Self::read_template_again(reader, &mut template_start)
+                            .unwrap_or(Err(e))
+                    }
+                }
+            }
+        }
+
+        loop {
+            let outside_blocks = stack.len() == outer;
+            let frame = stack.last_mut().expect("the stack is never empty");
+            let entry = match frame.collection_type {
+                // the root of the input only contains properties
+                None => reader.next_property_with_operator().map(|property| {
+                    property.map(|(info, operator)| Entry::Property(info, operator))
+                }),
+                Some(collection_type) => reader.next_collection_entry(collection_type),
+            };
+
+            let result = match (
+                Self::read_template_again(reader, &mut template_start),
+                entry,
+            ) {
+                (Some(template), _) => {
+                    stack.truncate(outer);
+                    template
+                }
+                (None, Ok(Some(entry))) => {
+                    frame.entry = Some(entry);
+                    if outside_blocks {
+                        template_start = Self::template_start(reader);
+                    }
+
+                    match Self::read_start(reader) {
+                        Ok(Start::Value(value)) => {
+                            frame.add(reader, Ok((value, None)))?;
+                            continue;
+                        }
+                        Ok(Start::Collection(collection_type)) => {
+                            stack.push(Frame::new(reader, Some(collection_type), comments));
+                            continue;
+                        }
+                        Err(e) => {
+                            let result = Self::read_template_again(reader, &mut template_start);
+                            frame.add(reader, result.unwrap_or(Err(e)))?;
+                            continue;
+                        }
+                    }
+                }
+                (None, Ok(None)) => stack.pop().unwrap().end(reader),
+                // errors that weren't recovered from end the collection, like they would
+                // end a recursive call
+                (None, Err(e)) => {
+                    stack.pop();
+                    Err(e)
+                }
+            };
+
+            if stack.len() == outer {
+                template_start = None;
+            }
+
+            match stack.last_mut() {
+                Some(parent) => parent.add(reader, result)?,
+                None => return result,
+            }
+        }
+    }
+
+    /// Saves the position of the next value if it's a block that might turn out to be a template.
+    fn template_start(reader: &mut Reader<'src>) -> Option<Checkpoint> {
+        if !reader.detects_templates() {
+            return None;
+        }
+
+        // parameters found before the block don't make it a template
+        reader.take_parameter();
+        matches!(reader.peek_next_type(), Ok(Some(RealType::ObjectOrArray)))
+            .then(|| reader.checkpoint())
+    }
+
+    /// Reads the block starting at `template_start` again as a [Value::Template], if a parameter
+    /// has been found since it started.
+    fn read_template_again(
+        reader: &mut Reader<'src>,
+        template_start: &mut Option<Checkpoint>,
+    ) -> Option<Result<(Value<'src>, Option<Comments<'src>>), Error>> {
+        if !reader.take_parameter() {
+            return None;
+        }
+
+        reader.restore(template_start.take()?);
+        Some(
+            reader
+                .read_template()
+                .map(|template| (Self::Template(template), None)),
+        )
+    }
+
+    /// Reads the start of the next value from a [Reader], which is the whole value unless
+    /// it's an object or array.
+    fn read_start(reader: &mut Reader<'src>) -> Result<Start<'src>, Error> {
         let next = reader.peek_next_type()?;
 
         if next.is_none() {
-            return Ok(Self::None);
+            return Ok(Start::Value(Self::None));
         }
 
         let next = next.unwrap();
 
-        match next {
-            RealType::Boolean => Ok(Self::Boolean(reader.read_boolean()?)),
-            RealType::Identifier => Ok(Self::Identifier(reader.read_identifier()?.into())),
-            RealType::String => Ok(Self::String(reader.read_string()?.into())),
-            RealType::Number => Ok({
+        Ok(Start::Value(match next {
+            RealType::Boolean => Self::Boolean(reader.read_boolean()?),
+            RealType::Identifier => Self::Identifier(reader.read_identifier()?),
+            RealType::String => Self::String(reader.read_string()?),
+            RealType::Number => {
                 let number = reader.read_number_as_str()?;
                 match number.contains(".") {
-                    true => Self::Decimal(reader.parse_number(number)?),
-                    false => Self::Integer(reader.parse_number(number)?),
+                    true => Self::Decimal(reader.parse_number(&number)?),
+                    false => Self::Integer(reader.parse_number(&number)?),
                 }
-            }),
-            RealType::Date => Ok(Self::Date(reader.read_date()?)),
-            RealType::ObjectOrArray => {
-                let collection_type = reader.try_discern_array_or_map()?;
-
-                if collection_type.is_none() {
-                    return Ok(Self::None);
+            }
+            RealType::Date => Self::Date(reader.read_date()?),
+            RealType::Color => Self::Color(reader.read_color()?),
+            RealType::ObjectOrArray => match reader.try_discern_array_or_map()? {
+                Some(collection_type) => {
+                    reader.begin_collection()?;
+                    return Ok(Start::Collection(collection_type));
                 }
+                None => Self::None,
+            },
+        }))
+    }
 
-                match collection_type.unwrap() {
-                    CollectionType::Array => {
-                        reader.begin_collection()?;
+    /// Creates a [Value::Object] if every entry is a property, a [Value::Array] if every entry
+    /// is a bare value, and a [Value::Mixed] otherwise. No entries make an empty array.
+    pub fn from_entries(entries: Vec<MixedEntry<'src>>) -> Value<'src> {
+        let properties = entries
+            .iter()
+            .filter(|entry| matches!(entry, MixedEntry::Property(..)))
+            .count();
 
-                        let mut values = Vec::new();
-                        while !reader.is_collection_ended()? {
-                            values.push(Self::next_from_reader(reader)?)
-                        }
+        match properties {
+            0 => Self::Array(
+                entries
+                    .into_iter()
+                    .filter_map(|entry| match entry {
+                        MixedEntry::Item(value) => Some(value),
+                        MixedEntry::Property(..) => None,
+                    })
+                    .collect(),
+            ),
+            n if n == entries.len() => Self::Object(
+                entries
+                    .into_iter()
+                    .filter_map(|entry| match entry {
+                        MixedEntry::Property(key, value) => Some((key, value)),
+                        MixedEntry::Item(_) => None,
+                    })
+                    .collect(),
+            ),
+            _ => Self::Mixed(entries),
+        }
+    }
 
-                        reader.end_collection()?;
+    /// Returns the kind of collection this value is, or `None` if it isn't a collection.
+    pub fn collection_type(&self) -> Option<CollectionType> {
+        match self {
+            Self::Object(_) => Some(CollectionType::Object),
+            Self::Array(_) => Some(CollectionType::Array),
+            Self::Mixed(_) => Some(CollectionType::Mixed),
+            _ => None,
+        }
+    }
 
-                        Ok(Self::Array(values))
-                    }
-                    CollectionType::Object => {
-                        reader.begin_collection()?;
+    /// Drops this value without recursion.
+    ///
+    /// Dropping a value normally recurses once for every nested collection, which can overflow
+    /// the stack for deeply nested values built by hand. Values read from input are never nested
+    /// more than [MAX_DEPTH](`crate::de::MAX_DEPTH`) deep.
+    pub fn drop_iteratively(mut self) {
+        // move the children of nested collections onto a stack before they're dropped, so
+        // each value is empty by the time it's dropped
+        let mut stack = Vec::new();
+        self.take_children(&mut stack);
+        while let Some(mut value) = stack.pop() {
+            value.take_children(&mut stack);
+        }
+    }
 
-                        let mut values = Vec::new();
+    /// Returns the position of this value's variant in the declaration of [Value], which orders
+    /// values of different variants.
+    fn variant_index(&self) -> usize {
+        match self {
+            Value::None => 0,
+            Value::Integer(_) => 1,
+            Value::Decimal(_) => 2,
+            Value::Boolean(_) => 3,
+            Value::Date(_) => 4,
+            Value::String(_) => 5,
+            Value::Identifier(_) => 6,
+            Value::Object(_) => 7,
+            Value::Array(_) => 8,
+            Value::Mixed(_) => 9,
+            Value::Color(_) => 10,
+            Value::Template(_) => 11,
+            Value::Comparison(..) => 12,
+        }
+    }
 
-                        while let Some((name, _)) = reader.next_property()? {
-                            values.push((name.into(), Self::next_from_reader(reader)?));
+    /// Converts this value into one that owns all of its strings.
+    pub fn into_owned(self) -> Value<'static> {
+        rebuild(self, Value::split_owned)
+    }
+
+    /// Splits this value into an owned copy of it whose children are [Value::None], and the
+    /// children themselves, for [rebuild].
+    fn split_owned(self) -> (Value<'static>, Vec<Value<'src>>) {
+        let mut children = Vec::new();
+        let shell = match self {
+            Value::None => Value::None,
+            Value::Integer(i) => Value::Integer(i),
+            Value::Decimal(d) => Value::Decimal(d),
+            Value::Boolean(b) => Value::Boolean(b),
+            Value::Date(d) => Value::Date(d),
+            Value::String(s) => Value::String(Cow::Owned(s.into_owned())),
+            Value::Identifier(s) => Value::Identifier(Cow::Owned(s.into_owned())),
+            Value::Object(entries) => Value::Object(
+                entries
+                    .into_iter()
+                    .map(|(key, value)| {
+                        children.push(value);
+                        (key.into_owned(), Value::None)
+                    })
+                    .collect(),
+            ),
+            Value::Array(values) => {
+                children = values;
+                Value::Array(Vec::new())
+            }
+            Value::Mixed(entries) => Value::Mixed(
+                entries
+                    .into_iter()
+                    .map(|entry| match entry {
+                        MixedEntry::Item(value) => {
+                            children.push(value);
+                            MixedEntry::Item(Value::None)
+                        }
+                        MixedEntry::Property(key, value) => {
+                            children.push(value);
+                            MixedEntry::Property(key.into_owned(), Value::None)
+                        }
+                    })
+                    .collect(),
+            ),
+            Value::Color(c) => Value::Color(c),
+            Value::Template(t) => Value::Template(t.into_owned()),
+            Value::Comparison(operator, value) => {
+                children.push(*value);
+                Value::Comparison(operator, Box::new(Value::None))
+            }
+        };
+
+        (shell, children)
+    }
+
+    /// Splits this value into a copy of it whose children are [Value::None], and references
+    /// to the children themselves, for [rebuild].
+    fn split_ref(&self) -> (Value<'src>, Vec<&Value<'src>>) {
+        let mut children = Vec::new();
+        let shell = match self {
+            Value::None => Value::None,
+            Value::Integer(i) => Value::Integer(*i),
+            Value::Decimal(d) => Value::Decimal(*d),
+            Value::Boolean(b) => Value::Boolean(*b),
+            Value::Date(d) => Value::Date(*d),
+            Value::String(s) => Value::String(s.clone()),
+            Value::Identifier(s) => Value::Identifier(s.clone()),
+            Value::Object(entries) => Value::Object(
+                entries
+                    .iter()
+                    .map(|(key, value)| {
+                        children.push(value);
+                        (key.clone(), Value::None)
+                    })
+                    .collect(),
+            ),
+            Value::Array(values) => {
+                children.extend(values);
+                Value::Array(Vec::new())
+            }
+            Value::Mixed(entries) => Value::Mixed(
+                entries
+                    .iter()
+                    .map(|entry| match entry {
+                        MixedEntry::Item(value) => {
+                            children.push(value);
+                            MixedEntry::Item(Value::None)
                         }
+                        MixedEntry::Property(key, value) => {
+                            children.push(value);
+                            MixedEntry::Property(key.clone(), Value::None)
+                        }
+                    })
+                    .collect(),
+            ),
+            Value::Color(c) => Value::Color(*c),
+            Value::Template(t) => Value::Template(t.clone()),
+            Value::Comparison(operator, value) => {
+                children.push(value);
+                Value::Comparison(*operator, Box::new(Value::None))
+            }
+        };
 
-                        reader.end_collection()?;
+        (shell, children)
+    }
 
-                        Ok(Self::Object(values))
+    /// Replaces the [Value::None] children of a value made by [Value::split_owned] or
+    /// [Value::split_ref] with the rebuilt children, in order.
+    fn fill(&mut self, children: Vec<Value<'src>>) {
+        let mut children = children.into_iter();
+        match self {
+            Value::Object(entries) => {
+                for ((_, slot), child) in entries.iter_mut().zip(&mut children) {
+                    *slot = child;
+                }
+            }
+            Value::Array(values) => *values = children.collect(),
+            Value::Mixed(entries) => {
+                for (entry, child) in entries.iter_mut().zip(&mut children) {
+                    match entry {
+                        MixedEntry::Item(slot) | MixedEntry::Property(_, slot) => *slot = child,
                     }
                 }
             }
+            Value::Comparison(_, slot) => {
+                if let Some(child) = children.next() {
+                    **slot = child;
+                }
+            }
+            _ => (),
+        }
+    }
+
+    /// Moves the children of this value onto `stack`, leaving its collections empty.
+    fn take_children(&mut self, stack: &mut Vec<Value<'src>>) {
+        match self {
+            Value::Object(entries) => stack.extend(entries.drain(..).map(|(_, value)| value)),
+            Value::Array(values) => stack.append(values),
+            Value::Mixed(entries) => stack.extend(entries.drain(..).map(|entry| match entry {
+                MixedEntry::Item(value) | MixedEntry::Property(_, value) => value,
+            })),
+            Value::Comparison(_, value) => stack.push(mem::replace(&mut **value, Value::None)),
+            _ => (),
         }
     }
 
@@ -115,4 +541,348 @@ impl<'reader, 'src: 'reader> Value<'src> {
         let mut reader = Reader::new(s);
         Value::from_reader(&mut reader)
     }
+
+    /// Parses the given string into a [Value], along with the [Comments] attached to its entries.
+    pub fn from_str_with_comments(s: &'src str) -> Result<(Value<'src>, Comments<'src>), Error> {
+        let mut reader = Reader::new(s).with_comments(true);
+        Value::from_reader_with_comments(&mut reader)
+    }
+
+    /// Parses the given string into a [Value] in [ParseMode::Lenient], returning the value
+    /// along with every error that was recovered from.
+    pub fn from_str_lenient(s: &'src str) -> (Value<'src>, Vec<Error>) {
+        let mut reader = Reader::new(s).with_mode(ParseMode::Lenient);
+        let value = Value::from_reader(&mut reader);
+        let mut diagnostics = reader.take_diagnostics();
+
+        match value {
+            Ok(value) => (value, diagnostics),
+            Err(e) => {
+                diagnostics.push(e);
+                (Value::None, diagnostics)
+            }
+        }
+    }
+
+    /// Parses raw bytes in the given [Encoding] into a [Value].
+    ///
+    /// Strings are borrowed from `bytes` if it doesn't need to be transcoded.
+    pub fn from_bytes(bytes: &'src [u8], encoding: Encoding) -> Result<Value<'src>, Error> {
+        let mut reader = Reader::from_bytes(bytes, encoding)?;
+        Value::from_reader(&mut reader)
+    }
+
+    /// Parses the file at `path` in the given [Encoding] into a [Value].
+    ///
+    /// The path is recorded in any [Error]. See
+    /// [Tokenizer::from_path](`crate::tokenizer::Tokenizer::from_path`) for more information.
+    pub fn from_path(path: impl AsRef<Path>, encoding: Encoding) -> Result<Value<'static>, Error> {
+        let path = path.as_ref();
+        let mut reader = Reader::from_path(path, encoding)?;
+        Value::from_reader(&mut reader).map_err(|e| e.with_path(path))
+    }
+}
+
+impl<'src> Clone for Value<'src> {
+    fn clone(&self) -> Self {
+        rebuild(self, Value::split_ref)
+    }
+}
+
+impl<'src> PartialEq for Value<'src> {
+    fn eq(&self, other: &Self) -> bool {
+        // pairs of values that still need to be compared, so nested values are compared without recursion
+        let mut stack = vec![(self, other)];
+
+        while let Some(pair) = stack.pop() {
+            let equal = match pair {
+                (Value::None, Value::None) => true,
+                (Value::Integer(a), Value::Integer(b)) => a == b,
+                (Value::Decimal(a), Value::Decimal(b)) => a == b,
+                (Value::Boolean(a), Value::Boolean(b)) => a == b,
+                (Value::Date(a), Value::Date(b)) => a == b,
+                (Value::String(a), Value::String(b)) => a == b,
+                (Value::Identifier(a), Value::Identifier(b)) => a == b,
+                (Value::Object(a), Value::Object(b)) => {
+                    a.len() == b.len()
+                        && a.iter().zip(b).all(|((key_a, a), (key_b, b))| {
+                            stack.push((a, b));
+                            key_a == key_b
+                        })
+                }
+                (Value::Array(a), Value::Array(b)) => {
+                    stack.extend(a.iter().zip(b));
+                    a.len() == b.len()
+                }
+                (Value::Mixed(a), Value::Mixed(b)) => {
+                    a.len() == b.len()
+                        && a.iter().zip(b).all(|entries| match entries {
+                            (MixedEntry::Item(a), MixedEntry::Item(b)) => {
+                                stack.push((a, b));
+                                true
+                            }
+                            (MixedEntry::Property(key_a, a), MixedEntry::Property(key_b, b)) => {
+                                stack.push((a, b));
+                                key_a == key_b
+                            }
+                            _ => false,
+                        })
+                }
+                (Value::Color(a), Value::Color(b)) => a == b,
+                (Value::Template(a), Value::Template(b)) => a == b,
+                (Value::Comparison(operator_a, a), Value::Comparison(operator_b, b)) => {
+                    stack.push((a, b));
+                    operator_a == operator_b
+                }
+                _ => false,
+            };
+
+            if !equal {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+impl<'src> PartialOrd for Value<'src> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        // comparisons left to make, in reverse order, so nested values are compared without
+        // recursion. Collections are ordered like slices, comparing their entries in order and
+        // then their lengths.
+        let mut stack = vec![Compare::Values(self, other)];
+
+        while let Some(compare) = stack.pop() {
+            let ordering = match compare {
+                Compare::Done(ordering) => ordering,
+                Compare::Values(a, b) => match (a, b) {
+                    (Value::None, Value::None) => Some(Ordering::Equal),
+                    (Value::Integer(a), Value::Integer(b)) => a.partial_cmp(b),
+                    (Value::Decimal(a), Value::Decimal(b)) => a.partial_cmp(b),
+                    (Value::Boolean(a), Value::Boolean(b)) => a.partial_cmp(b),
+                    (Value::Date(a), Value::Date(b)) => a.partial_cmp(b),
+                    (Value::String(a), Value::String(b)) => a.partial_cmp(b),
+                    (Value::Identifier(a), Value::Identifier(b)) => a.partial_cmp(b),
+                    (Value::Object(a), Value::Object(b)) => {
+                        stack.push(Compare::Done(a.len().partial_cmp(&b.len())));
+                        for ((key_a, a), (key_b, b)) in a.iter().zip(b).rev() {
+                            stack.push(Compare::Values(a, b));
+                            stack.push(Compare::Done(key_a.partial_cmp(key_b)));
+                        }
+                        continue;
+                    }
+                    (Value::Array(a), Value::Array(b)) => {
+                        stack.push(Compare::Done(a.len().partial_cmp(&b.len())));
+                        stack.extend(a.iter().zip(b).rev().map(|(a, b)| Compare::Values(a, b)));
+                        continue;
+                    }
+                    (Value::Mixed(a), Value::Mixed(b)) => {
+                        stack.push(Compare::Done(a.len().partial_cmp(&b.len())));
+                        for entries in a.iter().zip(b).rev() {
+                            match entries {
+                                (MixedEntry::Item(a), MixedEntry::Item(b)) => {
+                                    stack.push(Compare::Values(a, b))
+                                }
+                                (
+                                    MixedEntry::Property(key_a, a),
+                                    MixedEntry::Property(key_b, b),
+                                ) => {
+                                    stack.push(Compare::Values(a, b));
+                                    stack.push(Compare::Done(key_a.partial_cmp(key_b)));
+                                }
+                                // items come before properties
+                                (MixedEntry::Item(_), _) => {
+                                    stack.push(Compare::Done(Some(Ordering::Less)))
+                                }
+                                (_, MixedEntry::Item(_)) => {
+                                    stack.push(Compare::Done(Some(Ordering::Greater)))
+                                }
+                            }
+                        }
+                        continue;
+                    }
+                    (Value::Color(a), Value::Color(b)) => a.partial_cmp(b),
+                    (Value::Template(a), Value::Template(b)) => a.partial_cmp(b),
+                    (Value::Comparison(operator_a, a), Value::Comparison(operator_b, b)) => {
+                        stack.push(Compare::Values(a, b));
+                        operator_a.partial_cmp(operator_b)
+                    }
+                    (a, b) => a.variant_index().partial_cmp(&b.variant_index()),
+                },
+            };
+
+            if ordering != Some(Ordering::Equal) {
+                return ordering;
+            }
+        }
+
+        Some(Ordering::Equal)
+    }
+}
+
+/// A comparison left to make by [Value::partial_cmp].
+enum Compare<'a, 'src> {
+    /// Two values that still need to be compared.
+    Values(&'a Value<'src>, &'a Value<'src>),
+    /// A comparison that's already been made, but comes after the values before it.
+    Done(Option<Ordering>),
+}
+
+/// Rebuilds a tree of values from `root` without recursion, so deeply nested values can't
+/// overflow the stack.
+///
+/// `split` turns a node into a copy of it whose children are [Value::None], along with the nodes
+/// its children are rebuilt from, in order. The rebuilt children are filled in with [Value::fill].
+fn rebuild<'b, N>(root: N, split: impl Fn(N) -> (Value<'b>, Vec<N>)) -> Value<'b> {
+    // each level of the stack holds a value being rebuilt, the children left to rebuild, and
+    // the children rebuilt so far
+    let (value, children) = split(root);
+    let mut stack = vec![(value, children.into_iter(), Vec::new())];
+
+    loop {
+        let (_, pending, rebuilt) = stack.last_mut().expect("the stack is never empty");
+        match pending.next() {
+            Some(child) => {
+                let (value, children) = split(child);
+                match children.is_empty() {
+                    true => rebuilt.push(value),
+                    false => stack.push((value, children.into_iter(), Vec::new())),
+                }
+            }
+            None => {
+                let (mut value, _, rebuilt) = stack.pop().expect("the stack is never empty");
+                value.fill(rebuilt);
+                match stack.last_mut() {
+                    Some((_, _, parent)) => parent.push(value),
+                    None => return value,
+                }
+            }
+        }
+    }
+}
+
+/// The start of a value read by [Value::read_start].
+enum Start<'src> {
+    /// A value that isn't a collection, which has been read in full.
+    Value(Value<'src>),
+    /// A collection whose open bracket has been read, and whose entries come next.
+    Collection(CollectionType),
+}
+
+/// A collection being read by [Value::read], along with the entries read from it so far.
+struct Frame<'src> {
+    /// The kind of collection discerned from its first entry, or `None` for the root of the input.
+    ///
+    /// The collection is read as a [Value::Mixed] if it turns out to contain both properties
+    /// and bare values, whichever kind of collection it was discerned as.
+    collection_type: Option<CollectionType>,
+    entries: Vec<MixedEntry<'src>>,
+    /// The entry whose value is being read.
+    entry: Option<Entry<'src>>,
+    /// The comments attached to the entries of the collection, if they're being kept.
+    comments: Option<Comments<'src>>,
+    /// The end of the last token before the entries of the collection.
+    start: usize,
+    /// The end of each entry, used to attach comments to them.
+    entry_ends: Vec<usize>,
+}
+
+impl<'src> Frame<'src> {
+    fn new(
+        reader: &Reader<'src>,
+        collection_type: Option<CollectionType>,
+        comments: bool,
+    ) -> Frame<'src> {
+        Frame {
+            collection_type,
+            entries: Vec::new(),
+            entry: None,
+            comments: comments.then(Comments::default),
+            start: reader.last_token_end(),
+            entry_ends: Vec::new(),
+        }
+    }
+
+    /// Adds the value of the entry being read, or recovers from the error that kept it from
+    /// being read. Properties whose values can't be read are given [Value::None], and items
+    /// are skipped.
+    fn add(
+        &mut self,
+        reader: &mut Reader<'src>,
+        result: Result<(Value<'src>, Option<Comments<'src>>), Error>,
+    ) -> Result<(), Error> {
+        let entry = self.entry.take().expect("an entry is being read");
+        let (value, entry_comments) = match (result, &entry) {
+            (Ok(value), _) => value,
+            (Err(e), Entry::Property(..)) => {
+                reader.recover(e)?;
+                (Value::None, None)
+            }
+            // skip items that can't be read
+            (Err(e), Entry::Item) => return reader.recover(e),
+        };
+
+        self.entries.push(match entry {
+            Entry::Property((key, _), operator) => MixedEntry::Property(
+                key,
+                match operator {
+                    Operator::Equals => value,
+                    operator => Value::Comparison(operator, Box::new(value)),
+                },
+            ),
+            Entry::Item => MixedEntry::Item(value),
+        });
+
+        if let Some(comments) = self.comments.as_mut() {
+            comments.entries.push(entry_comments.unwrap_or_default());
+            self.entry_ends.push(reader.last_token_end());
+        }
+
+        Ok(())
+    }
+
+    /// Finishes reading the collection once all of its entries have been read.
+    fn end(
+        mut self,
+        reader: &mut Reader<'src>,
+    ) -> Result<(Value<'src>, Option<Comments<'src>>), Error> {
+        if let Some(comments) = self.comments.as_mut() {
+            Value::attach_comments(reader, comments, self.start, &self.entry_ends);
+        }
+
+        if self.collection_type.is_some() {
+            reader.end_collection()?;
+        }
+
+        let entries = mem::take(&mut self.entries);
+        let value = match self.collection_type {
+            // the root is always an object, even if it's empty
+            None => Value::Object(
+                entries
+                    .into_iter()
+                    .filter_map(|entry| match entry {
+                        MixedEntry::Property(key, value) => Some((key, value)),
+                        MixedEntry::Item(_) => None,
+                    })
+                    .collect(),
+            ),
+            Some(_) => Value::from_entries(entries),
+        };
+
+        Ok((value, self.comments.take()))
+    }
+}
+
+impl<'src> Drop for Frame<'src> {
+    fn drop(&mut self) {
+        // frames are dropped when reading fails, and their entries can be deeply nested
+        for entry in self.entries.drain(..) {
+            match entry {
+                MixedEntry::Item(value) | MixedEntry::Property(_, value) => {
+                    value.drop_iteratively()
+                }
+            }
+        }
+    }
 }
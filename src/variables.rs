@@ -0,0 +1,96 @@
+use std::collections::HashMap;
+
+use crate::{error::Error, reader::Reader, token::TokenType, value::Value};
+
+/// The value of a single scripted variable.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Variable {
+    /// The type of the token the variable was defined with.
+    pub token_type: TokenType,
+    /// The text of the variable's value.
+    ///
+    /// Strings are stored without their quotes, with any escape sequences left as-is.
+    pub value: String,
+}
+
+impl Variable {
+    /// Creates a new [Variable] from the given token type and value.
+    pub fn new(token_type: TokenType, value: impl Into<String>) -> Variable {
+        Variable {
+            token_type,
+            value: value.into(),
+        }
+    }
+}
+
+/// A table of scripted variables, such as `@cost = 100`.
+///
+/// A [Reader] adds every variable definition it reads to its table, and substitutes references
+/// to a variable (`@cost`) with the variable's value. Definitions can be shared between files
+/// by reading them into a table first, such as with [Variables::parse], and passing that
+/// table to [Reader::with_variables](`crate::reader::Reader::with_variables`) or
+/// [Deserializer::with_variables](`crate::de::Deserializer::with_variables`).
+///
+/// Variable names are stored without the leading `@`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Variables {
+    values: HashMap<String, Variable>,
+}
+
+impl Variables {
+    /// Creates an empty variable table.
+    pub fn new() -> Variables {
+        Variables::default()
+    }
+
+    /// Collects the variables defined in the given text, such as a file from `common/scripted_variables`.
+    ///
+    /// Properties other than variable definitions are parsed but ignored.
+    pub fn parse(text: &str) -> Result<Variables, Error> {
+        Variables::from_reader(Reader::new(text))
+    }
+
+    /// Collects the variables defined in the text read by the given [Reader], along with
+    /// any variables the [Reader] already knew about.
+    pub fn from_reader(mut reader: Reader) -> Result<Variables, Error> {
+        Value::from_reader(&mut reader)?;
+        Ok(reader.into_variables())
+    }
+
+    /// Returns the variable with the given name, if it's defined.
+    ///
+    /// The name can be given with or without its leading `@`.
+    pub fn get(&self, name: &str) -> Option<&Variable> {
+        self.values.get(name.strip_prefix('@').unwrap_or(name))
+    }
+
+    /// Defines a variable, returning the previous definition with the same name, if any.
+    ///
+    /// The name can be given with or without its leading `@`.
+    pub fn insert(&mut self, name: &str, variable: Variable) -> Option<Variable> {
+        self.values
+            .insert(name.strip_prefix('@').unwrap_or(name).to_owned(), variable)
+    }
+
+    /// Adds every variable from `other` to this table, replacing any existing definitions.
+    pub fn merge(&mut self, other: Variables) {
+        self.values.extend(other.values);
+    }
+
+    /// Returns the number of variables in this table.
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Returns true if this table has no variables.
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Returns an iterator over the names and values of every variable in this table.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &Variable)> {
+        self.values
+            .iter()
+            .map(|(name, value)| (name.as_str(), value))
+    }
+}
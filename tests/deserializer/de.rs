@@ -1,11 +1,18 @@
-use std::{collections::HashMap, fmt::Debug};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Debug,
+};
 
 use super::util::{expect_error, expect_str, SingleContainer};
 
 use clauser::{
-    de::from_str,
+    de::{from_bytes, from_deserializer, from_str, from_str_with_dialect, Deserializer},
+    dialect::Dialect,
+    encoding::Encoding,
     error::{Error, ErrorType},
-    types::Date,
+    template::Template,
+    types::{Color, ColorSpace, Comparison, Date, Operator},
+    variables::Variables,
 };
 use serde::Deserialize;
 
@@ -161,3 +168,466 @@ pub fn dates() -> Result<(), Error> {
 
     Ok(())
 }
+
+#[test]
+pub fn encoded_bytes() -> Result<(), Error> {
+    let source = b"\xEF\xBB\xBFval = { str = \"K\xC3\xB6ln\" }";
+    assert_eq!(
+        from_bytes::<SingleContainer<StringField>>(source, Encoding::Detect)?.val,
+        StringField {
+            str: String::from("Köln")
+        }
+    );
+
+    let source = b"val = { str = \"K\xF6ln\" }";
+    assert_eq!(
+        from_bytes::<SingleContainer<StringField>>(source, Encoding::Windows1252)?.val,
+        StringField {
+            str: String::from("Köln")
+        }
+    );
+
+    Ok(())
+}
+
+#[test]
+pub fn escaped_strings() -> Result<(), Error> {
+    SingleContainer::<String>::expect(
+        "val = \"He said \\\"hi\\\"\"",
+        String::from("He said \"hi\""),
+    )?;
+    SingleContainer::<String>::expect("val = \"a\\\\b\"", String::from("a\\b"))?;
+    SingleContainer::<String>::expect("val = \"a\\nb\\tc\"", String::from("a\nb\tc"))?;
+    SingleContainer::<&str>::expect("val = \"borrowed\"", "borrowed")?;
+
+    Ok(())
+}
+
+#[test]
+pub fn empty_after_operators() -> Result<(), Error> {
+    for operator in ["<", ">", "<=", ">=", "!=", "?="] {
+        expect_str::<MultiStringField>(
+            &format!("str1 {operator}\nstr2 = test\nstr3 {operator}\nstr4 {operator}"),
+            MultiStringField {
+                str1: String::new(),
+                str2: String::from("test"),
+                str3: String::new(),
+                str4: String::new(),
+            },
+        )?;
+        SingleContainer::<StringField>::expect(
+            &format!("val = {{ str {operator} }}"),
+            StringField { str: String::new() },
+        )?;
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+struct Trigger {
+    age: Comparison<i32>,
+    has_trait: Comparison<String>,
+    is_ruler: Comparison<bool>,
+    gold: i32,
+}
+
+#[test]
+pub fn operators() -> Result<(), Error> {
+    SingleContainer::<Trigger>::expect(
+        "val = { age > 30 has_trait ?= brave is_ruler = yes gold <= 100 }",
+        Trigger {
+            age: Comparison::new(Operator::GreaterThan, 30),
+            has_trait: Comparison::new(Operator::ExistenceCheck, String::from("brave")),
+            is_ruler: Comparison::new(Operator::Equals, true),
+            gold: 100,
+        },
+    )?;
+    SingleContainer::<Vec<Comparison<i32>>>::expect(
+        "val != { 1 2 }",
+        vec![
+            Comparison::new(Operator::Equals, 1),
+            Comparison::new(Operator::Equals, 2),
+        ],
+    )?;
+    SingleContainer::<Comparison<Vec<i32>>>::expect(
+        "val != { 1 2 }",
+        Comparison::new(Operator::NotEquals, vec![1, 2]),
+    )?;
+
+    Ok(())
+}
+
+#[test]
+pub fn non_identifier_keys() -> Result<(), Error> {
+    let mut provinces = HashMap::new();
+    provinces.insert(151, String::from("FRA"));
+    provinces.insert(-1, String::from("none"));
+    expect_str::<HashMap<i32, String>>("151 = FRA -1 = none", provinces)?;
+
+    let mut keys = HashMap::new();
+    keys.insert(String::from("my key"), 1);
+    keys.insert(String::from("no"), 2);
+    keys.insert(String::from("yes"), 3);
+    keys.insert(String::from("151"), 4);
+    keys.insert(String::from("ident"), 5);
+    keys.insert(String::from("1444.11.11"), 6);
+    SingleContainer::<HashMap<String, i32>>::expect(
+        "val = { \"my key\" = 1 no = 2 yes = 3 151 = 4 ident = 5 1444.11.11 = 6 }",
+        keys,
+    )?;
+
+    let mut quoted_numbers = HashMap::new();
+    quoted_numbers.insert(10u32, true);
+    SingleContainer::<HashMap<u32, bool>>::expect("val = { \"10\" = yes }", quoted_numbers)?;
+
+    expect_error::<SingleContainer<HashMap<i32, i32>>>(
+        "val = { test = 1 }",
+        ErrorType::InvalidType,
+    )?;
+
+    Ok(())
+}
+
+#[test]
+pub fn dialects() -> Result<(), Error> {
+    assert_eq!(
+        from_str_with_dialect::<SingleContainer<Comparison<String>>>("val ?= brave", Dialect::CK3)?
+            .val,
+        Comparison::new(Operator::ExistenceCheck, String::from("brave"))
+    );
+
+    let result =
+        from_str_with_dialect::<SingleContainer<Comparison<String>>>("val ?= brave", Dialect::EU4);
+    assert_eq!(result.unwrap_err().error_type, ErrorType::DialectError);
+
+    Ok(())
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+struct Culture {
+    pub color: Color,
+    pub rgb: [u8; 3],
+    pub secondary: Option<Color>,
+}
+
+#[test]
+pub fn colors() -> Result<(), Error> {
+    expect_str(
+        "color = hsv { 0.5 0.4 0.9 } rgb = hsv360 { 30 100 100 } secondary = hex { ff8000 }",
+        Culture {
+            color: Color::new(ColorSpace::Hsv, [0.5, 0.4, 0.9]),
+            rgb: [255, 128, 0],
+            secondary: Some(Color::from_hex("ff8000").unwrap()),
+        },
+    )?;
+    expect_str(
+        "color = { 255 128 0 } rgb = { 1 2 3 }",
+        Culture {
+            color: Color::new(ColorSpace::Rgb, [255.0, 128.0, 0.0]),
+            rgb: [1, 2, 3],
+            secondary: None,
+        },
+    )?;
+    SingleContainer::<Vec<Color>>::expect(
+        "val = { rgb { 1 2 3 4 } { 5 6 7 } }",
+        vec![
+            Color::new(ColorSpace::Rgb, [1.0, 2.0, 3.0]).with_alpha(4.0),
+            Color::new(ColorSpace::Rgb, [5.0, 6.0, 7.0]),
+        ],
+    )?;
+
+    expect_error::<SingleContainer<Color>>("val = rgb { 1 2 }", ErrorType::InvalidLength)?;
+
+    let result = from_str_with_dialect::<SingleContainer<Color>>(
+        "val = hsv360 { 30 100 100 }",
+        Dialect::HOI4,
+    );
+    assert!(result.is_err());
+
+    Ok(())
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+struct Building {
+    pub cost: u32,
+    pub upkeep: Option<f32>,
+    pub name: String,
+    pub costs: Vec<i32>,
+}
+
+#[test]
+pub fn variables() -> Result<(), Error> {
+    expect_str(
+        "@cost = 100 @upkeep = 2.5 @name = \"tower\"
+        cost = @cost upkeep = @upkeep name = @name costs = { @cost 5 }",
+        Building {
+            cost: 100,
+            upkeep: Some(2.5),
+            name: String::from("tower"),
+            costs: vec![100, 5],
+        },
+    )?;
+
+    let variables = Variables::parse("@cost = 50 @name = wall")?;
+    let building: Building = from_deserializer(
+        Deserializer::from_str("cost = @cost name = @name costs = {}").with_variables(variables),
+    )?;
+    assert_eq!(
+        building,
+        Building {
+            cost: 50,
+            upkeep: None,
+            name: String::from("wall"),
+            costs: vec![],
+        }
+    );
+
+    expect_error::<SingleContainer<u32>>("val = @missing", ErrorType::UndefinedVariableError)?;
+    expect_error::<SingleContainer<u32>>("@x = no val = @x", ErrorType::UnexpectedTokenError)?;
+
+    Ok(())
+}
+
+#[test]
+pub fn inline_math() -> Result<(), Error> {
+    expect_str(
+        "@cost = 40 cost = @[ cost / 2 ] upkeep = @[ cost * 0.05 ] name = x costs = { @[ -cost ] }",
+        Building {
+            cost: 20,
+            upkeep: Some(2.0),
+            name: String::from("x"),
+            costs: vec![-40],
+        },
+    )?;
+
+    expect_error::<SingleContainer<u32>>("val = @[ 5 / 2 ]", ErrorType::InvalidNumberError)?;
+    expect_error::<SingleContainer<u32>>("val = @[ 5 + ]", ErrorType::ExpressionError)?;
+
+    Ok(())
+}
+
+#[derive(Deserialize, Debug)]
+struct ScriptedEffects<'a> {
+    #[serde(borrow)]
+    pub give_gold: Template<'a>,
+}
+
+#[test]
+pub fn templates() -> Result<(), Error> {
+    let effects: ScriptedEffects =
+        from_str("give_gold = { add_gold = $AMOUNT$ [[!SILENT] send_message = gold ] }")?;
+    assert_eq!(effects.give_gold.parameters(), ["AMOUNT", "SILENT"]);
+
+    let expanded = effects
+        .give_gold
+        .expand(&[("AMOUNT", "25"), ("SILENT", "yes")])?;
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct GiveGold {
+        pub add_gold: u32,
+        pub send_message: Option<String>,
+    }
+
+    expect_str(
+        &expanded,
+        GiveGold {
+            add_gold: 25,
+            send_message: None,
+        },
+    )?;
+
+    let effects: HashMap<String, Template> =
+        from_str("first = { a = $A$ } second = { [[B] b = c ] }")?;
+    assert_eq!(effects["first"].text(), " a = $A$ ");
+    assert_eq!(effects["second"].parameters(), ["B"]);
+
+    Ok(())
+}
+
+#[test]
+pub fn unknown_fields() -> Result<(), Error> {
+    #[derive(Deserialize, Debug)]
+    struct Country {
+        tag: String,
+        gold: i32,
+    }
+
+    // unknown fields are skipped without being read
+    let source = "
+	tag = FRA
+	history = { 1444.11.11 = { owner = FRA } nested = { { } { a = b } } }
+	color = rgb { 1 2 3 }
+	name = \"France\"
+	gold = 20
+	empty =";
+
+    let deserialized = from_str::<Country>(source)?;
+    assert_eq!(deserialized.tag, "FRA");
+    assert_eq!(deserialized.gold, 20);
+
+    expect_error::<Country>(
+        "tag = FRA gold = 1 history = { a = b",
+        ErrorType::UnexpectedTokenError,
+    )?;
+
+    Ok(())
+}
+
+#[test]
+pub fn mixed_items() -> Result<(), Error> {
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Mixed {
+        #[serde(rename = "$items")]
+        items: Vec<i32>,
+        a: String,
+    }
+
+    expect_str(
+        "val = { 1 2 3 a = b }",
+        SingleContainer {
+            val: Mixed {
+                items: vec![1, 2, 3],
+                a: "b".into(),
+            },
+        },
+    )?;
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Flags {
+        key: i32,
+        #[serde(rename = "$items")]
+        flags: Vec<String>,
+        other: Option<i32>,
+    }
+
+    // items can be anywhere in the collection, and there can be none of them
+    expect_str(
+        "val = { flag key = 1 other_flag }",
+        SingleContainer {
+            val: Flags {
+                key: 1,
+                flags: vec!["flag".into(), "other_flag".into()],
+                other: None,
+            },
+        },
+    )?;
+    expect_str(
+        "val = { key = 1 other = 2 }",
+        SingleContainer {
+            val: Flags {
+                key: 1,
+                flags: vec![],
+                other: Some(2),
+            },
+        },
+    )?;
+
+    // as well as at the root of the input
+    let root: Flags = from_str("hidden key = 5 global")?;
+    assert_eq!(root.flags, vec!["hidden", "global"]);
+
+    expect_error::<SingleContainer<Mixed>>("val = { 1 x a = b }", ErrorType::UnexpectedTokenError)?;
+
+    Ok(())
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+#[serde(untagged)]
+enum Any {
+    Integer(i64),
+    String(String),
+    Array(Vec<Any>),
+    Object(BTreeMap<String, Any>),
+}
+
+fn object<const N: usize>(entries: [(&str, Any); N]) -> Any {
+    Any::Object(entries.map(|(k, v)| (k.to_owned(), v)).into())
+}
+
+#[test]
+pub fn mixed_any() -> Result<(), Error> {
+    // self-describing types get the bare values of mixed collections under $items
+    let value: BTreeMap<String, Any> =
+        from_str("a = { 1 2 b = c } d = { e = 1 flag } f = { 1 2 } g = { h = 1 }")?;
+    assert_eq!(
+        value,
+        BTreeMap::from([
+            (
+                "a".into(),
+                object([
+                    ("b", Any::String("c".into())),
+                    ("$items", Any::Array(vec![Any::Integer(1), Any::Integer(2)])),
+                ])
+            ),
+            (
+                "d".into(),
+                object([
+                    ("e", Any::Integer(1)),
+                    ("$items", Any::Array(vec![Any::String("flag".into())])),
+                ])
+            ),
+            (
+                "f".into(),
+                Any::Array(vec![Any::Integer(1), Any::Integer(2)])
+            ),
+            ("g".into(), object([("h", Any::Integer(1))])),
+        ])
+    );
+
+    // nested collections are discerned by their own entries
+    let value: HashMap<String, Any> =
+        from_str("a = { { 1 x = 2 } { 3 } 4 } b = { { 1 y = 2 } c = d }")?;
+    assert_eq!(
+        value["a"],
+        Any::Array(vec![
+            object([
+                ("x", Any::Integer(2)),
+                ("$items", Any::Array(vec![Any::Integer(1)])),
+            ]),
+            Any::Array(vec![Any::Integer(3)]),
+            Any::Integer(4),
+        ])
+    );
+    assert_eq!(
+        value["b"],
+        object([
+            ("c", Any::String("d".into())),
+            (
+                "$items",
+                Any::Array(vec![object([
+                    ("y", Any::Integer(2)),
+                    ("$items", Any::Array(vec![Any::Integer(1)])),
+                ])])
+            ),
+        ])
+    );
+
+    // including when they're nested deeply
+    let depth = 100;
+    let text = format!("a = {}b = c{}", "{ 1 ".repeat(depth), " }".repeat(depth));
+    let value: HashMap<String, Any> = from_str(&text)?;
+    let mut next = &value["a"];
+    for _ in 1..depth {
+        match next {
+            Any::Array(items) => next = &items[1],
+            other => panic!("expected array, found {:?}", other),
+        }
+    }
+    assert_eq!(
+        *next,
+        object([
+            ("b", Any::String("c".into())),
+            ("$items", Any::Array(vec![Any::Integer(1)])),
+        ])
+    );
+
+    let root: HashMap<String, Any> = from_str("hidden value = 5")?;
+    assert_eq!(
+        root["$items"],
+        Any::Array(vec![Any::String("hidden".into())])
+    );
+    assert_eq!(root["value"], Any::Integer(5));
+
+    Ok(())
+}
@@ -1,5 +1,9 @@
 use super::util::{expect_error, SingleContainer};
-use clauser::error::{Error, ErrorType};
+use clauser::{
+    de::from_bytes,
+    encoding::Encoding,
+    error::{Error, ErrorType},
+};
 use clauser_macros::duplicate_keys;
 use trybuild::TestCases;
 
@@ -81,9 +85,30 @@ pub fn empty_duplicate() -> Result<(), Error> {
     Ok(())
 }
 
+#[test]
+pub fn transcoded_keys() -> Result<(), Error> {
+    // keys can't be borrowed from transcoded input
+    let source = b"item = \"K\xF6ln\" unique1 = 1 item = two unique2 = \"M\xFCnchen\"";
+    assert_eq!(
+        from_bytes::<DuplicateAndNormalKeys>(source, Encoding::Windows1252)?,
+        DuplicateAndNormalKeys {
+            item: vec![String::from("Köln"), String::from("two")],
+            unique1: 1,
+            unique2: String::from("München"),
+        }
+    );
+
+    Ok(())
+}
+
 #[test]
 pub fn duplicate_keys_invalid() -> Result<(), Error> {
     expect_error::<SingleContainer<i32>>("val = 1 val = 2 val = 3", ErrorType::DuplicateField)?;
+    expect_error::<DuplicateAndNormalKeys>(
+        "item = one unique1 = 1 unique2 = two unique3 = three",
+        ErrorType::UnknownField,
+    )?;
+    expect_error::<DuplicateAndNormalKeys>("item = one unique1 = 1", ErrorType::MissingField)?;
 
     Ok(())
 }
@@ -0,0 +1,62 @@
+use clauser::{
+    dialect::Dialect,
+    error::{Error, ErrorType},
+    token::TokenType,
+    tokenizer::Tokenizer,
+};
+
+fn token_types(text: &str, dialect: Dialect) -> Result<Vec<TokenType>, Error> {
+    let mut tokenizer = Tokenizer::new(text).with_dialect(dialect);
+    let mut types = Vec::new();
+    while let Some(token) = tokenizer.next()? {
+        types.push(token.token_type);
+    }
+
+    Ok(types)
+}
+
+fn expect_dialect_error(text: &str, dialect: Dialect) {
+    let error = token_types(text, dialect).unwrap_err();
+    assert_eq!(
+        error.error_type,
+        ErrorType::DialectError,
+        "expected dialect error, got {:?}",
+        error
+    );
+    assert!(
+        error.message.contains(dialect.name),
+        "error message '{}' should name the dialect",
+        error.message
+    );
+}
+
+#[test]
+fn existence_check() -> Result<(), Error> {
+    let expected = vec![
+        TokenType::Identifier,
+        TokenType::ExistenceCheck,
+        TokenType::Identifier,
+    ];
+    assert_eq!(token_types("a ?= b", Dialect::CK3)?, expected);
+    assert_eq!(token_types("a ?= b", Dialect::VICTORIA3)?, expected);
+    assert_eq!(token_types("a ?= b", Dialect::default())?, expected);
+
+    expect_dialect_error("a ?= b", Dialect::EU4);
+    expect_dialect_error("a ?= b", Dialect::HOI4);
+
+    Ok(())
+}
+
+#[test]
+fn unsupported_constructs() {
+    expect_dialect_error("@cost = 10", Dialect::EU4);
+    expect_dialect_error("a = @[ 1 + 2 ]", Dialect::HOI4);
+    expect_dialect_error("a = { [[PARAM] b = c ] }", Dialect::EU4);
+    expect_dialect_error("a = { [[PARAM] b = c ] }", Dialect::HOI4);
+
+    let no_parameters = Dialect {
+        parameters: false,
+        ..Dialect::default()
+    };
+    expect_dialect_error("a = $PARAM$", no_parameters);
+}
@@ -0,0 +1,78 @@
+use std::borrow::Cow;
+
+use clauser::{
+    encoding::{decode, Encoding},
+    error::{Error, ErrorType},
+    token::TokenType,
+    tokenizer::Tokenizer,
+    value::Value,
+};
+
+#[test]
+fn utf8() -> Result<(), Error> {
+    let decoded = decode("name = \"Zürich\"".as_bytes(), Encoding::Utf8)?;
+    assert!(matches!(decoded, Cow::Borrowed(_)));
+    assert_eq!(decoded, "name = \"Zürich\"");
+
+    let error = decode(b"name = \"Z\xFCrich\"", Encoding::Utf8).unwrap_err();
+    assert_eq!(error.error_type, ErrorType::EncodingError);
+    assert_eq!(error.position, Some(9));
+
+    Ok(())
+}
+
+#[test]
+fn byte_order_mark() -> Result<(), Error> {
+    let decoded = decode(b"\xEF\xBB\xBFa = b", Encoding::Utf8)?;
+    assert!(matches!(decoded, Cow::Borrowed(_)));
+    assert_eq!(decoded, "a = b");
+
+    let decoded = decode(b"\xEF\xBB\xBFa = \xC3\xA9", Encoding::Detect)?;
+    assert_eq!(decoded, "a = é");
+
+    Ok(())
+}
+
+#[test]
+fn windows_1252() -> Result<(), Error> {
+    let decoded = decode(b"name = \"Z\xFCrich\" \x80 \x9F", Encoding::Windows1252)?;
+    assert!(matches!(decoded, Cow::Owned(_)));
+    assert_eq!(decoded, "name = \"Zürich\" € Ÿ");
+
+    // plain ASCII doesn't need to be copied
+    let decoded = decode(b"a = b", Encoding::Windows1252)?;
+    assert!(matches!(decoded, Cow::Borrowed(_)));
+
+    Ok(())
+}
+
+#[test]
+fn detect() {
+    assert_eq!(Encoding::detect(b"a = b"), Encoding::Utf8);
+    assert_eq!(Encoding::detect("a = ü".as_bytes()), Encoding::Utf8);
+    assert_eq!(Encoding::detect(b"\xEF\xBB\xBFa = b"), Encoding::Utf8);
+    assert_eq!(Encoding::detect(b"a = \xFC"), Encoding::Windows1252);
+}
+
+#[test]
+fn from_bytes() -> Result<(), Error> {
+    let source = b"\xC9tat = { capital = \"Besan\xE7on\" }";
+
+    let mut tokenizer = Tokenizer::from_bytes(source, Encoding::Detect)?;
+    let token = tokenizer.next()?.unwrap();
+    assert_eq!(token.token_type, TokenType::Identifier);
+    assert_eq!(tokenizer.str_for_token(&token), "État");
+
+    assert_eq!(
+        Value::from_bytes(source, Encoding::Windows1252)?,
+        Value::Object(vec![(
+            "État".into(),
+            Value::Object(vec![("capital".into(), Value::String("Besançon".into()))])
+        )])
+    );
+
+    let error = Value::from_bytes(b"\xEF\xBB\xBFa = ?", Encoding::Detect).unwrap_err();
+    assert_eq!(error.location(), Some((1, 5)));
+
+    Ok(())
+}
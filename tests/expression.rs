@@ -0,0 +1,108 @@
+use clauser::{
+    error::{Error, ErrorType},
+    expression::{Expression, MathOperator},
+    token::TokenType,
+    variables::{Variable, Variables},
+};
+
+fn evaluate(text: &str) -> Result<f64, Error> {
+    let mut variables = Variables::new();
+    variables.insert("cost", Variable::new(TokenType::Number, "10"));
+    variables.insert("name", Variable::new(TokenType::Identifier, "tower"));
+
+    Expression::parse(text)?.evaluate(&variables)
+}
+
+fn expect_error(text: &str, expected: ErrorType, position: usize) {
+    let error = evaluate(text).unwrap_err();
+    assert_eq!(error.error_type, expected, "{:?}", error);
+    assert_eq!(error.position, Some(position), "{:?}", error);
+}
+
+#[test]
+fn parse() -> Result<(), Error> {
+    assert_eq!(
+        Expression::parse("cost * 2 + 5")?,
+        Expression::Binary(
+            MathOperator::Add,
+            Box::new(Expression::Binary(
+                MathOperator::Multiply,
+                Box::new(Expression::Variable(String::from("cost"), 0)),
+                Box::new(Expression::Number(2.0)),
+                5,
+            )),
+            Box::new(Expression::Number(5.0)),
+            9,
+        )
+    );
+    assert_eq!(
+        Expression::parse(" -(@a - b) / 2 ")?.to_string(),
+        "(-(a - b) / 2)"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn precedence() -> Result<(), Error> {
+    assert_eq!(evaluate("2 + 3 * 4")?, 14.0);
+    assert_eq!(evaluate("(2 + 3) * 4")?, 20.0);
+    assert_eq!(evaluate("10 - 4 - 3")?, 3.0);
+    assert_eq!(evaluate("100 / 10 / 5")?, 2.0);
+    assert_eq!(evaluate("7 % 4 * 2")?, 6.0);
+    assert_eq!(evaluate("-2 * -3")?, 6.0);
+    assert_eq!(evaluate("cost * 0.5 + @cost")?, 15.0);
+
+    Ok(())
+}
+
+#[test]
+fn errors() {
+    expect_error("2 +", ErrorType::ExpressionError, 3);
+    expect_error("(2 + 3", ErrorType::ExpressionError, 6);
+    expect_error("2 3", ErrorType::ExpressionError, 2);
+    expect_error("2 & 3", ErrorType::ExpressionError, 2);
+    expect_error("1.2.3", ErrorType::InvalidNumberError, 0);
+    expect_error("2 * missing", ErrorType::UndefinedVariableError, 4);
+    expect_error("name + 1", ErrorType::ExpressionError, 0);
+
+    // errors evaluating an operator point at the operator
+    expect_error("cost / (5 - 5)", ErrorType::ExpressionError, 5);
+    expect_error("1 + 7 % 0", ErrorType::ExpressionError, 6);
+}
+
+#[test]
+fn non_finite() {
+    let huge = "9".repeat(400);
+    expect_error(&huge, ErrorType::InvalidNumberError, 0);
+
+    // results that can't be written back out as numbers are rejected
+    for (text, position) in [
+        (format!("{}0 * {}0", "1".repeat(200), "1".repeat(200)), 202),
+        (
+            format!("1{} / 0.{}1", "0".repeat(300), "0".repeat(100)),
+            302,
+        ),
+    ] {
+        expect_error(&text, ErrorType::ExpressionError, position);
+    }
+}
+
+#[test]
+fn complexity() -> Result<(), Error> {
+    let nested = |depth: usize| format!("{}1{}", "(".repeat(depth), ")".repeat(depth));
+    assert_eq!(evaluate(&nested(100))?, 1.0);
+    assert_eq!(evaluate(&vec!["1"; 200].join(" + "))?, 200.0);
+
+    // deeply nested or very long expressions can't overflow the stack
+    for text in [
+        nested(100_000),
+        "-".repeat(100_000) + "1",
+        vec!["1"; 100_000].join(" + "),
+    ] {
+        let error = Expression::parse(&text).unwrap_err();
+        assert_eq!(error.error_type, ErrorType::ExpressionError);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,177 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use clauser::{
+    de::{self, Deserializer},
+    encoding::Encoding,
+    error::{Error, ErrorType},
+    reader::Reader,
+    tokenizer::Tokenizer,
+    value::Value,
+};
+use serde::Deserialize;
+
+/// A file in the temporary directory that's removed when dropped.
+struct TempFile(PathBuf);
+
+impl TempFile {
+    fn new(name: &str, contents: &[u8]) -> TempFile {
+        let path = std::env::temp_dir().join(format!("clauser-{}-{}", std::process::id(), name));
+        fs::write(&path, contents).unwrap();
+        TempFile(path)
+    }
+
+    fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+/// Returns text with `count` properties, large enough to be mapped if `count` is in the thousands.
+fn properties(count: usize) -> String {
+    (0..count)
+        .map(|i| format!("key_{} = {{ name = \"Name {}\" value = {} }}\n", i, i, i))
+        .collect()
+}
+
+fn assert_has_path(error: &Error, path: &Path) {
+    assert_eq!(error.path.as_deref(), Some(path), "{:?}", error);
+    assert!(error.to_string().contains(&path.display().to_string()));
+}
+
+#[test]
+fn small_and_large() -> Result<(), Error> {
+    for count in [3, 5000] {
+        let text = properties(count);
+        let file = TempFile::new(&format!("properties-{}.txt", count), text.as_bytes());
+
+        assert_eq!(
+            Value::from_path(file.path(), Encoding::Utf8)?,
+            Value::from_str(&text)?
+        );
+
+        let mut tokenizer = Tokenizer::from_path(file.path(), Encoding::Utf8)?;
+        assert_eq!(tokenizer.path(), Some(file.path()));
+        assert_eq!(tokenizer.text(), text);
+        assert_eq!(tokenizer.iter().count(), count * 10);
+
+        // safety: nothing else changes the file while it's mapped
+        let mut tokenizer = unsafe { Tokenizer::from_path_mapped(file.path(), Encoding::Utf8)? };
+        assert_eq!(tokenizer.path(), Some(file.path()));
+        assert_eq!(tokenizer.text(), text);
+        assert_eq!(tokenizer.iter().count(), count * 10);
+
+        // safety: as above
+        let tokenizer = unsafe { Tokenizer::from_path_mapped(file.path(), Encoding::Utf8)? };
+        let mut reader = Reader::from_tokenizer(tokenizer);
+        assert_eq!(Value::from_reader(&mut reader)?, Value::from_str(&text)?);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn encodings() -> Result<(), Error> {
+    for count in [3, 5000] {
+        let text = properties(count) + "city = \"Zürich\"";
+
+        let mut bytes = b"\xEF\xBB\xBF".to_vec();
+        bytes.extend_from_slice(text.as_bytes());
+        let file = TempFile::new(&format!("bom-{}.txt", count), &bytes);
+        let tokenizer = Tokenizer::from_path(file.path(), Encoding::Utf8)?;
+        assert_eq!(tokenizer.text(), text);
+        // safety: nothing else changes the file while it's mapped
+        let tokenizer = unsafe { Tokenizer::from_path_mapped(file.path(), Encoding::Utf8)? };
+        assert_eq!(tokenizer.text(), text);
+
+        // every character of the text is in Latin-1, so this encodes it as Windows-1252
+        let bytes = text.chars().map(|c| c as u8).collect::<Vec<_>>();
+        let file = TempFile::new(&format!("windows-1252-{}.txt", count), &bytes);
+        let value = Value::from_path(file.path(), Encoding::Detect)?;
+        assert_eq!(value, Value::from_str(&text)?);
+
+        let error = Value::from_path(file.path(), Encoding::Utf8).unwrap_err();
+        assert_eq!(error.error_type, ErrorType::EncodingError);
+        assert_has_path(&error, file.path());
+    }
+
+    Ok(())
+}
+
+#[test]
+fn missing_file() {
+    let path = std::env::temp_dir().join("clauser-missing-file.txt");
+    let error = Value::from_path(&path, Encoding::Utf8).unwrap_err();
+    assert_eq!(error.error_type, ErrorType::IoError);
+    assert_has_path(&error, &path);
+
+    let error = de::from_path::<HashMap<String, i32>>(&path, Encoding::Utf8).unwrap_err();
+    assert_eq!(error.error_type, ErrorType::IoError);
+    assert_has_path(&error, &path);
+}
+
+#[test]
+fn errors() -> Result<(), Error> {
+    for count in [3, 5000] {
+        let text = properties(count) + "broken = { a = 1";
+        let file = TempFile::new(&format!("broken-{}.txt", count), text.as_bytes());
+
+        let error = Value::from_path(file.path(), Encoding::Utf8).unwrap_err();
+        assert_has_path(&error, file.path());
+        assert_eq!(error.location().map(|(line, _)| line), Some(count + 1));
+
+        // errors found long after the file was opened still know where they came from
+        let mut reader = Reader::from_path(file.path(), Encoding::Utf8)?;
+        for _ in 0..count {
+            reader.next_property()?;
+            reader.skip_value()?;
+        }
+        reader.next_property()?;
+        let error = reader.skip_value().unwrap_err();
+        assert_has_path(&error, file.path());
+    }
+
+    Ok(())
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+struct Entry {
+    name: String,
+    value: i32,
+}
+
+#[test]
+fn deserializer() -> Result<(), Error> {
+    for count in [3, 5000] {
+        let text = properties(count);
+        let file = TempFile::new(&format!("de-{}.txt", count), text.as_bytes());
+
+        let entries: HashMap<String, Entry> = de::from_path(file.path(), Encoding::Utf8)?;
+        assert_eq!(entries.len(), count);
+        assert_eq!(
+            entries["key_2"],
+            Entry {
+                name: "Name 2".into(),
+                value: 2
+            }
+        );
+
+        let entries: HashMap<String, Entry> =
+            de::from_deserializer(Deserializer::from_path(file.path(), Encoding::Utf8)?)?;
+        assert_eq!(entries.len(), count);
+
+        // errors raised by serde have the path too
+        let error = de::from_path::<HashMap<String, i32>>(file.path(), Encoding::Utf8).unwrap_err();
+        assert_has_path(&error, file.path());
+    }
+
+    Ok(())
+}
@@ -0,0 +1,217 @@
+use std::{cmp::Ordering, collections::HashMap};
+
+use clauser::{
+    de::{from_deserializer, from_str, Deserializer, MAX_DEPTH},
+    error::{Error, ErrorType},
+    limits::Limits,
+    reader::{ParseMode, Reader},
+    tape,
+    types::{ObjectKey, Operator},
+    value::{MixedEntry, Value},
+};
+use serde::{de::IgnoredAny, Deserialize};
+
+/// Returns the text of a property whose value is `depth` arrays nested inside each other.
+fn nested(depth: usize) -> String {
+    format!("a = {}{}", "{ ".repeat(depth), "} ".repeat(depth))
+}
+
+fn read_value(text: &str, limits: Limits) -> Result<Value<'_>, Error> {
+    Value::from_reader(&mut Reader::new(text).with_limits(limits))
+}
+
+fn limit_error<T>(result: Result<T, Error>) -> Error {
+    let error = result.err().expect("expected a limit error");
+    assert_eq!(error.error_type, ErrorType::LimitError, "{}", error);
+    error
+}
+
+#[test]
+fn depth() -> Result<(), Error> {
+    let limits = Limits::default().with_max_depth(3);
+    read_value(&nested(3), limits)?;
+    let error = limit_error(read_value(&nested(4), limits));
+    assert_eq!(error.position, Some(10));
+
+    // skipped values are limited too
+    let text = nested(4);
+    let mut reader = Reader::new(&text).with_limits(limits);
+    reader.next_property()?;
+    limit_error(reader.skip_value());
+
+    // and nothing is recovered from
+    let text = text + " b = 1";
+    let mut reader = Reader::new(&text)
+        .with_limits(limits)
+        .with_mode(ParseMode::Lenient);
+    limit_error(Value::from_reader(&mut reader));
+
+    #[derive(Deserialize)]
+    struct Nested(Vec<Nested>);
+
+    let value: HashMap<String, Nested> = from_str(&nested(3))?;
+    assert_eq!(value["a"].0.len(), 1);
+
+    // the deserializers never nest deeper than MAX_DEPTH, even without limits
+    let value: HashMap<String, Nested> = from_str(&nested(MAX_DEPTH))?;
+    assert_eq!(value["a"].0.len(), 1);
+    let value: HashMap<String, Nested> = tape::de::from_str(&nested(MAX_DEPTH))?;
+    assert_eq!(value["a"].0.len(), 1);
+
+    let text = nested(MAX_DEPTH + 1);
+    limit_error(from_str::<HashMap<String, Nested>>(&text));
+    limit_error(tape::de::from_str::<HashMap<String, Nested>>(&text));
+    limit_error(from_deserializer::<HashMap<String, Nested>>(
+        Deserializer::from_str(&text).with_limits(Limits::NONE),
+    ));
+
+    // so deeply nested input can't overflow the stack, even when it's skipped
+    let text = nested(100_000);
+    limit_error(from_str::<HashMap<String, Nested>>(&text));
+    limit_error(tape::de::from_str::<HashMap<String, Nested>>(&text));
+    limit_error(from_deserializer::<HashMap<String, Nested>>(
+        Deserializer::from_str(&text).with_limits(Limits::UNTRUSTED),
+    ));
+    limit_error(from_deserializer::<HashMap<String, IgnoredAny>>(
+        Deserializer::from_str(&text).with_limits(Limits::UNTRUSTED),
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn deep_values() -> Result<(), Error> {
+    // values are dropped and formatted with recursion, so they're never read deeper than
+    // MAX_DEPTH, even without limits
+    let text = nested(MAX_DEPTH);
+    let value = Value::from_str(&text)?;
+    assert!(format!("{:#?}", value).contains("Array"));
+    let text = nested(MAX_DEPTH + 1);
+    let error = limit_error(Value::from_str(&text));
+    assert_eq!(error.position, Some(4 + MAX_DEPTH * 2));
+    limit_error(read_value(&text, Limits::NONE));
+    limit_error(Value::from_reader(
+        &mut Reader::new(&text).with_mode(ParseMode::Lenient),
+    ));
+
+    // the reader's own limits are left as they were
+    let mut reader = Reader::new(&text);
+    limit_error(Value::from_reader(&mut reader));
+    assert_eq!(*reader.limits(), Limits::NONE);
+
+    // so deeply nested input can't overflow the stack, and neither can the values read
+    // before the error
+    let depth = 200_000;
+    limit_error(Value::from_str(&nested(depth)));
+    let text = format!("a = {}2 {}", "{ 1 b > ".repeat(depth), "} ".repeat(depth));
+    limit_error(Value::from_str(&text));
+
+    // values built by hand can be nested deeper, and are cloned and compared without recursion
+    let mut value = Value::Integer(1);
+    for _ in 0..depth {
+        value = Value::Array(vec![value]);
+    }
+
+    let copy = value.clone();
+    assert_eq!(copy, value);
+    assert_eq!(copy.partial_cmp(&value), Some(Ordering::Equal));
+    let Value::Array(mut items) = copy else {
+        panic!("expected array");
+    };
+    let shallower = items.pop().unwrap();
+    assert_ne!(shallower, value);
+    assert_eq!(shallower.partial_cmp(&value), Some(Ordering::Less));
+    shallower.drop_iteratively();
+
+    let owned = value.clone().into_owned();
+    assert_eq!(owned, value);
+    owned.drop_iteratively();
+    value.drop_iteratively();
+
+    // mixed collections and comparisons are nested the same way
+    let mut value = Value::Integer(2);
+    for _ in 0..depth {
+        value = Value::Mixed(vec![
+            MixedEntry::Item(Value::Integer(1)),
+            MixedEntry::Property(
+                ObjectKey::Identifier("b".into()),
+                Value::Comparison(Operator::GreaterThan, Box::new(value)),
+            ),
+        ]);
+    }
+
+    let owned = value.clone().into_owned();
+    assert_eq!(owned, value);
+    assert_eq!(owned.partial_cmp(&value), Some(Ordering::Equal));
+    owned.drop_iteratively();
+    value.drop_iteratively();
+
+    Ok(())
+}
+
+#[test]
+fn tokens() -> Result<(), Error> {
+    // looking ahead to discern collections doesn't count tokens more than once
+    let text = "a = { b = 1 } c = { 1 2 } # comment";
+    let limits = Limits::default().with_max_tokens(14);
+    let mut reader = Reader::new(text).with_limits(limits).with_comments(true);
+    Value::from_reader(&mut reader)?;
+
+    let mut reader = Reader::new(text)
+        .with_limits(limits.with_max_tokens(13))
+        .with_comments(true);
+    let error = limit_error(Value::from_reader(&mut reader));
+    assert_eq!(error.position, text.find('#'));
+
+    Ok(())
+}
+
+#[test]
+fn string_length() -> Result<(), Error> {
+    let limits = Limits::default().with_max_string_length(8);
+    read_value("a = \"12345678\" abcdefgh = b", limits)?;
+
+    for text in [
+        "a = \"123456789\"",
+        "abcdefghi = b",
+        "a = abcdefghi",
+        "a = { b c abcdefghi }",
+    ] {
+        limit_error(read_value(text, limits));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn collection_size() -> Result<(), Error> {
+    let limits = Limits::default().with_max_collection_size(3);
+    read_value(
+        "a = { 1 2 3 } b = { c = 1 d = 2 e = 3 } f = { 1 g = 2 3 }",
+        limits,
+    )?;
+
+    for text in [
+        "a = { 1 2 3 4 }",
+        "a = { b = 1 c = 2 d = 3 e = 4 }",
+        "a = { 1 b = 2 3 c = 4 }",
+        "a = 1 b = 2 c = 3 d = 4",
+    ] {
+        limit_error(read_value(text, limits));
+    }
+
+    // checkpoints restore the size of the collection
+    let mut reader = Reader::new("a = { 1 2 3 }").with_limits(limits);
+    reader.next_property()?;
+    let checkpoint = reader.checkpoint();
+    Value::next_from_reader(&mut reader)?;
+    reader.restore(checkpoint);
+    Value::next_from_reader(&mut reader)?;
+
+    let error = limit_error(from_deserializer::<HashMap<String, Vec<i32>>>(
+        Deserializer::from_str("a = { 1 2 3 4 }").with_limits(limits),
+    ));
+    assert_eq!(error.position, Some(12));
+
+    Ok(())
+}
@@ -0,0 +1,455 @@
+use clauser::{
+    dialect::Dialect,
+    error::{Error, ErrorType},
+    reader::{Entry, Event, ParseMode, Reader},
+    types::{CollectionType, Color, ColorSpace, Date, ObjectKey, Operator, RealType},
+    value::Value,
+};
+
+/// Reads every event from `reader`, along with the depth of the reader after each one.
+fn read_events<'a>(reader: &mut Reader<'a>) -> Result<Vec<(Event<'a>, usize)>, Error> {
+    let mut events = Vec::new();
+    while let Some(event) = reader.next_event()? {
+        events.push((event, reader.depth()));
+    }
+
+    Ok(events)
+}
+
+/// Builds a [Value] from a stream of events, to compare against [Value::from_str].
+fn value_from_events<'a>(events: &mut impl Iterator<Item = Event<'a>>) -> Value<'a> {
+    let mut entries = Vec::new();
+    while let Some(event) = events.next() {
+        let key = match event {
+            Event::Key(key) => key,
+            Event::End => break,
+            event => panic!("expected key, found {:?}", event),
+        };
+
+        let operator = match events.next() {
+            Some(Event::Operator(operator)) => operator,
+            event => panic!("expected operator, found {:?}", event),
+        };
+
+        let value = item_from_events(events.next().unwrap(), events);
+        entries.push((
+            key,
+            match operator {
+                Operator::Equals => value,
+                operator => Value::Comparison(operator, Box::new(value)),
+            },
+        ));
+    }
+
+    Value::Object(entries)
+}
+
+fn item_from_events<'a>(
+    event: Event<'a>,
+    events: &mut impl Iterator<Item = Event<'a>>,
+) -> Value<'a> {
+    match event {
+        Event::Scalar(value) => value,
+        Event::BeginObject => value_from_events(events),
+        Event::BeginArray => {
+            let mut items = Vec::new();
+            loop {
+                match events.next().unwrap() {
+                    Event::End => return Value::Array(items),
+                    event => items.push(item_from_events(event, events)),
+                }
+            }
+        }
+        event => panic!("expected value, found {:?}", event),
+    }
+}
+
+#[test]
+fn events() -> Result<(), Error> {
+    let mut reader =
+        Reader::new("date = 1444.11.11\nFRA = { tags = { a b } age > 30 }\nempty = {}");
+    let events = read_events(&mut reader)?;
+
+    assert_eq!(
+        events,
+        vec![
+            (Event::Key("date".into()), 0),
+            (Event::Operator(Operator::Equals), 0),
+            (Event::Scalar(Value::Date(Date::new(1444, 11, 11, 0))), 0),
+            (Event::Key("FRA".into()), 0),
+            (Event::Operator(Operator::Equals), 0),
+            (Event::BeginObject, 1),
+            (Event::Key("tags".into()), 1),
+            (Event::Operator(Operator::Equals), 1),
+            (Event::BeginArray, 2),
+            (Event::Scalar(Value::Identifier("a".into())), 2),
+            (Event::Scalar(Value::Identifier("b".into())), 2),
+            (Event::End, 1),
+            (Event::Key("age".into()), 1),
+            (Event::Operator(Operator::GreaterThan), 1),
+            (Event::Scalar(Value::Integer(30)), 1),
+            (Event::End, 0),
+            (Event::Key("empty".into()), 0),
+            (Event::Operator(Operator::Equals), 0),
+            (Event::BeginArray, 1),
+            (Event::End, 0),
+        ]
+    );
+
+    // the end of the input keeps returning None
+    assert_eq!(reader.next_event()?, None);
+
+    Ok(())
+}
+
+#[test]
+fn event_values() -> Result<(), Error> {
+    let mut reader =
+        Reader::new("@size = 5\ncolor = rgb { 1 2 3 }\nlist = { { 1 } { x = @size } }")
+            .with_dialect(Dialect::CK3);
+    let events: Vec<Event> = read_events(&mut reader)?
+        .into_iter()
+        .map(|(e, _)| e)
+        .collect();
+
+    assert_eq!(
+        events,
+        vec![
+            Event::Key("color".into()),
+            Event::Operator(Operator::Equals),
+            Event::Scalar(Value::Color(Color::new(ColorSpace::Rgb, [1.0, 2.0, 3.0]))),
+            Event::Key("list".into()),
+            Event::Operator(Operator::Equals),
+            Event::BeginArray,
+            Event::BeginArray,
+            Event::Scalar(Value::Integer(1)),
+            Event::End,
+            Event::BeginObject,
+            Event::Key(ObjectKey::Identifier("x".into())),
+            Event::Operator(Operator::Equals),
+            Event::Scalar(Value::Integer(5)),
+            Event::End,
+            Event::End,
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn events_match_values() -> Result<(), Error> {
+    let text =
+        "a = 1 b = { c = \"d\" e = { 1.5 2 3 } f = { } } g = { { h = yes } { i <= 2 } } j = no";
+    let events = read_events(&mut Reader::new(text))?;
+    let mut events = events.into_iter().map(|(e, _)| e);
+
+    assert_eq!(value_from_events(&mut events), Value::from_str(text)?);
+
+    Ok(())
+}
+
+#[test]
+fn event_errors() -> Result<(), Error> {
+    let mut reader = Reader::new("a = { b = }");
+    assert_eq!(reader.next_event()?, Some(Event::Key("a".into())));
+    assert_eq!(
+        reader.next_event()?,
+        Some(Event::Operator(Operator::Equals))
+    );
+    assert_eq!(reader.next_event()?, Some(Event::BeginObject));
+    // the property is checked for a value before its key is returned
+    let error = reader.next_event().unwrap_err();
+    assert_eq!(error.error_type, ErrorType::UnexpectedTokenError);
+
+    // lenient readers skip what they can't read, and close unclosed collections
+    let mut reader = Reader::new("a = { 1 ! 2 }\nb = { c = 1").with_mode(ParseMode::Lenient);
+    let events: Vec<Event> = read_events(&mut reader)?
+        .into_iter()
+        .map(|(e, _)| e)
+        .collect();
+    assert_eq!(
+        events,
+        vec![
+            Event::Key("a".into()),
+            Event::Operator(Operator::Equals),
+            Event::BeginArray,
+            Event::Scalar(Value::Integer(1)),
+            Event::Scalar(Value::Integer(2)),
+            Event::End,
+            Event::Key("b".into()),
+            Event::Operator(Operator::Equals),
+            Event::BeginObject,
+            Event::Key("c".into()),
+            Event::Operator(Operator::Equals),
+            Event::Scalar(Value::Integer(1)),
+            Event::End,
+        ]
+    );
+    assert_eq!(reader.diagnostics().len(), 2);
+    assert_eq!(reader.depth(), 0);
+
+    Ok(())
+}
+
+#[test]
+fn checkpoints() -> Result<(), Error> {
+    let mut reader = Reader::new("a = { b c } d = 1");
+    let start = reader.checkpoint();
+
+    // try reading the value of a as an object, then start over
+    reader.next_property()?;
+    let checkpoint = reader.checkpoint();
+    reader.begin_collection()?;
+    assert_eq!(reader.depth(), 1);
+    assert!(reader.next_property().is_err());
+
+    reader.restore(checkpoint);
+    assert_eq!(reader.depth(), 0);
+    assert_eq!(
+        Value::next_from_reader(&mut reader)?,
+        Value::Array(vec![
+            Value::Identifier("b".into()),
+            Value::Identifier("c".into())
+        ])
+    );
+
+    reader.restore(start.clone());
+    assert_eq!(reader.current_position(), start.position());
+    assert_eq!(
+        Value::from_reader(&mut reader)?,
+        Value::from_str("a = { b c } d = 1")?
+    );
+
+    Ok(())
+}
+
+#[test]
+fn checkpoint_state() -> Result<(), Error> {
+    // scripted variables defined after a checkpoint are forgotten
+    let mut reader = Reader::new("@x = 1\na = @x\n@x = 2\nb = @x").with_dialect(Dialect::CK3);
+    let checkpoint = reader.checkpoint();
+    Value::from_reader(&mut reader)?;
+    assert_eq!(reader.variables().get("x").unwrap().value, "2");
+    reader.restore(checkpoint);
+    assert!(reader.variables().is_empty());
+
+    // and so are diagnostics
+    let mut reader = Reader::new("a = 1 b = ! 2").with_mode(ParseMode::Lenient);
+    let checkpoint = reader.checkpoint();
+    Value::from_reader(&mut reader)?;
+    assert_eq!(reader.diagnostics().len(), 1);
+    reader.restore(checkpoint);
+    assert!(reader.diagnostics().is_empty());
+
+    // events continue from where the checkpoint was made
+    let mut reader = Reader::new("a = { b = 1 }");
+    assert_eq!(reader.next_event()?, Some(Event::Key("a".into())));
+    let checkpoint = reader.checkpoint();
+    reader.next_event()?;
+    assert_eq!(reader.next_event()?, Some(Event::BeginObject));
+    reader.restore(checkpoint);
+    assert_eq!(
+        reader.next_event()?,
+        Some(Event::Operator(Operator::Equals))
+    );
+    assert_eq!(reader.next_event()?, Some(Event::BeginObject));
+    assert_eq!(reader.depth(), 1);
+
+    // a checkpoint right after an operator still knows the value of the property is missing
+    let mut reader = Reader::new("a =\nb = c").with_mode(ParseMode::Lenient);
+    reader.next_property()?;
+    let checkpoint = reader.checkpoint();
+    assert_eq!(reader.read_stringlike()?, "");
+    assert_eq!(
+        reader.next_property()?,
+        Some(("b".into(), RealType::Identifier))
+    );
+    assert_eq!(reader.read_stringlike()?, "c");
+    reader.restore(checkpoint);
+    assert_eq!(reader.read_stringlike()?, "");
+    assert_eq!(
+        reader.next_property()?,
+        Some(("b".into(), RealType::Identifier))
+    );
+
+    // comments after the checkpoint are forgotten until they're found again
+    let mut reader = Reader::new("a = 1 # one\nb = 2 # two").with_comments(true);
+    let checkpoint = reader.checkpoint();
+    Value::from_reader(&mut reader)?;
+    reader.restore(checkpoint);
+    assert!(reader.take_comments().is_empty());
+    Value::from_reader(&mut reader)?;
+    let comments = reader.take_comments();
+    assert_eq!(
+        comments.iter().map(|c| c.content()).collect::<Vec<_>>(),
+        vec!["one", "two"]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn skip_values() -> Result<(), Error> {
+    let text =
+        "a = { b = { c = 1 } d = \"}\" } e = \"str\" f = rgb { 1 2 3 } g = 1444.11.11 # done";
+    let mut reader = Reader::new(text).with_comments(true);
+
+    reader.next_property()?;
+    let span = reader.skip_value()?;
+    assert_eq!(&text[span.start..span.end], "{ b = { c = 1 } d = \"}\" }");
+    assert_eq!(reader.depth(), 0);
+
+    reader.next_property()?;
+    let (raw, span) = reader.read_raw_value()?;
+    assert_eq!(raw, "\"str\"");
+    assert_eq!(span.start, text.find("\"str\"").unwrap());
+    assert_eq!(span.len(), 5);
+
+    reader.next_property()?;
+    assert_eq!(reader.read_raw_value()?.0, "rgb { 1 2 3 }");
+
+    reader.next_property()?;
+    assert_eq!(reader.read_raw_value()?.0, "1444.11.11");
+    assert_eq!(reader.next_property()?, None);
+
+    // comments inside skipped values are discarded
+    assert_eq!(reader.take_comments().len(), 1);
+
+    Ok(())
+}
+
+#[test]
+fn extract_raw_value() -> Result<(), Error> {
+    // find a single country without reading the others
+    let text = "countries = {\n\tENG = { gold = 10 }\n\tFRA = { gold = 20 capital = 183 }\n}";
+    let mut reader = Reader::new(text);
+    reader.next_property()?;
+    reader.begin_collection()?;
+
+    let mut france = None;
+    while let Some((key, _)) = reader.next_property()? {
+        match key {
+            ObjectKey::Identifier(tag) if tag == "FRA" => france = Some(reader.read_raw_value()?),
+            _ => {
+                reader.skip_value()?;
+            }
+        }
+    }
+
+    reader.end_collection()?;
+    let (raw, span) = france.unwrap();
+    assert_eq!(raw, "{ gold = 20 capital = 183 }");
+    assert_eq!(span.start, text.find("{ gold = 20").unwrap());
+    assert_eq!(
+        Value::from_str(&format!("FRA = {}", raw))?,
+        Value::Object(vec![(
+            "FRA".into(),
+            Value::Object(vec![
+                ("gold".into(), Value::Integer(20)),
+                ("capital".into(), Value::Integer(183))
+            ])
+        )])
+    );
+
+    Ok(())
+}
+
+#[test]
+fn skip_value_errors() -> Result<(), Error> {
+    let mut reader = Reader::new("a = { b = 1");
+    reader.next_property()?;
+    assert_eq!(
+        reader.skip_value().unwrap_err().error_type,
+        ErrorType::UnexpectedTokenError
+    );
+
+    let mut reader = Reader::new("}");
+    assert!(reader.skip_value().is_err());
+    assert!(Reader::new("").skip_value().is_err());
+
+    let mut reader = Reader::new("a = { b = 1").with_mode(ParseMode::Lenient);
+    reader.next_property()?;
+    assert_eq!(reader.read_raw_value()?.0, "{ b = 1");
+    assert_eq!(reader.diagnostics().len(), 1);
+
+    Ok(())
+}
+
+#[test]
+fn mixed_entries() -> Result<(), Error> {
+    let mut reader = Reader::new("a = { 1 2 b = c flag }");
+    reader.next_property()?;
+    assert_eq!(reader.discern_collection()?, Some(CollectionType::Mixed));
+    reader.begin_collection()?;
+
+    assert_eq!(reader.next_entry()?, Some(Entry::Item));
+    assert_eq!(Value::next_from_reader(&mut reader)?, Value::Integer(1));
+    assert_eq!(reader.next_entry()?, Some(Entry::Item));
+    reader.skip_value()?;
+    assert_eq!(
+        reader.next_entry()?,
+        Some(Entry::Property(
+            ("b".into(), RealType::Identifier),
+            Operator::Equals
+        ))
+    );
+    reader.skip_value()?;
+    assert!(!reader.is_next_property()?);
+    assert_eq!(reader.next_entry()?, Some(Entry::Item));
+    reader.skip_value()?;
+    assert_eq!(reader.next_entry()?, None);
+    reader.end_collection()?;
+
+    for (text, expected) in [
+        ("{ a = b }", Some(CollectionType::Object)),
+        ("{ a b }", Some(CollectionType::Array)),
+        ("{ }", Some(CollectionType::Array)),
+    ] {
+        assert_eq!(
+            Reader::new(text).discern_collection()?,
+            expected,
+            "{}",
+            text
+        );
+    }
+
+    // nested collections are discerned while reading through the outer one
+    let mut reader = Reader::new("{ { 1 a = b } { c = { 2 } } }");
+    assert_eq!(reader.discern_collection()?, Some(CollectionType::Array));
+    reader.begin_collection()?;
+    for expected in [CollectionType::Mixed, CollectionType::Object] {
+        assert_eq!(reader.next_entry()?, Some(Entry::Item));
+        assert_eq!(reader.discern_collection()?, Some(expected));
+        reader.skip_value()?;
+    }
+
+    let mut reader = Reader::new("{ a = b").with_mode(ParseMode::Lenient);
+    assert_eq!(reader.discern_collection()?, None);
+    assert_eq!(reader.current_position(), 0);
+    assert!(Reader::new("{ a = b").discern_collection().is_err());
+    assert!(Reader::new("a").discern_collection().is_err());
+
+    // mixed collections are read as objects, with bare values as scalars
+    let events: Vec<Event> = read_events(&mut Reader::new("a = { x = 1 flag { 2 } }"))?
+        .into_iter()
+        .map(|(e, _)| e)
+        .collect();
+    assert_eq!(
+        events,
+        vec![
+            Event::Key("a".into()),
+            Event::Operator(Operator::Equals),
+            Event::BeginObject,
+            Event::Key("x".into()),
+            Event::Operator(Operator::Equals),
+            Event::Scalar(Value::Integer(1)),
+            Event::Scalar(Value::Identifier("flag".into())),
+            Event::BeginArray,
+            Event::Scalar(Value::Integer(2)),
+            Event::End,
+            Event::End,
+        ]
+    );
+
+    Ok(())
+}
@@ -0,0 +1,150 @@
+use clauser::{
+    de::{from_deserializer, Deserializer},
+    encoding::Encoding,
+    error::{Error, ErrorType},
+    save::{SaveFile, SaveHeader},
+    types::Date,
+    value::Value,
+};
+use serde::Deserialize;
+
+const EU4_SAVE: &str =
+    "EU4txt\ndate=1444.11.11\nplayer=\"FRA\"\nchecksum=\"14ae7d2a985dafb20fea1dda69403557\"\n";
+const CK3_SAVE: &str = "SAV0100a1b2c3d40000001d\nmetadata={\n\tversion=\"1.12\"\n}\ndate=867.1.1\nplayed_character={ id=1 }\nchecksum = \"dd8aae71ac3c5b4b5e9e103e3d6a815f\"";
+
+#[test]
+fn headers() -> Result<(), Error> {
+    let header = SaveHeader::parse(b"EU4txt\nfoo=bar")?;
+    assert_eq!(header.magic, "EU4txt");
+    assert_eq!(header.version, None);
+    assert!(header.is_text());
+    assert_eq!(header.length, 7);
+
+    let header = SaveHeader::parse(b"HOI4bin\r\n\x01\x02")?;
+    assert_eq!(header.magic, "HOI4bin");
+    assert!(header.binary);
+    assert_eq!(header.length, 9);
+
+    let header = SaveHeader::parse(b"SAV0102a1b2c3d4000004a3\nPK")?;
+    assert_eq!(header.magic, "SAV");
+    assert_eq!(header.version, Some(1));
+    assert_eq!(header.flags, Some(2));
+    assert!(!header.binary);
+    assert!(header.compressed);
+    assert_eq!(header.metadata_size, Some(0x4a3));
+
+    let error = SaveHeader::parse(b"date=1444.11.11\n").unwrap_err();
+    assert_eq!(error.error_type, ErrorType::SaveHeaderError);
+    for header in [&b"SAV01zz\n"[..], b"EU4txt"] {
+        let error = SaveHeader::parse(header).unwrap_err();
+        assert_eq!(error.error_type, ErrorType::SaveHeaderError);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn gamestate() -> Result<(), Error> {
+    #[derive(Deserialize)]
+    struct Gamestate {
+        date: Date,
+        player: String,
+    }
+
+    let save = SaveFile::parse(EU4_SAVE)?;
+    assert_eq!(save.gamestate(), "date=1444.11.11\nplayer=\"FRA\"\n");
+    assert_eq!(save.metadata(), None);
+
+    let gamestate: Gamestate = from_deserializer(Deserializer::from_reader(save.reader()))?;
+    assert_eq!(gamestate.date, Date::new(1444, 11, 11, 0));
+    assert_eq!(gamestate.player, "FRA");
+
+    // the checksum footer isn't part of the game state
+    let value = Value::from_reader(&mut save.reader())?;
+    assert_eq!(
+        value,
+        Value::Object(vec![
+            ("date".into(), Value::Date(Date::new(1444, 11, 11, 0))),
+            ("player".into(), Value::String("FRA".into())),
+        ])
+    );
+
+    Ok(())
+}
+
+#[test]
+fn metadata() -> Result<(), Error> {
+    let save = SaveFile::parse(CK3_SAVE)?;
+    assert_eq!(save.header().metadata_size, Some(29));
+    assert_eq!(save.metadata(), Some("metadata={\n\tversion=\"1.12\"\n}\n"));
+
+    let mut reader = save.metadata_reader().unwrap();
+    let value = Value::from_reader(&mut reader)?;
+    assert_eq!(
+        value,
+        Value::Object(vec![(
+            "metadata".into(),
+            Value::Object(vec![("version".into(), Value::String("1.12".into()))])
+        )])
+    );
+
+    // errors are positioned relative to the whole save
+    let save = SaveFile::parse("EU4txt\na = }")?;
+    let error = Value::from_reader(&mut save.reader()).unwrap_err();
+    assert_eq!(error.location(), Some((2, 5)));
+
+    Ok(())
+}
+
+#[test]
+fn checksums() -> Result<(), Error> {
+    let save = SaveFile::parse(EU4_SAVE)?;
+    assert_eq!(save.checksum(), Some("14ae7d2a985dafb20fea1dda69403557"));
+    assert!(save.checksum_matches());
+
+    let save = SaveFile::parse(CK3_SAVE)?;
+    assert!(save.checksum_matches());
+
+    let modified = EU4_SAVE.replace("FRA", "ENG");
+    let save = SaveFile::parse(&modified)?;
+    assert!(!save.checksum_matches());
+    let fixed = save.with_updated_checksum();
+    assert!(SaveFile::parse(&fixed)?.checksum_matches());
+    assert_eq!(fixed.len(), modified.len());
+
+    let save = SaveFile::parse("EU4txt\ndate=1444.11.11\nplayer=\"FRA\"\n")?;
+    assert_eq!(save.checksum(), None);
+    assert!(!save.checksum_matches());
+    assert_eq!(save.with_updated_checksum(), EU4_SAVE);
+
+    // the checksum is computed from the original bytes of transcoded saves
+    let bytes = b"EU4txt\nname=\"Fran\xe7ois\"\n";
+    let save = SaveFile::from_bytes(bytes, Encoding::Windows1252)?;
+    assert_eq!(save.gamestate(), "name=\"François\"\n");
+    assert_eq!(save.compute_checksum(), "2233910cf2d7687f70d0b3669b86433c");
+    assert!(!save.checksum_matches());
+
+    // updated saves are UTF-8, so their checksum is computed over the UTF-8 text
+    let bytes = b"EU4txt\nname=\"Fran\xe7ois\"\nchecksum=\"2233910cf2d7687f70d0b3669b86433c\"\n";
+    let save = SaveFile::from_bytes(bytes, Encoding::Windows1252)?;
+    assert!(save.checksum_matches());
+    let fixed = save.with_updated_checksum();
+    assert!(fixed.contains("François"));
+    assert!(SaveFile::parse(&fixed)?.checksum_matches());
+
+    let save = SaveFile::from_bytes(b"EU4txt\nname=\"Fran\xe7ois\"", Encoding::Windows1252)?;
+    assert!(SaveFile::parse(&save.with_updated_checksum())?.checksum_matches());
+
+    Ok(())
+}
+
+#[test]
+fn unsupported() {
+    let error = SaveFile::from_bytes(b"SAV0103a1b2c3d400000000\nPK\x03\x04", Encoding::Utf8)
+        .err()
+        .unwrap();
+    assert_eq!(error.error_type, ErrorType::Unsupported);
+
+    let error = SaveFile::parse("EU4bin\n").err().unwrap();
+    assert_eq!(error.error_type, ErrorType::Unsupported);
+}
@@ -0,0 +1,185 @@
+//! Checks that the tokenizer produces exactly the same tokens and errors as the original
+//! character-by-character implementation, using hashes recorded from that implementation.
+
+use clauser::{dialect::Dialect, tokenizer::Tokenizer};
+
+/// Pieces of text that exercise every path through the tokenizer, including invalid input.
+const FRAGMENTS: &[&str] = &[
+    "a",
+    "key_1",
+    "flavor_fra.100",
+    "scope:actor.primary_title",
+    "gfx/interface/icon.dds",
+    "1st_army",
+    "yes",
+    "no",
+    "ünïcödé",
+    "日本",
+    "-5",
+    "1.5",
+    "1444.11.11",
+    "1.2.3.4",
+    "1.2.3.4.5",
+    "\"string\"",
+    "\"esc \\\" aped\"",
+    "\"multi\nline\"",
+    "\"ünï\"",
+    "# comment\n",
+    "## doc ünï\n",
+    "#",
+    " ",
+    "\t",
+    "\n",
+    "\r\n",
+    "\u{a0}",
+    "\u{2003}",
+    "=",
+    "==",
+    "!=",
+    "<>",
+    "<",
+    "<=",
+    ">",
+    ">=",
+    "?=",
+    "{",
+    "}",
+    "@var",
+    "@[ a + 1 ]",
+    "@",
+    "$PARAM$",
+    "$P|d$",
+    "[[PARAM]",
+    "[[!P]",
+    "[",
+    "]",
+    "k_$T$_x",
+    "'quoted'",
+    "a|b",
+    "50%",
+    "rgb",
+    "\u{feff}",
+    "!",
+    "?",
+    ".",
+    "..",
+    "5.",
+    "-",
+    "\\",
+    "\"",
+    "\u{1F600}",
+    "_",
+];
+
+/// A small deterministic random number generator, so the corpus is the same on every run.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+}
+
+/// A 64-bit FNV-1a hash, which is stable between runs and Rust versions.
+struct Fnv(u64);
+
+impl Fnv {
+    fn write(&mut self, bytes: &[u8]) {
+        for b in bytes {
+            self.0 ^= *b as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+
+    fn write_str(&mut self, s: &str) {
+        self.write(&(s.len() as u64).to_le_bytes());
+        self.write(s.as_bytes());
+    }
+}
+
+/// Tokenizes `text`, hashing every token and error. Tokenizing continues after errors.
+fn hash_tokens(hash: &mut Fnv, text: &str, dialect: Dialect, comments: bool) -> usize {
+    let mut tokenizer = Tokenizer::new(text)
+        .with_dialect(dialect)
+        .with_comments(comments);
+    let mut count = 0;
+
+    loop {
+        match tokenizer.next_owned() {
+            Ok(Some(token)) => {
+                hash.write(&(token.index as u64).to_le_bytes());
+                hash.write_str(&format!("{:?}", token.token_type));
+                hash.write_str(&token.value);
+            }
+            Ok(None) => return count,
+            Err(e) => {
+                hash.write(&(e.position.unwrap_or(usize::MAX) as u64).to_le_bytes());
+                hash.write_str(&format!("{:?}", e.error_type));
+                hash.write_str(&e.message);
+                tokenizer.skip_error(&e);
+            }
+        }
+
+        hash.write(&(tokenizer.position as u64).to_le_bytes());
+        count += 1;
+    }
+}
+
+fn generate(rng: &mut Rng, fragments: usize) -> String {
+    let mut text = String::new();
+    for _ in 0..fragments {
+        text.push_str(FRAGMENTS[rng.below(FRAGMENTS.len())]);
+        // usually separate fragments, but sometimes run them together
+        match rng.below(4) {
+            0 => (),
+            1 => text.push('\n'),
+            _ => text.push(' '),
+        }
+    }
+
+    text
+}
+
+fn corpus_hash(dialect: Dialect, comments: bool) -> (u64, usize) {
+    let mut rng = Rng(0x2545f4914f6cdd1d);
+    let mut hash = Fnv(0xcbf29ce484222325);
+    let mut count = 0;
+
+    for i in 0..2000 {
+        let text = generate(&mut rng, 1 + i % 40);
+        count += hash_tokens(&mut hash, &text, dialect, comments);
+    }
+
+    (hash.0, count)
+}
+
+#[test]
+fn same_tokens_as_scalar_tokenizer() {
+    let cases = [
+        (Dialect::default(), false, 0x016df25177267342, 30656),
+        (Dialect::default(), true, 0xb5cb3a9638ffcf2c, 32261),
+        (Dialect::EU4, false, 0x852100a3b27aef5f, 38367),
+        (Dialect::CK3, true, 0xb5cb3a9638ffcf2c, 32261),
+        (Dialect::STELLARIS, false, 0x0bf4d8620b4df246, 31196),
+        (Dialect::IMPERATOR, true, 0x664b4d3cbba000c0, 32801),
+    ];
+
+    for (dialect, comments, expected_hash, expected_count) in cases {
+        let name = dialect.name;
+        let (hash, count) = corpus_hash(dialect, comments);
+        assert_eq!(
+            (hash, count),
+            (expected_hash, expected_count),
+            "tokens differ for {} with comments {}",
+            name,
+            comments
+        );
+    }
+}
@@ -0,0 +1,81 @@
+use clauser::{
+    error::{Error, ErrorType},
+    reader::Reader,
+    source::{LineIndex, SourceId, SourceMap, Span},
+    token::TokenType,
+    tokenizer::Tokenizer,
+    value::Value,
+};
+
+#[test]
+fn line_index() {
+    let text = "a = b\nc = \"ünïcödé\"\n\nd = e";
+    let index = LineIndex::new(text);
+
+    assert_eq!(index.line_count(), 4);
+    assert_eq!(index.line_col(text, 0), (1, 1));
+    // the line break is the end of the first line
+    assert_eq!(index.line_col(text, 5), (1, 6));
+    assert_eq!(index.line_col(text, 6), (2, 1));
+    // columns are counted in characters
+    assert_eq!(index.line_col(text, text.find('é').unwrap()), (2, 12));
+    assert_eq!(index.line_col(text, text.len()), (4, 6));
+    assert_eq!(index.line_range(text, 3), Some((24, 25)));
+    assert_eq!(index.line_range(text, 5), None);
+}
+
+#[test]
+fn token_spans() -> Result<(), Error> {
+    let mut sources = SourceMap::new();
+    let id = sources.add("test.txt", "key = \"value\"");
+    let mut tokenizer = sources.tokenizer(id).unwrap();
+
+    let key = tokenizer.next()?.unwrap();
+    assert_eq!(key.span(), Span::new(id, 0, 3));
+    tokenizer.next()?;
+    let value = tokenizer.next()?.unwrap();
+    assert_eq!(value.token_type, TokenType::String);
+    assert_eq!(value.span(), Span::new(id, 7, 12));
+
+    let token = Tokenizer::new("a").next_owned()?.unwrap();
+    assert_eq!(token.span(), Span::new(SourceId::UNKNOWN, 0, 1));
+
+    Ok(())
+}
+
+#[test]
+fn multiple_files() {
+    let mut sources = SourceMap::new();
+    let first = sources.add("common/first.txt", "a = 1\nb = 2");
+    let second = sources.add("common/second.txt", "c = {\n\td = 3\n\te = }\n}");
+
+    assert_ne!(first, second);
+    assert_eq!(sources.len(), 2);
+    assert_eq!(sources.find("common/second.txt"), Some(second));
+    assert_eq!(sources.get(first).unwrap().name(), "common/first.txt");
+    assert!(sources.get(SourceId::UNKNOWN).is_none());
+
+    assert!(Value::from_reader(&mut sources.reader(first).unwrap()).is_ok());
+
+    let error = Value::from_reader(&mut sources.reader(second).unwrap()).unwrap_err();
+    assert_eq!(error.error_type, ErrorType::UnexpectedTokenError);
+    assert_eq!(error.span, Some(Span::new(second, 18, 19)));
+    assert_eq!(error.location(), Some((3, 6)));
+    assert_eq!(
+        sources.format_span(error.span.unwrap()),
+        Some("common/second.txt:3:6-3:7".to_owned())
+    );
+    assert!(sources
+        .format_error(&error)
+        .starts_with("common/second.txt:3:6-3:7: unexpected token type CloseBracket"));
+}
+
+#[test]
+fn unregistered_source() {
+    let error = Value::from_str("a = }").unwrap_err();
+    assert_eq!(error.span.map(|s| s.source), Some(SourceId::UNKNOWN));
+    assert_eq!(error.position, error.span.map(|s| s.start));
+
+    let reader = Reader::new("a = b").with_source(SourceId::default());
+    assert!(reader.source().is_unknown());
+}
@@ -0,0 +1,230 @@
+use std::io::{self, Read};
+
+use clauser::{
+    de::{from_read, from_str},
+    dialect::Dialect,
+    encoding::Encoding,
+    error::{Error, ErrorType},
+    reader::{ParseMode, Reader},
+    token::TokenType,
+    tokenizer::Tokenizer,
+    value::Value,
+};
+use serde::Deserialize;
+
+/// A [Read] that returns a few bytes at a time, so tokens and characters are split between reads.
+struct Chunked {
+    bytes: Vec<u8>,
+    position: usize,
+    reads: usize,
+    /// Fails every read once this many bytes have been returned.
+    fail_at: Option<usize>,
+}
+
+impl Chunked {
+    fn new(bytes: impl Into<Vec<u8>>) -> Chunked {
+        Chunked {
+            bytes: bytes.into(),
+            position: 0,
+            reads: 0,
+            fail_at: None,
+        }
+    }
+
+    fn failing_at(mut self, position: usize) -> Chunked {
+        self.fail_at = Some(position);
+        self
+    }
+}
+
+impl Read for Chunked {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.fail_at.is_some_and(|fail_at| self.position >= fail_at) {
+            return Err(io::Error::other("disk on fire"));
+        }
+
+        self.reads += 1;
+        let len = (1 + self.reads % 7)
+            .min(buf.len())
+            .min(self.bytes.len() - self.position);
+        buf[..len].copy_from_slice(&self.bytes[self.position..self.position + len]);
+        self.position += len;
+        Ok(len)
+    }
+}
+
+/// A token's position, type, and value, or an error's position and type.
+type TokenOrError = Result<(usize, TokenType, String), (usize, ErrorType)>;
+
+/// Reads every token and error from `tokenizer`, continuing past errors.
+fn read_all(mut tokenizer: Tokenizer) -> Vec<TokenOrError> {
+    let mut results = Vec::new();
+    loop {
+        match tokenizer.next_owned() {
+            Ok(Some(token)) => results.push(Ok((token.index, token.token_type, token.value))),
+            Ok(None) => return results,
+            Err(e) => {
+                results.push(Err((e.position.unwrap(), e.error_type.clone())));
+                tokenizer.skip_error(&e);
+            }
+        }
+    }
+}
+
+const SAMPLE: &str = "# a save\n\
+    date = 1444.11.11\n\
+    player = \"FRA\"\n\
+    name = \"Île-de-France 日本\" # ünïcödé comment\n\
+    long = \"a string that's much longer than the buffer of the tokenizer\"\n\
+    values = { -5 1.5 2.25 yes no scope:actor.primary_title }\n\
+    k_$TITLE$ = [[PARAM] x ]\n\
+    age >= 30 tags != { a b } \u{1F600} = 1\n\
+    broken = ! 5..0\n";
+
+#[test]
+fn same_tokens_as_text() {
+    for buffer_size in [1, 4, 16, 1024] {
+        for comments in [false, true] {
+            let expected = read_all(
+                Tokenizer::new(SAMPLE)
+                    .with_dialect(Dialect::CK3)
+                    .with_comments(comments),
+            );
+            let streamed = read_all(
+                Tokenizer::from_read(Chunked::new(SAMPLE), Encoding::Utf8)
+                    .with_dialect(Dialect::CK3)
+                    .with_comments(comments)
+                    .with_buffer_size(buffer_size),
+            );
+
+            assert_eq!(streamed, expected, "buffer size {}", buffer_size);
+        }
+    }
+}
+
+#[test]
+fn encodings() -> Result<(), Error> {
+    let read_value = |bytes: &[u8], encoding| -> Result<Value<'static>, Error> {
+        let mut reader = Reader::from_read(Chunked::new(bytes), encoding);
+        Ok(Value::from_reader(&mut reader)?.into_owned())
+    };
+
+    let expected = Value::from_str("name = \"Zürich\"")?;
+    assert_eq!(
+        read_value(b"\xEF\xBB\xBFname = \"Z\xC3\xBCrich\"", Encoding::Utf8)?,
+        expected
+    );
+    assert_eq!(
+        read_value(b"\xEF\xBB\xBFname = \"Z\xC3\xBCrich\"", Encoding::Detect)?,
+        expected
+    );
+    assert_eq!(
+        read_value(b"name = \"Z\xFCrich\"", Encoding::Windows1252)?,
+        expected
+    );
+    assert_eq!(
+        read_value(b"name = \"Z\xFCrich\"", Encoding::Detect)?,
+        expected
+    );
+
+    let error = read_value(b"name = \"Z\xFCrich\"", Encoding::Utf8).unwrap_err();
+    assert_eq!(error.error_type, ErrorType::EncodingError);
+    assert_eq!(error.position, Some(9));
+
+    Ok(())
+}
+
+#[test]
+fn values() -> Result<(), Error> {
+    let text = "a = 1 b = { c = \"d\" e = { 1.5 2 3 } f = { } } g = { { h = yes } { i <= 2 } } j = rgb { 1 2 3 }";
+    let mut reader = Reader::from_read(Chunked::new(text), Encoding::Utf8);
+    assert_eq!(Value::from_reader(&mut reader)?, Value::from_str(text)?);
+
+    // raw values are kept buffered until they've been read
+    let mut reader = Reader::from_tokenizer(
+        Tokenizer::from_read(Chunked::new(text), Encoding::Utf8).with_buffer_size(2),
+    );
+    reader.next_property()?;
+    reader.skip_value()?;
+    reader.next_property()?;
+    assert_eq!(
+        reader.read_raw_value()?.0,
+        "{ c = \"d\" e = { 1.5 2 3 } f = { } }"
+    );
+
+    Ok(())
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+struct Country {
+    name: String,
+    capital: i32,
+    ideas: Vec<String>,
+}
+
+#[test]
+fn deserializer() -> Result<(), Error> {
+    let text = "name = \"Île-de-France\" capital = 183 ideas = { a b \"c d\" }";
+    let country: Country = from_read(Chunked::new(text), Encoding::Detect)?;
+    assert_eq!(country, from_str(text)?);
+
+    Ok(())
+}
+
+#[test]
+fn errors() -> Result<(), Error> {
+    // errors keep their location after the lines before them have been discarded
+    let mut text = String::new();
+    for i in 0..500 {
+        text.push_str(&format!("key_{} = \"ünï {}\"\n", i, i));
+    }
+    text.push_str("bad = = 1\n");
+
+    let mut reader = Reader::from_tokenizer(
+        Tokenizer::from_read(Chunked::new(text.clone()), Encoding::Utf8).with_buffer_size(64),
+    );
+    let error = Value::from_reader(&mut reader).unwrap_err();
+    assert_eq!(error.error_type, ErrorType::UnexpectedTokenError);
+    assert_eq!(error.position, Some(text.find("= 1").unwrap()));
+    assert_eq!(error.location(), Some((501, 7)));
+
+    // errors reading the input aren't recovered from
+    let mut reader = Reader::from_read(Chunked::new(text).failing_at(100), Encoding::Utf8)
+        .with_mode(ParseMode::Lenient);
+    let error = Value::from_reader(&mut reader).unwrap_err();
+    assert_eq!(error.error_type, ErrorType::IoError);
+    assert!(error.message.contains("disk on fire"));
+
+    Ok(())
+}
+
+#[test]
+fn bounded_memory() -> Result<(), Error> {
+    let mut text = String::from("countries = {\n");
+    for i in 0..20000 {
+        text.push_str(&format!(
+            "\tC{:05} = {{ name = \"Country {}\" gold = {}.5 history = {{ 1444.11.11 = {{ owner = yes }} }} }}\n",
+            i, i, i
+        ));
+    }
+    text.push_str("}\n");
+
+    let mut tokenizer =
+        Tokenizer::from_read(Chunked::new(text.clone()), Encoding::Utf8).with_buffer_size(1024);
+    let mut count = 0;
+    let mut max_buffered = 0;
+    while let Some(token) = tokenizer.next()? {
+        assert_eq!(
+            tokenizer.str_for_token(&token),
+            &text[token.index..token.index + token.length]
+        );
+        max_buffered = max_buffered.max(tokenizer.text().len());
+        count += 1;
+    }
+
+    assert_eq!(count, Tokenizer::parse_all(&text)?.len());
+    assert!(text.len() > 1_000_000);
+    assert!(max_buffered <= 4096, "buffered {} bytes", max_buffered);
+
+    Ok(())
+}
@@ -0,0 +1,407 @@
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap},
+};
+
+use clauser::{
+    de,
+    dialect::Dialect,
+    error::{Error, ErrorType},
+    limits::Limits,
+    tape::{
+        de::{from_str, from_tape, from_value},
+        Tape, TapeEntry, TapeToken,
+    },
+    token::TokenType,
+    tokenizer::Tokenizer,
+    types::{CollectionType, Color, ColorSpace, Comparison, Date, ObjectKey, Operator, RealType},
+};
+use serde::{de::IgnoredAny, Deserialize};
+
+fn build_error(text: &str) -> Error {
+    match Tape::new(text) {
+        Ok(_) => panic!("expected an error building {:?}", text),
+        Err(e) => e,
+    }
+}
+
+#[test]
+fn brackets() -> Result<(), Error> {
+    let text = "a = { b = { 1 2 } } c = d";
+    let tape = Tape::new(text)?;
+    let tokens = tape.tokens();
+
+    // the root is wrapped in brackets of its own
+    assert_eq!(
+        tokens[0],
+        TapeToken::Open {
+            close: 14,
+            index: 0
+        }
+    );
+    assert_eq!(
+        tokens[14],
+        TapeToken::Close {
+            open: 0,
+            index: text.len()
+        }
+    );
+
+    assert_eq!(
+        tokens[3],
+        TapeToken::Open {
+            close: 10,
+            index: 4
+        }
+    );
+    assert_eq!(tokens[10], TapeToken::Close { open: 3, index: 18 });
+    assert_eq!(
+        tokens[6],
+        TapeToken::Open {
+            close: 9,
+            index: 10
+        }
+    );
+    assert_eq!(tokens[9], TapeToken::Close { open: 6, index: 16 });
+    assert_eq!(
+        tokens[7],
+        TapeToken::Token {
+            token_type: TokenType::Number,
+            index: 12,
+            length: 1
+        }
+    );
+    assert_eq!(tape.depth(), 2);
+
+    Ok(())
+}
+
+#[test]
+fn entries() -> Result<(), Error> {
+    let tape = Tape::new("a = { b = { 1 2 } } x c >= rgb { 1 2 3 } { 4 } d = 1444.11.11")?;
+    let root = tape.root();
+    assert_eq!(root.collection_type(), Some(CollectionType::Mixed));
+
+    let entries = root.entries().collect::<Vec<_>>();
+    assert_eq!(entries.len(), 5);
+
+    // each entry starts right after the value of the last one
+    let TapeEntry::Property(key, Operator::Equals, a) = entries[0] else {
+        panic!("expected property, found {:?}", entries[0]);
+    };
+    assert_eq!(key.as_key()?, ObjectKey::Identifier(Cow::Borrowed("a")));
+    assert_eq!((a.index(), a.end_index()), (3, 10));
+    assert_eq!(a.raw_text(), "{ b = { 1 2 } }");
+    assert_eq!(a.collection_type(), Some(CollectionType::Object));
+
+    let TapeEntry::Item(x) = entries[1] else {
+        panic!("expected item, found {:?}", entries[1]);
+    };
+    assert_eq!(x.as_str()?, "x");
+
+    let TapeEntry::Property(_, Operator::GreaterThanEq, c) = entries[2] else {
+        panic!("expected property, found {:?}", entries[2]);
+    };
+    assert_eq!(c.real_type(), Some(RealType::Color));
+    assert_eq!(c.raw_text(), "rgb { 1 2 3 }");
+    assert_eq!(c.as_color()?, Color::new(ColorSpace::Rgb, [1.0, 2.0, 3.0]));
+    assert_eq!(c.entries().count(), 0);
+
+    let TapeEntry::Item(block) = entries[3] else {
+        panic!("expected item, found {:?}", entries[3]);
+    };
+    assert_eq!(block.collection_type(), Some(CollectionType::Array));
+
+    let d = root.get("d").unwrap();
+    assert_eq!(d.as_date()?, Date::new(1444, 11, 11, 0));
+    assert!(root.get("missing").is_none());
+
+    // values can be found again from their index
+    let b = tape.value_at(a.index()).unwrap().get("b").unwrap();
+    assert_eq!(b.raw_text(), "{ 1 2 }");
+    assert!(tape.value_at(b.end_index()).is_none());
+    assert!(tape.value_at(2).is_none());
+
+    Ok(())
+}
+
+#[test]
+fn values() -> Result<(), Error> {
+    let text = "s = \"a \\\"quoted\\\" string\" i = ident n = -2.5 b = no \"1\" = yes 10 = x e =";
+    let tape = Tape::new(text)?;
+    let root = tape.root();
+
+    assert_eq!(root.get("s").unwrap().as_str()?, "a \"quoted\" string");
+    assert!(matches!(
+        root.get("i").unwrap().as_str()?,
+        Cow::Borrowed("ident")
+    ));
+    assert_eq!(root.get("n").unwrap().as_number::<f64>()?, -2.5);
+    assert!(!root.get("b").unwrap().as_bool()?);
+    assert!(root.get("1").unwrap().as_bool()?);
+    assert_eq!(root.get("10").unwrap().as_str()?, "x");
+
+    let e = root.get("e").unwrap();
+    assert!(e.is_empty());
+    assert_eq!(e.real_type(), None);
+
+    let error = root.get("i").unwrap().as_number::<i32>().unwrap_err();
+    assert_eq!(error.error_type, ErrorType::UnexpectedTokenError);
+    assert_eq!(error.position, text.find("ident"));
+    assert_eq!(error.location(), Some((1, text.find("ident").unwrap() + 1)));
+
+    let error = root.get("n").unwrap().as_number::<i32>().unwrap_err();
+    assert_eq!(error.error_type, ErrorType::InvalidNumberError);
+
+    Ok(())
+}
+
+#[test]
+fn build_errors() -> Result<(), Error> {
+    for (text, error_type, position) in [
+        ("a = { b = 1", ErrorType::UnexpectedTokenError, 4),
+        ("a = 1 } b = 2", ErrorType::UnexpectedTokenError, 6),
+        ("a = = 1", ErrorType::UnexpectedTokenError, 4),
+        ("= 1", ErrorType::UnexpectedTokenError, 0),
+        ("a = { 1 } = 2", ErrorType::UnexpectedTokenError, 10),
+        ("@cost = 1", ErrorType::Unsupported, 0),
+        ("a = @[ 1 + 2 ]", ErrorType::Unsupported, 4),
+        ("a = { [[P] b = 1 ] }", ErrorType::Unsupported, 6),
+    ] {
+        let error = build_error(text);
+        assert_eq!(error.error_type, error_type, "{}", text);
+        assert_eq!(error.position, Some(position), "{}", text);
+    }
+
+    // missing values are only allowed at the end of a collection
+    Tape::new("a = { b = } c =")?;
+    assert_eq!(
+        build_error("a = { b = c = 1 }").error_type,
+        ErrorType::UnexpectedTokenError
+    );
+
+    // comments are skipped
+    let tape = Tape::from_tokenizer(Tokenizer::new("a = 1 # one\nb = 2").with_comments(true))?;
+    assert_eq!(tape.root().entries().count(), 2);
+
+    Ok(())
+}
+
+#[test]
+fn limits() -> Result<(), Error> {
+    fn build(text: &str, limits: Limits) -> Result<Tape<'_>, Error> {
+        Tape::from_tokenizer_with_limits(Tokenizer::new(text), limits)
+    }
+
+    let limit_error = |result: Result<Tape, Error>| {
+        let error = result.err().expect("expected a limit error");
+        assert_eq!(error.error_type, ErrorType::LimitError, "{}", error);
+        error
+    };
+
+    let limits = Limits::default().with_max_depth(2);
+    build("a = { { } } b = rgb { 1 2 3 }", limits)?;
+    let error = limit_error(build("a = { { { } } }", limits));
+    assert_eq!(error.position, Some(8));
+
+    let limits = Limits::default().with_max_collection_size(3);
+    build(
+        "a = { 1 2 3 } b = { c = 1 d = rgb { 1 2 3 } e = 3 } f = { 1 g = 2 3 }",
+        limits,
+    )?;
+    limit_error(build("a = { 1 2 3 4 }", limits));
+    limit_error(build("a = 1 b = 2 c = 3 d = 4", limits));
+
+    limit_error(build("a = b", Limits::default().with_max_tokens(2)));
+    limit_error(build(
+        "a = abcdefghi",
+        Limits::default().with_max_string_length(8),
+    ));
+
+    Ok(())
+}
+
+#[test]
+fn dialect() -> Result<(), Error> {
+    let text = "color = hsv360 { 180 50 50 }";
+    let tape = Tape::from_tokenizer(Tokenizer::new(text).with_dialect(Dialect::CK3))?;
+    assert_eq!(
+        tape.root().get("color").unwrap().real_type(),
+        Some(RealType::Color)
+    );
+
+    // prefixes from other games are plain identifiers
+    let tape = Tape::from_tokenizer(Tokenizer::new(text).with_dialect(Dialect::EU4))?;
+    assert_eq!(tape.root().entries().count(), 2);
+
+    Ok(())
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+struct Country<'a> {
+    #[serde(borrow)]
+    tag: Cow<'a, str>,
+    name: String,
+    capital: i32,
+    gold: f64,
+    founded: Date,
+    ideas: Vec<String>,
+    color: [u8; 3],
+    flag: Color,
+    age: Comparison<i32>,
+    missing: Option<String>,
+    #[serde(default)]
+    history: HashMap<Date, HashMap<String, String>>,
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+struct Modifier {
+    #[serde(rename = "$items")]
+    flags: Vec<String>,
+    value: i32,
+}
+
+#[test]
+fn deserializer() -> Result<(), Error> {
+    let text = "tag = FRA name = \"France\" capital = 183 gold = 10.5 founded = 843.8.10 \
+        ideas = { a b \"c d\" } color = rgb { 255 128 0 } flag = { 1 2 3 } age >= 30 \
+        unknown = { deeply = { nested = { 1 2 3 } } } \
+        history = { 1444.11.11 = { owner = FRA } } missing =";
+
+    let expected: Country = de::from_str(text)?;
+    let country: Country = from_str(text)?;
+    assert_eq!(country, expected);
+    assert!(matches!(country.tag, Cow::Borrowed("FRA")));
+    assert_eq!(country.age, Comparison::new(Operator::GreaterThanEq, 30));
+
+    let text = "hidden value = 5 global";
+    assert_eq!(from_str::<Modifier>(text)?, de::from_str::<Modifier>(text)?);
+
+    let text = "a = 1 b = { c = 2.5 d = { yes no } e = \"f\" } g = { }";
+    assert_eq!(from_str::<HashMap<String, IgnoredAny>>(text)?.len(), 3);
+
+    // self-describing types get the bare values of mixed collections under $items
+    #[derive(Deserialize, Debug, PartialEq)]
+    #[serde(untagged)]
+    enum Any {
+        Integer(i64),
+        String(String),
+        Array(Vec<Any>),
+        Object(BTreeMap<String, Any>),
+    }
+
+    let text = "hidden a = { 1 2 b = c } d = { e = 1 flag } f = { 1 2 } g = { h = 1 }";
+    let value = from_str::<BTreeMap<String, Any>>(text)?;
+    assert_eq!(value, de::from_str::<BTreeMap<String, Any>>(text)?);
+    assert_eq!(
+        value["$items"],
+        Any::Array(vec![Any::String("hidden".into())])
+    );
+
+    Ok(())
+}
+
+#[test]
+fn enums() -> Result<(), Error> {
+    #[derive(Deserialize, Debug, PartialEq)]
+    enum External {
+        Unit,
+        Newtype(i32),
+        Tuple(i32, i32),
+        Struct { x: i32 },
+    }
+
+    for text in ["a = Unit", "a = \"Unit\""] {
+        let value = from_str::<HashMap<String, External>>(text)?;
+        assert_eq!(value, de::from_str::<HashMap<String, External>>(text)?);
+        assert_eq!(value["a"], External::Unit);
+    }
+
+    // externally tagged variants with data can't be read by either deserializer
+    for text in ["a = Newtype", "a = \"Tuple\" { 1 2 }", "a = Struct"] {
+        let error = from_str::<HashMap<String, External>>(text).unwrap_err();
+        assert_eq!(error.error_type, ErrorType::InvalidType, "{}", text);
+        let error = de::from_str::<HashMap<String, External>>(text).unwrap_err();
+        assert_eq!(error.error_type, ErrorType::InvalidType, "{}", text);
+    }
+
+    for text in ["a = { Newtype = 1 }", "a = 1"] {
+        assert!(from_str::<HashMap<String, External>>(text).is_err());
+        assert!(de::from_str::<HashMap<String, External>>(text).is_err());
+    }
+
+    // but other representations can
+    #[derive(Deserialize, Debug, PartialEq)]
+    #[serde(tag = "type")]
+    enum Internal {
+        Unit,
+        Struct { x: i32 },
+    }
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    #[serde(tag = "t", content = "c")]
+    enum Adjacent {
+        Newtype(i32),
+        Tuple(i32, i32),
+    }
+
+    let text = "a = { type = Unit } b = { type = Struct x = 1 }";
+    let value = from_str::<HashMap<String, Internal>>(text)?;
+    assert_eq!(value, de::from_str::<HashMap<String, Internal>>(text)?);
+    assert_eq!(value["b"], Internal::Struct { x: 1 });
+
+    let text = "a = { t = Newtype c = 1 } b = { t = Tuple c = { 1 2 } }";
+    let value = from_str::<HashMap<String, Adjacent>>(text)?;
+    assert_eq!(value, de::from_str::<HashMap<String, Adjacent>>(text)?);
+    assert_eq!(value["b"], Adjacent::Tuple(1, 2));
+
+    Ok(())
+}
+
+#[test]
+fn subtrees() -> Result<(), Error> {
+    let mut text = String::from("countries = {\n");
+    for i in 0..1000 {
+        text.push_str(&format!(
+            "\tC{:04} = {{ name = \"Country {}\" gold = {}.5 ideas = {{ a b c }} }}\n",
+            i, i, i
+        ));
+    }
+    text.push_str("}\n");
+
+    let tape = Tape::new(&text)?;
+    let country = tape.root().get("countries").unwrap().get("C0500").unwrap();
+
+    #[derive(Deserialize, Debug, PartialEq)]
+    struct Country {
+        name: String,
+        gold: f64,
+    }
+
+    assert_eq!(
+        from_value::<Country>(country)?,
+        Country {
+            name: "Country 500".into(),
+            gold: 500.5
+        }
+    );
+
+    let countries: HashMap<String, Country> = from_value(tape.root().get("countries").unwrap())?;
+    assert_eq!(countries.len(), 1000);
+
+    // errors point at the value that failed, with its line
+    let error = from_value::<HashMap<String, i32>>(country).unwrap_err();
+    assert_eq!(error.error_type, ErrorType::UnexpectedTokenError);
+    assert_eq!(error.location(), Some((502, 20)));
+
+    let error = from_tape::<HashMap<String, HashMap<String, Vec<i32>>>>(&tape).unwrap_err();
+    assert_eq!(error.location(), Some((2, 12)));
+
+    let tape = Tape::new("a = 1 b = { name = \"Country\" }")?;
+    let b = tape.root().get("b").unwrap();
+    let error = from_value::<Country>(b).unwrap_err();
+    assert_eq!(error.error_type, ErrorType::MissingField);
+    assert_eq!(error.position, Some(b.span().start));
+
+    Ok(())
+}
@@ -0,0 +1,95 @@
+use clauser::{
+    error::{Error, ErrorType},
+    template::Template,
+    value::Value,
+};
+
+fn expect_error(text: &str, arguments: &[(&str, &str)], position: usize) {
+    let error = Template::parse(text)
+        .and_then(|t| t.expand(arguments))
+        .unwrap_err();
+    assert_eq!(error.error_type, ErrorType::ParameterError, "{:?}", error);
+    assert_eq!(error.position, Some(position), "{:?}", error);
+}
+
+#[test]
+fn parameters() -> Result<(), Error> {
+    let template = Template::parse(
+        "add_gold = $AMOUNT$ [[BONUS] add_prestige = $BONUS$ ] title = k_$TITLE|france$",
+    )?;
+    assert_eq!(template.parameters(), ["AMOUNT", "BONUS", "TITLE"]);
+
+    assert_eq!(
+        template.expand(&[("AMOUNT", "100"), ("BONUS", "5"), ("TITLE", "england")])?,
+        "add_gold = 100  add_prestige = 5  title = k_england"
+    );
+    assert_eq!(
+        template.expand(&[("AMOUNT", "100")])?,
+        "add_gold = 100  title = k_france"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn conditional_blocks() -> Result<(), Error> {
+    let template = Template::parse(
+        "[[A] a = yes [[!B] b = no ] ] [[!A] not_a = yes ] math = @[ x * 2 ] # [[A] $ ]",
+    )?;
+
+    assert_eq!(
+        template.expand(&[("A", "1")])?,
+        " a = yes  b = no    math = @[ x * 2 ] # [[A] $ ]"
+    );
+    assert_eq!(
+        template.expand(&[("A", "1"), ("B", "1")])?,
+        " a = yes    math = @[ x * 2 ] # [[A] $ ]"
+    );
+    assert_eq!(
+        template.expand(&[])?,
+        "  not_a = yes  math = @[ x * 2 ] # [[A] $ ]"
+    );
+
+    Ok(())
+}
+
+#[test]
+fn strings() -> Result<(), Error> {
+    let template = Template::parse(r#"name = "$NAME$ the \"$EPITHET$\"" other = "]""#)?;
+    assert_eq!(
+        template.expand(&[("NAME", "Charles"), ("EPITHET", "Great")])?,
+        r#"name = "Charles the \"Great\"" other = "]""#
+    );
+
+    Ok(())
+}
+
+#[test]
+fn expand_value() -> Result<(), Error> {
+    let template = Template::parse("add_gold = $AMOUNT$ [[TARGET] target = $TARGET$ ]")?;
+    assert_eq!(
+        template.expand_value(&[("AMOUNT", "50"), ("TARGET", "ROOT")])?,
+        Value::Object(vec![
+            ("add_gold".into(), Value::Integer(50)),
+            ("target".into(), Value::Identifier("ROOT".into())),
+        ])
+    );
+
+    Ok(())
+}
+
+#[test]
+fn errors() {
+    expect_error("a = $AMOUNT$", &[], 4);
+    expect_error("a = $AMOUNT", &[], 4);
+    expect_error("a = $$", &[], 4);
+    expect_error("[[A] a = b", &[], 0);
+    expect_error("[[A a = b", &[], 0);
+    expect_error("a = b ]", &[], 6);
+    expect_error("a = \"$A$", &[("A", "1")], 4);
+
+    // parameters in blocks that are left out aren't required
+    assert!(Template::parse("[[A] a = $B$ ]")
+        .and_then(|t| t.expand(&[]))
+        .is_ok());
+}
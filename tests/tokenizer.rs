@@ -1,7 +1,7 @@
 use clauser::{
     error::{Error, ErrorType},
     token::{OwnedToken, TokenType},
-    tokenizer::Tokenizer,
+    tokenizer::{TextEdit, Tokenizer},
 };
 
 #[derive(Debug)]
@@ -118,6 +118,36 @@ fn identifier() -> Result<(), Error> {
         TokenType::Identifier,
         "test:with:colons",
     )?;
+    expect_token("flavor_fra.100", TokenType::Identifier)?;
+    expect_token("scope:actor.primary_title", TokenType::Identifier)?;
+    expect_token("event_target:foo", TokenType::Identifier)?;
+    expect_token("gfx/interface/icon.dds", TokenType::Identifier)?;
+    expect_token("Ma'rib", TokenType::Identifier)?;
+    expect_token("var@scope", TokenType::Identifier)?;
+    expect_token("a-b|c%", TokenType::Identifier)?;
+    expect_token("1st_army", TokenType::Identifier)?;
+    expect_token("2nd", TokenType::Identifier)?;
+    expect_token("yes_please", TokenType::Identifier)?;
+
+    assert_vec_equal(
+        &Tokenizer::parse_all("id = flavor_fra.100 date = 1444.11.11 num = -5.5 path=gfx/a.dds")?,
+        &vec![
+            ExpectedToken(TokenType::Identifier, "id"),
+            ExpectedToken(TokenType::Equals, "="),
+            ExpectedToken(TokenType::Identifier, "flavor_fra.100"),
+            ExpectedToken(TokenType::Identifier, "date"),
+            ExpectedToken(TokenType::Equals, "="),
+            ExpectedToken(TokenType::Date, "1444.11.11"),
+            ExpectedToken(TokenType::Identifier, "num"),
+            ExpectedToken(TokenType::Equals, "="),
+            ExpectedToken(TokenType::Number, "-5.5"),
+            ExpectedToken(TokenType::Identifier, "path"),
+            ExpectedToken(TokenType::Equals, "="),
+            ExpectedToken(TokenType::Identifier, "gfx/a.dds"),
+        ],
+    );
+
+    expect_error("'quoted'")?;
 
     Ok(())
 }
@@ -139,7 +169,15 @@ fn string() -> Result<(), Error> {
         ],
     );
 
+    expect_token_and_value(
+        "\"He said \\\"hi\\\"\"",
+        TokenType::String,
+        "He said \\\"hi\\\"",
+    )?;
+    expect_token_and_value("\"C:\\\\\"", TokenType::String, "C:\\\\")?;
+
     expect_error("\"unclosed")?;
+    expect_error("\"escaped end\\\"")?;
     expect_error("unopened\"")?;
     expect_error("'single quotes'")?;
 
@@ -156,6 +194,10 @@ fn symbols() -> Result<(), Error> {
     expect_token_and_value("<", TokenType::LessThan, "<")?;
     expect_token_and_value("<=", TokenType::LessThanEq, "<=")?;
     expect_token_and_value("?=", TokenType::ExistenceCheck, "?=")?;
+    expect_token_and_value("==", TokenType::DoubleEquals, "==")?;
+    expect_token_and_value("!=", TokenType::NotEquals, "!=")?;
+    expect_token_and_value("<>", TokenType::NotEquals, "<>")?;
+    expect_error("!")?;
 
     Ok(())
 }
@@ -194,7 +236,265 @@ fn iterator() -> Result<(), Error> {
 }
 
 #[test]
-fn error_context() {}
+fn unicode() -> Result<(), Error> {
+    expect_token_and_value("Île_de_France", TokenType::Identifier, "Île_de_France")?;
+    expect_token_and_value("Köln", TokenType::Identifier, "Köln")?;
+    expect_token_and_value(
+        "\"Bourgogne-Franche-Comté\"",
+        TokenType::String,
+        "Bourgogne-Franche-Comté",
+    )?;
+
+    let tokens = Tokenizer::parse_all("name = \"Æthelstan\" # ünïcödé comment\nkey = Düsseldorf")?;
+    let expected = vec![
+        ExpectedToken(TokenType::Identifier, "name"),
+        ExpectedToken(TokenType::Equals, "="),
+        ExpectedToken(TokenType::String, "Æthelstan"),
+        ExpectedToken(TokenType::Identifier, "key"),
+        ExpectedToken(TokenType::Equals, "="),
+        ExpectedToken(TokenType::Identifier, "Düsseldorf"),
+    ];
+    assert_vec_equal(&tokens, &expected);
+
+    // token positions are byte offsets
+    let mut tokenizer = Tokenizer::new("é = ü");
+    let token = tokenizer.next()?.unwrap();
+    assert_eq!((token.index, token.length), (0, 2));
+    let token = tokenizer.next()?.unwrap();
+    assert_eq!((token.index, token.length), (3, 1));
+    let token = tokenizer.next()?.unwrap();
+    assert_eq!((token.index, token.length), (5, 2));
+    assert_eq!(tokenizer.str_for_token(&token), "ü");
+
+    Ok(())
+}
+
+#[test]
+fn trailing_comment() -> Result<(), Error> {
+    assert_vec_equal(
+        &Tokenizer::parse_all("a # no new line")?,
+        &vec![ExpectedToken(TokenType::Identifier, "a")],
+    );
+
+    Ok(())
+}
+
+#[test]
+fn error_context() {
+    let error = Tokenizer::parse_all("a = b\nc = ?").unwrap_err();
+    assert_eq!(error.location(), Some((2, 5)));
+
+    // columns are counted in characters, not bytes
+    let error = Tokenizer::parse_all("a = b\nçà = ?").unwrap_err();
+    assert_eq!(error.location(), Some((2, 6)));
+    let error = Tokenizer::parse_all("ä = \"ö").unwrap_err();
+    assert_eq!(error.error_type, ErrorType::TokenizerError);
+    assert_eq!(error.location(), Some((1, 6)));
+}
+
+#[test]
+fn variable() -> Result<(), Error> {
+    expect_token("@cost", TokenType::Variable)?;
+    expect_token("@base_cost_2", TokenType::Variable)?;
+    assert_vec_equal(
+        &Tokenizer::parse_all("@cost = 10 a = @cost")?,
+        &vec![
+            ExpectedToken(TokenType::Variable, "@cost"),
+            ExpectedToken(TokenType::Equals, "="),
+            ExpectedToken(TokenType::Number, "10"),
+            ExpectedToken(TokenType::Identifier, "a"),
+            ExpectedToken(TokenType::Equals, "="),
+            ExpectedToken(TokenType::Variable, "@cost"),
+        ],
+    );
+    expect_error("@ = 10")?;
+
+    Ok(())
+}
+
+#[test]
+fn inline_math() -> Result<(), Error> {
+    expect_token("@[ cost * 2 + 5 ]", TokenType::InlineMath)?;
+    expect_token("@[(a+b)*2]", TokenType::InlineMath)?;
+    assert_vec_equal(
+        &Tokenizer::parse_all("a = @[ x\n/ 2 ] b = 1")?,
+        &vec![
+            ExpectedToken(TokenType::Identifier, "a"),
+            ExpectedToken(TokenType::Equals, "="),
+            ExpectedToken(TokenType::InlineMath, "@[ x\n/ 2 ]"),
+            ExpectedToken(TokenType::Identifier, "b"),
+            ExpectedToken(TokenType::Equals, "="),
+            ExpectedToken(TokenType::Number, "1"),
+        ],
+    );
+    expect_error("a = @[ 1 + 2")?;
+
+    Ok(())
+}
+
+#[test]
+fn parameters() -> Result<(), Error> {
+    expect_token("$AMOUNT$", TokenType::Identifier)?;
+    expect_token("k_$TITLE$_title", TokenType::Identifier)?;
+    expect_token("$TITLE|k_france$", TokenType::Identifier)?;
+    assert_vec_equal(
+        &Tokenizer::parse_all("[[BONUS] a = $BONUS$ ] [[!BONUS] b = c ]")?,
+        &vec![
+            ExpectedToken(TokenType::ParameterBlockStart, "[[BONUS]"),
+            ExpectedToken(TokenType::Identifier, "a"),
+            ExpectedToken(TokenType::Equals, "="),
+            ExpectedToken(TokenType::Identifier, "$BONUS$"),
+            ExpectedToken(TokenType::ParameterBlockEnd, "]"),
+            ExpectedToken(TokenType::ParameterBlockStart, "[[!BONUS]"),
+            ExpectedToken(TokenType::Identifier, "b"),
+            ExpectedToken(TokenType::Equals, "="),
+            ExpectedToken(TokenType::Identifier, "c"),
+            ExpectedToken(TokenType::ParameterBlockEnd, "]"),
+        ],
+    );
+    expect_error("[[BONUS a = b")?;
+    expect_error("[a]")?;
+
+    Ok(())
+}
+
+#[test]
+fn lenient() {
+    let (tokens, errors) = Tokenizer::parse_all_lenient("a = ^ b\nc = \"d");
+    assert_vec_equal(
+        &tokens,
+        &vec![
+            ExpectedToken(TokenType::Identifier, "a"),
+            ExpectedToken(TokenType::Equals, "="),
+            ExpectedToken(TokenType::Identifier, "b"),
+            ExpectedToken(TokenType::Identifier, "c"),
+            ExpectedToken(TokenType::Equals, "="),
+        ],
+    );
+    assert_eq!(errors.len(), 2);
+    assert!(errors
+        .iter()
+        .all(|e| e.error_type == ErrorType::TokenizerError));
+    assert_eq!(errors[0].location(), Some((1, 5)));
+}
+
+#[test]
+fn comments() -> Result<(), Error> {
+    let text = "## cardinality = 0..1\r\na = b # trailing\n#";
+    let mut tokenizer = Tokenizer::new(text).with_comments(true);
+    let tokens: Result<Vec<OwnedToken>, Error> = tokenizer.iter_owned().collect();
+    assert_vec_equal(
+        &tokens?,
+        &vec![
+            ExpectedToken(TokenType::Comment, "## cardinality = 0..1"),
+            ExpectedToken(TokenType::Identifier, "a"),
+            ExpectedToken(TokenType::Equals, "="),
+            ExpectedToken(TokenType::Identifier, "b"),
+            ExpectedToken(TokenType::Comment, "# trailing"),
+            ExpectedToken(TokenType::Comment, "#"),
+        ],
+    );
+
+    // comments are skipped by default
+    assert_eq!(Tokenizer::parse_all(text)?.len(), 3);
+
+    Ok(())
+}
+
+/// Applies an edit and re-lexes, checking the result matches tokenizing the new text from scratch.
+fn relex(text: &str, range: std::ops::Range<usize>, replacement: &str, comments: bool) -> usize {
+    let mut tokens: Vec<OwnedToken> = Tokenizer::new(text)
+        .with_comments(comments)
+        .iter_owned()
+        .collect::<Result<_, _>>()
+        .unwrap();
+    let (new_text, edit) = TextEdit::apply(text, range, replacement);
+
+    let mut tokenizer = Tokenizer::new(&new_text).with_comments(comments);
+    let window = tokenizer.relex(&mut tokens, &edit).unwrap();
+    let expected: Vec<OwnedToken> = Tokenizer::new(&new_text)
+        .with_comments(comments)
+        .iter_owned()
+        .collect::<Result<_, _>>()
+        .unwrap();
+
+    assert_eq!(
+        tokens.len(),
+        expected.len(),
+        "token count after edit of {:?}",
+        text
+    );
+    for (token, expected) in tokens.iter().zip(expected.iter()) {
+        assert_eq!(
+            (token.index, &token.token_type, &token.value),
+            (expected.index, &expected.token_type, &expected.value),
+            "token after edit of {:?}",
+            text
+        );
+    }
+
+    window.inserted
+}
+
+#[test]
+fn incremental() {
+    let text = "a = 1\nb = \"text\"\nc = { d = e }\nf = 2\ng = 3\n";
+
+    // only the edited token is re-lexed
+    assert_eq!(relex(text, 4..5, "100", false), 2);
+    assert_eq!(relex(text, 0..0, "x", false), 1);
+    assert_eq!(relex(text, text.len()..text.len(), "h = 4", false), 4);
+    // joining and splitting tokens
+    relex(text, 1..2, "", false);
+    relex(text, 2..2, " ", false);
+    relex(text, 17..17, " x = y", false);
+    relex(text, 0..text.len(), "", false);
+
+    // opening and closing strings
+    relex("a = 1 b = 2 \"c\"", 4..13, "\"1 b = 2 ", false);
+    relex("a = \"1 b = 2 c\"", 4..13, "1 b = 2 \"", false);
+    relex(text, 10..16, "text \"\"", false);
+    relex("a = \"b\" c = \"d\"", 5..5, "\" x \"", false);
+
+    // opening and closing comments
+    assert_eq!(relex(text, 6..6, "# ", false), 1);
+    relex(text, 16..16, "#", false);
+    relex("a = 1 # b = 2\nc = 3", 6..7, "", false);
+    relex("a = 1 # b = 2\nc = 3", 6..7, "", true);
+    relex(text, 16..16, "#", true);
+
+    // every single character edit that leaves valid text
+    let text = "a = { \"b c\" # d = \"e\"\n f = 1.5 } g = 1444.11.11";
+    for i in 0..=text.len() {
+        for (range, replacement) in [(i..i, "\""), (i..i, "#"), (i..i, " "), (i..i, "\n")]
+            .into_iter()
+            .chain((i < text.len()).then_some((i..i + 1, "")))
+        {
+            let (new_text, _) = TextEdit::apply(text, range.clone(), replacement);
+            for comments in [false, true] {
+                if Tokenizer::new(&new_text)
+                    .with_comments(comments)
+                    .iter_owned()
+                    .collect::<Result<Vec<_>, _>>()
+                    .is_ok()
+                {
+                    relex(text, range.clone(), replacement, comments);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn incremental_errors() {
+    let text = "a = b";
+    let mut tokens = Tokenizer::parse_all(text).unwrap();
+    let (new_text, edit) = TextEdit::apply(text, 4..4, "\"");
+
+    let result = Tokenizer::new(&new_text).relex(&mut tokens, &edit);
+    assert!(result.is_err());
+    assert_eq!(tokens.len(), 3);
+}
 
 #[test]
 fn error_cases() {}
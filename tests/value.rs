@@ -1,4 +1,13 @@
-use clauser::{error::Error, types::Date, value::Value};
+use clauser::{
+    comments::{Comment, Comments},
+    error::{Error, ErrorType},
+    reader::{ParseMode, Reader},
+    template::Template,
+    token::TokenType,
+    types::{CollectionType, Color, ColorSpace, Date, ObjectKey, Operator},
+    value::{MixedEntry, Value},
+    variables::{Variable, Variables},
+};
 
 pub fn single_equal(source: &str, expected: Value<'static>, desc: &str) -> Result<(), Error> {
     assert_eq!(
@@ -26,6 +35,21 @@ pub fn parse_primitives() -> Result<(), Error> {
         "identifier parsing",
     )?;
     single_equal("val = yes", Value::Boolean(true), "boolean parsing")?;
+    single_equal(
+        "val = \"Zürich\"",
+        Value::String("Zürich".into()),
+        "non-ascii string parsing",
+    )?;
+    single_equal(
+        "val = \"He said \\\"hi\\\"\\n\\tC:\\\\\\x\"",
+        Value::String("He said \"hi\"\n\tC:\\\\x".into()),
+        "escaped string parsing",
+    )?;
+    single_equal(
+        "val = Besançon",
+        Value::Identifier("Besançon".into()),
+        "non-ascii identifier parsing",
+    )?;
 
     Ok(())
 }
@@ -116,3 +140,604 @@ pub fn dates() -> Result<(), Error> {
 
     Ok(())
 }
+
+#[test]
+pub fn operators() -> Result<(), Error> {
+    single_equal(
+        "val = { age > 30 has_trait ?= brave a != b c <> d e == 1.5 f >= 0 g <= 1 h < 2 i = 3 }",
+        Value::Object(vec![
+            (
+                "age".into(),
+                Value::Comparison(Operator::GreaterThan, Box::new(Value::Integer(30))),
+            ),
+            (
+                "has_trait".into(),
+                Value::Comparison(
+                    Operator::ExistenceCheck,
+                    Box::new(Value::Identifier("brave".into())),
+                ),
+            ),
+            (
+                "a".into(),
+                Value::Comparison(Operator::NotEquals, Box::new(Value::Identifier("b".into()))),
+            ),
+            (
+                "c".into(),
+                Value::Comparison(Operator::NotEquals, Box::new(Value::Identifier("d".into()))),
+            ),
+            (
+                "e".into(),
+                Value::Comparison(Operator::DoubleEquals, Box::new(Value::Decimal(1.5))),
+            ),
+            (
+                "f".into(),
+                Value::Comparison(Operator::GreaterThanEq, Box::new(Value::Integer(0))),
+            ),
+            (
+                "g".into(),
+                Value::Comparison(Operator::LessThanEq, Box::new(Value::Integer(1))),
+            ),
+            (
+                "h".into(),
+                Value::Comparison(Operator::LessThan, Box::new(Value::Integer(2))),
+            ),
+            ("i".into(), Value::Integer(3)),
+        ]),
+        "comparison operators",
+    )?;
+    single_equal(
+        "val = { limit > { a = 1 } }",
+        Value::Object(vec![(
+            "limit".into(),
+            Value::Comparison(
+                Operator::GreaterThan,
+                Box::new(Value::Object(vec![("a".into(), Value::Integer(1))])),
+            ),
+        )]),
+        "operator before an object",
+    )?;
+
+    Ok(())
+}
+
+#[test]
+pub fn keys() -> Result<(), Error> {
+    single_equal(
+        "val = { 151 = { owner = FRA } -1 = {} \"my key\" = 1 no = { } yes = 2 }",
+        Value::Object(vec![
+            (
+                151.into(),
+                Value::Object(vec![("owner".into(), Value::Identifier("FRA".into()))]),
+            ),
+            (ObjectKey::Integer(-1), Value::Array(vec![])),
+            (ObjectKey::String("my key".into()), Value::Integer(1)),
+            (false.into(), Value::Array(vec![])),
+            (true.into(), Value::Integer(2)),
+        ]),
+        "non-identifier keys",
+    )?;
+
+    assert_eq!(
+        Value::from_str("151 = { 152 = 1 }")?,
+        Value::Object(vec![(
+            151.into(),
+            Value::Object(vec![(152.into(), Value::Integer(1))])
+        )])
+    );
+    assert!(Value::from_str("1.5 = 1").is_err());
+
+    Ok(())
+}
+
+#[test]
+pub fn colors() -> Result<(), Error> {
+    single_equal(
+        "val = rgb { 255 128 0 }",
+        Value::Color(Color::new(ColorSpace::Rgb, [255.0, 128.0, 0.0])),
+        "rgb color",
+    )?;
+    single_equal(
+        "val = hsv { 0.5 0.4 0.9 }",
+        Value::Color(Color::new(ColorSpace::Hsv, [0.5, 0.4, 0.9])),
+        "hsv color",
+    )?;
+    single_equal(
+        "val = hsv360 { 180 40 90 }",
+        Value::Color(Color::new(ColorSpace::Hsv360, [180.0, 40.0, 90.0])),
+        "hsv360 color",
+    )?;
+    single_equal(
+        "val = hex { 0xff8000 }",
+        Value::Color(Color::new(ColorSpace::Hex, [255.0, 128.0, 0.0])),
+        "hex color",
+    )?;
+    single_equal(
+        "val = rgb { 255 128 0 64 }",
+        Value::Color(Color::new(ColorSpace::Rgb, [255.0, 128.0, 0.0]).with_alpha(64.0)),
+        "rgb color with alpha",
+    )?;
+    single_equal(
+        "val = { rgb { 1 2 3 } hsv { 0 0 0 } }",
+        Value::Array(vec![
+            Value::Color(Color::new(ColorSpace::Rgb, [1.0, 2.0, 3.0])),
+            Value::Color(Color::new(ColorSpace::Hsv, [0.0, 0.0, 0.0])),
+        ]),
+        "array of colors",
+    )?;
+    single_equal(
+        "val = { 255 128 0 }",
+        Value::Array(vec![
+            Value::Integer(255),
+            Value::Integer(128),
+            Value::Integer(0),
+        ]),
+        "unprefixed color",
+    )?;
+
+    assert!(Value::from_str("val = rgb { 1 2 }").is_err());
+    assert!(Value::from_str("val = hex { 12345 }").is_err());
+
+    Ok(())
+}
+
+#[test]
+pub fn color_conversions() {
+    let orange = [255, 128, 0];
+    assert_eq!(
+        Color::new(ColorSpace::Rgb, [255.0, 128.0, 0.0]).to_rgb(),
+        orange
+    );
+    assert_eq!(
+        Color::new(ColorSpace::Rgb, [1.0, 0.5, 0.0]).to_rgb(),
+        orange
+    );
+    assert_eq!(Color::from_hex("ff8000").unwrap().to_rgb(), orange);
+    assert_eq!(
+        Color::new(ColorSpace::Hsv, [30.0 / 360.0, 1.0, 1.0]).to_rgb(),
+        [255, 128, 0]
+    );
+    assert_eq!(
+        Color::new(ColorSpace::Hsv360, [30.0, 100.0, 100.0]).to_rgb(),
+        [255, 128, 0]
+    );
+
+    let [h, s, v] = Color::new(ColorSpace::Rgb, [255.0, 0.0, 0.0]).to_hsv();
+    assert_eq!((h, s, v), (0.0, 1.0, 1.0));
+    let [h, _, _] = Color::new(ColorSpace::Rgb, [0.0, 0.0, 255.0]).to_hsv();
+    assert!((h - 2.0 / 3.0).abs() < 1e-9);
+
+    assert_eq!(
+        Color::from_hex("0x11223344"),
+        Some(Color::new(ColorSpace::Hex, [17.0, 34.0, 51.0]).with_alpha(68.0))
+    );
+    assert_eq!(Color::from_hex("fff"), None);
+    assert_eq!(
+        Color::new(ColorSpace::Hsv, [0.5, 0.4, 0.9]).to_string(),
+        "hsv { 0.5 0.4 0.9 }"
+    );
+    assert_eq!(
+        Color::from_hex("ff8000").unwrap().to_string(),
+        "hex { ff8000 }"
+    );
+}
+
+#[test]
+pub fn variables() -> Result<(), Error> {
+    assert_eq!(
+        Value::from_str(
+            "@cost = 100 @name = \"Big \\\"Tower\\\"\" @alias = @cost
+            building = { cost = @cost name = @name refund = { @alias @cost } }"
+        )?,
+        Value::Object(vec![(
+            "building".into(),
+            Value::Object(vec![
+                ("cost".into(), Value::Integer(100)),
+                ("name".into(), Value::String("Big \"Tower\"".into())),
+                (
+                    "refund".into(),
+                    Value::Array(vec![Value::Integer(100), Value::Integer(100)])
+                ),
+            ])
+        )])
+    );
+
+    // definitions inside objects apply to the rest of the file
+    assert_eq!(
+        Value::from_str("a = { @x = 1.5 b = @x } c = @x")?,
+        Value::Object(vec![
+            (
+                "a".into(),
+                Value::Object(vec![("b".into(), Value::Decimal(1.5))])
+            ),
+            ("c".into(), Value::Decimal(1.5)),
+        ])
+    );
+
+    let error = Value::from_str("a = 1\nb = @missing").unwrap_err();
+    assert_eq!(error.error_type, ErrorType::UndefinedVariableError);
+    assert_eq!(error.location(), Some((2, 5)));
+
+    // values of variables are parsed the same way as values written out in full
+    assert_eq!(
+        Value::from_str("@date = 1444.11.11.12 @n = 5 start = @date n = @n")?,
+        Value::from_str("start = 1444.11.11.12 n = 5")?
+    );
+    for text in ["a = 1444.99999999999.1", "@d = 1444.99999999999.1 a = @d"] {
+        let error = Value::from_str(text).unwrap_err();
+        assert_eq!(error.error_type, ErrorType::InvalidNumberError, "{}", text);
+    }
+
+    Ok(())
+}
+
+#[test]
+pub fn shared_variables() -> Result<(), Error> {
+    let mut variables = Variables::parse("@cost = 100\n@date = 1444.11.11")?;
+    assert_eq!(
+        variables.get("@cost"),
+        Some(&Variable::new(TokenType::Number, "100"))
+    );
+    variables.merge(Variables::parse("@tag = FRA @cost = 50")?);
+    assert_eq!(variables.len(), 3);
+
+    let text = "start = @date owner = @tag cost = @cost @cost = 10 local = @cost";
+    let mut reader = Reader::new(text).with_variables(variables);
+    assert_eq!(
+        Value::from_reader(&mut reader)?,
+        Value::Object(vec![
+            ("start".into(), Value::Date(Date::new(1444, 11, 11, 0))),
+            ("owner".into(), Value::Identifier("FRA".into())),
+            ("cost".into(), Value::Integer(50)),
+            ("local".into(), Value::Integer(10)),
+        ])
+    );
+    assert_eq!(
+        reader.variables().get("cost"),
+        Some(&Variable::new(TokenType::Number, "10"))
+    );
+
+    Ok(())
+}
+
+#[test]
+pub fn inline_math() -> Result<(), Error> {
+    assert_eq!(
+        Value::from_str(
+            "@base_cost = 10 @half = @[ base_cost / 4 ]
+            cost = @[ base_cost * 2 + 5 ] half = @half list = { @[@base_cost - 1] 3 }"
+        )?,
+        Value::Object(vec![
+            ("cost".into(), Value::Integer(25)),
+            ("half".into(), Value::Decimal(2.5)),
+            (
+                "list".into(),
+                Value::Array(vec![Value::Integer(9), Value::Integer(3)])
+            ),
+        ])
+    );
+
+    let error = Value::from_str("a = @[ 2 * missing ]").unwrap_err();
+    assert_eq!(error.error_type, ErrorType::UndefinedVariableError);
+    assert_eq!(error.location(), Some((1, 12)));
+
+    let error = Value::from_str("a = @[ 2 / 0 ]").unwrap_err();
+    assert_eq!(error.error_type, ErrorType::ExpressionError);
+    assert_eq!(error.location(), Some((1, 10)));
+
+    let text = format!("a = @[{}1{}]", "(".repeat(100_000), ")".repeat(100_000));
+    let error = Value::from_str(&text).unwrap_err();
+    assert_eq!(error.error_type, ErrorType::ExpressionError);
+
+    Ok(())
+}
+
+fn read_templates(text: &str) -> Result<Value<'_>, Error> {
+    Value::from_reader(&mut Reader::new(text).with_templates(true))
+}
+
+#[test]
+pub fn templates() -> Result<(), Error> {
+    let value = read_templates(
+        "my_effect = { add_gold = $AMOUNT$ [[BONUS] add_prestige = $BONUS$ ] }
+        plain = { a = b }",
+    )?;
+    assert_eq!(
+        value,
+        Value::Object(vec![
+            (
+                "my_effect".into(),
+                Value::Template(Template::parse(
+                    " add_gold = $AMOUNT$ [[BONUS] add_prestige = $BONUS$ ] "
+                )?)
+            ),
+            (
+                "plain".into(),
+                Value::Object(vec![("a".into(), Value::Identifier("b".into()))])
+            ),
+        ])
+    );
+
+    let template = match &value {
+        Value::Object(entries) => match &entries[0].1 {
+            Value::Template(template) => template,
+            _ => unreachable!(),
+        },
+        _ => unreachable!(),
+    };
+    assert_eq!(
+        template.expand_value(&[("AMOUNT", "10")])?,
+        Value::Object(vec![("add_gold".into(), Value::Integer(10))])
+    );
+
+    let error = read_templates("a = {\n [[B] b = c }").unwrap_err();
+    assert_eq!(error.error_type, ErrorType::ParameterError);
+    assert_eq!(error.location(), Some((2, 2)));
+
+    // the outermost block containing a parameter is the template
+    let text = "k_$TITLE$ = { a = b }
+        effect = { if = { limit = { $COND$ = yes } } }
+        after = 1";
+    assert_eq!(
+        read_templates(text)?,
+        Value::Object(vec![
+            (
+                "k_$TITLE$".into(),
+                Value::Object(vec![("a".into(), Value::Identifier("b".into()))])
+            ),
+            (
+                "effect".into(),
+                Value::Template(Template::parse(" if = { limit = { $COND$ = yes } } ")?)
+            ),
+            ("after".into(), Value::Integer(1)),
+        ])
+    );
+
+    let mut reader = Reader::new("{ a = { [[B] b = c ] } } { d = e }").with_templates(true);
+    assert_eq!(
+        Value::next_from_reader(&mut reader)?,
+        Value::Template(Template::parse(" a = { [[B] b = c ] } ")?)
+    );
+    assert_eq!(
+        Value::next_from_reader(&mut reader)?,
+        Value::Object(vec![("d".into(), Value::Identifier("e".into()))])
+    );
+
+    // parameters are only found in identifiers and keys, never in strings
+    let text = r#"countries = { FRA = { name = "Cost $5" gold = 1 } }"#;
+    assert_eq!(read_templates(text)?, Value::from_str(text)?);
+    assert!(matches!(
+        &read_templates(text)?,
+        Value::Object(entries) if matches!(entries[0].1, Value::Object(_))
+    ));
+
+    // templates aren't detected unless enabled
+    assert_eq!(
+        Value::from_str("a = { b = $X$ }")?,
+        Value::Object(vec![(
+            "a".into(),
+            Value::Object(vec![("b".into(), Value::Identifier("$X$".into()))])
+        )])
+    );
+
+    Ok(())
+}
+
+#[test]
+pub fn lenient() -> Result<(), Error> {
+    let (value, diagnostics) =
+        Value::from_str_lenient("a = 1\n} b = 2\nc d = { e = ^ f = 3 }\ng = { h = 4");
+    assert_eq!(
+        value,
+        Value::Object(vec![
+            ("a".into(), Value::Integer(1)),
+            ("b".into(), Value::Integer(2)),
+            ("c".into(), Value::Identifier("d".into())),
+            (
+                "g".into(),
+                Value::Object(vec![("h".into(), Value::Integer(4))])
+            ),
+        ])
+    );
+    let errors: Vec<_> = diagnostics
+        .iter()
+        .map(|e| (e.error_type.clone(), e.location()))
+        .collect();
+    assert_eq!(
+        errors,
+        vec![
+            (ErrorType::UnexpectedTokenError, Some((2, 1))),
+            (ErrorType::UnexpectedTokenError, Some((3, 3))),
+            (ErrorType::UnexpectedTokenError, Some((3, 5))),
+            (ErrorType::TokenizerError, Some((3, 13))),
+            (ErrorType::UnexpectedTokenError, Some((4, 11))),
+        ]
+    );
+
+    // every problem is reported, and values that can't be read are skipped
+    let mut reader =
+        Reader::new("a = { 1 2 = } b = \"x\" c = @undefined").with_mode(ParseMode::Lenient);
+    let value = Value::from_reader(&mut reader)?;
+    assert_eq!(
+        value,
+        Value::Object(vec![
+            (
+                "a".into(),
+                Value::Array(vec![Value::Integer(1), Value::Integer(2)])
+            ),
+            ("b".into(), Value::String("x".into())),
+        ])
+    );
+    assert_eq!(reader.diagnostics().len(), 2);
+    assert_eq!(
+        reader.diagnostics()[1].error_type,
+        ErrorType::UndefinedVariableError
+    );
+
+    // strict mode still stops at the first error
+    assert!(Value::from_str("a = 1 } b = 2").is_err());
+
+    Ok(())
+}
+
+#[test]
+pub fn comments() -> Result<(), Error> {
+    let text = "# file header
+
+## cardinality = 0..1
+### The building's cost.
+cost = 100 # in gold
+modifiers = {
+    # leading
+    tax = 0.5 # trailing
+    # dangling
+} # after modifiers
+list = { a # first
+    b }
+effect = { add_gold = $AMOUNT$ # kept in template
+}
+# end of file
+";
+    let mut reader = Reader::new(text).with_comments(true).with_templates(true);
+    let (value, comments) = Value::from_reader_with_comments(&mut reader)?;
+    assert_eq!(value, read_templates(text)?);
+
+    let contents = |comments: &Vec<Comment>| -> Vec<String> {
+        comments.iter().map(|c| c.text.to_string()).collect()
+    };
+    let trailing = |comments: &Comments| comments.trailing.as_ref().map(|c| c.text.to_string());
+
+    assert_eq!(comments.entries.len(), 4);
+    assert_eq!(
+        contents(&comments.entries[0].leading),
+        vec![
+            "# file header",
+            "## cardinality = 0..1",
+            "### The building's cost."
+        ]
+    );
+    assert_eq!(comments.entries[0].leading[1].level(), 2);
+    assert_eq!(
+        comments.entries[0].leading[2].content(),
+        "The building's cost."
+    );
+    assert_eq!(trailing(&comments.entries[0]), Some("# in gold".to_owned()));
+
+    let modifiers = comments.entry(1).unwrap();
+    assert!(modifiers.leading.is_empty());
+    assert_eq!(trailing(modifiers), Some("# after modifiers".to_owned()));
+    assert_eq!(contents(&modifiers.entries[0].leading), vec!["# leading"]);
+    assert_eq!(
+        trailing(&modifiers.entries[0]),
+        Some("# trailing".to_owned())
+    );
+    assert_eq!(contents(&modifiers.dangling), vec!["# dangling"]);
+
+    let list = comments.entry(2).unwrap();
+    assert_eq!(list.entries.len(), 2);
+    assert_eq!(trailing(&list.entries[0]), Some("# first".to_owned()));
+    assert!(list.entries[1].is_empty());
+
+    // comments inside templates are part of the template's text
+    assert!(comments.entry(3).unwrap().is_empty());
+    assert_eq!(contents(&comments.dangling), vec!["# end of file"]);
+
+    // comments are ignored unless enabled
+    let mut reader = Reader::new(text);
+    let (_, comments) = Value::from_reader_with_comments(&mut reader)?;
+    assert!(comments.is_empty());
+
+    Ok(())
+}
+
+#[test]
+pub fn mixed_collections() -> Result<(), Error> {
+    // positional and keyed entries keep their order
+    assert_eq!(
+        Value::from_str("val = { 1 2 3 a = b }")?,
+        Value::Object(vec![(
+            "val".into(),
+            Value::Mixed(vec![
+                MixedEntry::Item(Value::Integer(1)),
+                MixedEntry::Item(Value::Integer(2)),
+                MixedEntry::Item(Value::Integer(3)),
+                MixedEntry::Property("a".into(), Value::Identifier("b".into())),
+            ])
+        )])
+    );
+
+    // bare flags after properties aren't an error
+    let value = Value::from_str("val = { key = 1 flag other >= 2 { 3 } }")?;
+    let Value::Object(entries) = &value else {
+        panic!("expected object, found {:?}", value);
+    };
+    assert_eq!(entries[0].1.collection_type(), Some(CollectionType::Mixed));
+    assert_eq!(
+        entries[0].1,
+        Value::Mixed(vec![
+            MixedEntry::Property("key".into(), Value::Integer(1)),
+            MixedEntry::Item(Value::Identifier("flag".into())),
+            MixedEntry::Property(
+                "other".into(),
+                Value::Comparison(Operator::GreaterThanEq, Box::new(Value::Integer(2)))
+            ),
+            MixedEntry::Item(Value::Array(vec![Value::Integer(3)])),
+        ])
+    );
+
+    // collections that don't mix are unchanged
+    single_equal(
+        "val = { a b }",
+        Value::Array(vec![
+            Value::Identifier("a".into()),
+            Value::Identifier("b".into()),
+        ]),
+        "array",
+    )?;
+    single_equal(
+        "val = { a = b }",
+        Value::Object(vec![("a".into(), Value::Identifier("b".into()))]),
+        "object",
+    )?;
+
+    assert_eq!(
+        Value::from_entries(vec![MixedEntry::Item(Value::Integer(1))]),
+        Value::Array(vec![Value::Integer(1)])
+    );
+    assert_eq!(
+        Value::from_entries(vec![]).collection_type(),
+        Some(CollectionType::Array)
+    );
+    assert_eq!(Value::Integer(1).collection_type(), None);
+
+    Ok(())
+}
+
+#[test]
+pub fn ordering() {
+    use std::cmp::Ordering;
+
+    let a = Value::Array(vec![Value::Integer(1), Value::Integer(2)]);
+    assert!(Value::Integer(1) < Value::Integer(2));
+    assert!(Value::Integer(5) < Value::Decimal(1.0));
+    assert!(Value::Array(vec![Value::Integer(1)]) < a);
+    assert!(a < Value::Array(vec![Value::Integer(2)]));
+    assert_eq!(a.partial_cmp(&a.clone()), Some(Ordering::Equal));
+    assert_eq!(
+        Value::Decimal(f64::NAN).partial_cmp(&Value::Decimal(1.0)),
+        None
+    );
+
+    // keys are compared before their values, and items come before properties
+    assert!(
+        Value::Object(vec![("a".into(), Value::Integer(2))])
+            < Value::Object(vec![("b".into(), Value::Integer(1))])
+    );
+    assert!(
+        Value::Mixed(vec![MixedEntry::Item(Value::Integer(2))])
+            < Value::Mixed(vec![MixedEntry::Property("a".into(), Value::Integer(1))])
+    );
+    assert!(
+        Value::Comparison(Operator::Equals, Box::new(Value::Integer(2)))
+            < Value::Comparison(Operator::LessThan, Box::new(Value::Integer(1)))
+    );
+}